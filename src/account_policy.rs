@@ -0,0 +1,232 @@
+use std::fmt;
+use crate::config;
+
+// bcrypt only looks at the first 72 bytes of a password
+const MAX_PASSWORD_BYTES: usize = 72;
+
+// longest display name, bio and location in characters
+const PROFILE_FIELD_LIMITS: [(&str, usize); 3] = [("display name", 30), ("bio", 160), ("location", 40)];
+
+
+/**
+* A username or password that breaks the registration rules in the `[usernames]` and
+* `[passwords]` config sections, or a profile field that is too long.
+* `Display` is the message shown to the user
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    UsernameLength { min: usize, max: usize },
+    UsernameCharacters(String),
+    UsernameReserved,
+    PasswordTooShort(usize),
+    PasswordTooLong,
+    // fewer kinds of characters than `passwords.min_classes`
+    PasswordTooSimple(usize),
+    PasswordIsUsername,
+    PasswordMismatch,
+    ProfileFieldTooLong { field: &'static str, max: usize },
+    // a display name that is another user's username or display name, or a reserved name
+    DisplayNameTaken,
+    // terminal control characters would let a profile redraw other users' screens
+    ProfileFieldCharacters(&'static str),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::UsernameLength { min, max } => write!(f, "Usernames must be {} to {} characters long.", min, max),
+            PolicyError::UsernameCharacters(symbols) if symbols.is_empty() => {
+                write!(f, "Usernames must start with a letter and only use letters and digits.")
+            }
+            PolicyError::UsernameCharacters(symbols) => {
+                write!(f, "Usernames must start with a letter and only use letters, digits and {}", symbols)
+            }
+            PolicyError::UsernameReserved => write!(f, "That username is reserved, please pick another."),
+            PolicyError::PasswordTooShort(min) => write!(f, "Passwords must be at least {} characters long.", min),
+            PolicyError::PasswordTooLong => write!(f, "Passwords can be at most {} bytes long.", MAX_PASSWORD_BYTES),
+            PolicyError::PasswordTooSimple(classes) => write!(
+                f,
+                "Passwords need at least {} of: lowercase letters, uppercase letters, digits, symbols.",
+                classes
+            ),
+            PolicyError::PasswordIsUsername => write!(f, "Your password can't contain your username."),
+            PolicyError::PasswordMismatch => write!(f, "The passwords did not match."),
+            PolicyError::DisplayNameTaken => write!(f, "That display name is another user's name or reserved, please pick another."),
+            PolicyError::ProfileFieldTooLong { field, max } => write!(f, "Your {} can be at most {} characters long.", field, max),
+            PolicyError::ProfileFieldCharacters(field) => write!(f, "Your {} can't contain control characters.", field),
+        }
+    }
+}
+
+
+/**
+* checks a new username against the length, character and reserved name rules,
+* taken names are checked by `Manager::create_user`
+*/
+pub fn check_username(username: &str) -> Result<(), PolicyError> {
+    check_username_format(username)?;
+    let rules = &config::get().usernames;
+    if rules.reserved.iter().any(|name| name.eq_ignore_ascii_case(username)) {
+        return Err(PolicyError::UsernameReserved);
+    }
+    Ok(())
+}
+
+
+/**
+* the length and character rules only, reserved names are still allowed for the sysop
+* account created from the command line
+*/
+pub fn check_username_format(username: &str) -> Result<(), PolicyError> {
+    let rules = &config::get().usernames;
+    let length = username.chars().count();
+    if length < rules.min_length || length > rules.max_length {
+        return Err(PolicyError::UsernameLength { min: rules.min_length, max: rules.max_length });
+    }
+
+    let starts_with_letter = username.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+    let allowed = username.chars().all(|c| c.is_ascii_alphanumeric() || rules.allowed_symbols.contains(c));
+    if !starts_with_letter || !allowed {
+        return Err(PolicyError::UsernameCharacters(rules.allowed_symbols.clone()));
+    }
+    Ok(())
+}
+
+
+/**
+* checks a new password for `username` against the length and strength rules
+*/
+pub fn check_password(username: &str, password: &str) -> Result<(), PolicyError> {
+    let rules = &config::get().passwords;
+    if password.chars().count() < rules.min_length {
+        return Err(PolicyError::PasswordTooShort(rules.min_length));
+    }
+    if password.len() > MAX_PASSWORD_BYTES {
+        return Err(PolicyError::PasswordTooLong);
+    }
+
+    // lowercase, uppercase, digits and everything else each count as one class
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    if classes.iter().filter(|present| **present).count() < rules.min_classes {
+        return Err(PolicyError::PasswordTooSimple(rules.min_classes));
+    }
+
+    if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+        return Err(PolicyError::PasswordIsUsername);
+    }
+    Ok(())
+}
+
+
+/**
+* checks a display name against the profile field rules and the reserved usernames, names of
+* other users are checked by `Manager::update_profile`
+*/
+pub fn check_display_name(display_name: &str) -> Result<(), PolicyError> {
+    check_profile_field("display name", display_name)?;
+    let rules = &config::get().usernames;
+    if rules.reserved.iter().any(|name| name.eq_ignore_ascii_case(display_name.trim())) {
+        return Err(PolicyError::DisplayNameTaken);
+    }
+    Ok(())
+}
+
+
+/**
+* checks a profile `field` ("display name", "bio" or "location") before it is saved
+*/
+pub fn check_profile_field(field: &str, value: &str) -> Result<(), PolicyError> {
+    let (field, max) = PROFILE_FIELD_LIMITS
+        .iter()
+        .find(|(name, _)| *name == field)
+        .copied()
+        .expect("unknown profile field");
+    if value.trim().chars().count() > max {
+        return Err(PolicyError::ProfileFieldTooLong { field, max });
+    }
+    if value.chars().any(|c| c.is_control()) {
+        return Err(PolicyError::ProfileFieldCharacters(field));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // defaults: usernames 3 to 20 characters with "_-.", passwords of 8 with 2 classes
+
+    #[test]
+    fn username_length() {
+        config::init_defaults();
+        let too_short = Err(PolicyError::UsernameLength { min: 3, max: 20 });
+        assert_eq!(check_username("ab"), too_short);
+        assert_eq!(check_username("abc"), Ok(()));
+        assert_eq!(check_username(&"a".repeat(20)), Ok(()));
+        assert_eq!(check_username(&"a".repeat(21)), too_short);
+    }
+
+    #[test]
+    fn username_characters() {
+        config::init_defaults();
+        let bad = Err(PolicyError::UsernameCharacters("_-.".to_string()));
+        assert_eq!(check_username("alice_b-c.d9"), Ok(()));
+        assert_eq!(check_username("9alice"), bad);
+        assert_eq!(check_username("_alice"), bad);
+        assert_eq!(check_username("ali ce"), bad);
+        assert_eq!(check_username("alice!"), bad);
+        assert_eq!(check_username("alicé"), bad);
+    }
+
+    #[test]
+    fn reserved_usernames_ignore_case() {
+        config::init_defaults();
+        assert_eq!(check_username("SysOp"), Err(PolicyError::UsernameReserved));
+        assert_eq!(check_username("sysops"), Ok(()));
+        // the format check alone lets the sysop account be created from the command line
+        assert_eq!(check_username_format("sysop"), Ok(()));
+    }
+
+    #[test]
+    fn password_length() {
+        config::init_defaults();
+        assert_eq!(check_password("alice", "Short1"), Err(PolicyError::PasswordTooShort(8)));
+        assert_eq!(check_password("alice", "Longer12"), Ok(()));
+        assert_eq!(check_password("alice", &"Aa1".repeat(25)), Err(PolicyError::PasswordTooLong));
+    }
+
+    #[test]
+    fn password_classes() {
+        config::init_defaults();
+        let too_simple = Err(PolicyError::PasswordTooSimple(2));
+        assert_eq!(check_password("alice", "onlylowercase"), too_simple);
+        assert_eq!(check_password("alice", "ONLYUPPERCASE"), too_simple);
+        assert_eq!(check_password("alice", "1234567890"), too_simple);
+        assert_eq!(check_password("alice", "!@#$%^&*()"), too_simple);
+        assert_eq!(check_password("alice", "lowercase1"), Ok(()));
+        assert_eq!(check_password("alice", "lower case"), Ok(()));
+        assert_eq!(check_password("alice", "UPPER!CASE"), Ok(()));
+    }
+
+    #[test]
+    fn password_cannot_contain_the_username() {
+        config::init_defaults();
+        assert_eq!(check_password("alice", "myALICE123"), Err(PolicyError::PasswordIsUsername));
+        assert_eq!(check_password("", "lowercase1"), Ok(()));
+    }
+
+    #[test]
+    fn display_names() {
+        config::init_defaults();
+        assert_eq!(check_display_name("Alice B."), Ok(()));
+        assert_eq!(check_display_name(" Admin "), Err(PolicyError::DisplayNameTaken));
+        assert_eq!(check_display_name(&"x".repeat(31)), Err(PolicyError::ProfileFieldTooLong { field: "display name", max: 30 }));
+        assert_eq!(check_display_name("a\x1b[2Jb"), Err(PolicyError::ProfileFieldCharacters("display name")));
+    }
+}
@@ -1,90 +1,126 @@
-use std::io::Write;
-use std::net::TcpStream;
-use std::sync::{Arc, Mutex};
-// use serde::{Deserialize};
-use serde_json::{Value};
-use serde_json;
+use serde::{Deserialize, Serialize};
 
+use crate::connection::output_status_line;
+use crate::hub::{Subscription, Topic};
 use crate::input_interface::UserInterface;
+use crate::presence::{self, SessionId};
+use crate::views::direct_message_view::DirectMessageView;
 
-// #[derive(Deserialize, Debug)]
-// struct UserEvent {
-//     user_id: i32,
-//     event_type: String
-// }
-//
-// #[derive(Deserialize, Debug)]
-// struct UserDirectMessageEvent {
-//     sender_id: i32,
-//     receiver_id: i32,
-//     message: String
-// }
-//
-//
-// #[derive(Deserialize, Debug)]
-// struct RoomJoinEvent {
-//     user_id: i32,
-//     room_id: i32
-// }
-//
-// #[derive(Deserialize, Debug)]
-// struct RoomLeaveEvent {
-//     user_id: i32,
-//     room_id: i32
-// }
-//
-// #[derive(Deserialize, Debug)]
-// struct RoomExitEvent {
-//     user_id: i32,
-//     room_id: i32
-// }
-//
-// #[derive(Deserialize, Debug)]
-// struct RoomMessageEvent {
-//     user_id: i32,
-//     room_id: i32,
-//     message: String,
-// }
 
+/**
+* Events shared between client sessions over the broadcast channels
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event_type", rename_all = "snake_case")]
+pub enum BroadcastEvent {
+    // a session logged in as the user
+    UserLogin { user_id: i32 },
+    // one of the user's sessions closed, they may still be online from another
+    Logout { user_id: i32 },
+    // another session of the same user or a sysop asked this one to close
+    EndSession { session_id: SessionId, message: String },
+    AnonLogout,
+    RoomJoin { user_id: i32, room_id: i32 },
+    RoomLeave { user_id: i32, room_id: i32 },
+    RoomMessage { room_id: i32 },
+    // `username` is the sender, for the notification shown to the recipient
+    DirectMessage { user_id: i32, to_user_id: i32, username: String },
+    // a sysop renamed the room or purged its messages
+    RoomChanged { room_id: i32 },
+    RoomDeleted { room_id: i32 },
+    // a room moderator kicked or banned the user, presence was already updated
+    RoomKick { room_id: i32, user_id: i32, message: String },
+    // a sysop message for everyone online
+    Announcement { message: String },
+}
 
 
-pub fn handle_broadcast_event(raw_event: String, ui: &Arc<Mutex<UserInterface>>, s_ref: &Arc<Mutex<TcpStream>>) -> i32 {
+/**
+* re-renders the current view of the session after refreshing its data
+*/
+pub fn refresh_current_view(interface: &mut UserInterface, output: &mut Vec<u8>) {
+    interface.get_current_view().lock().unwrap().refresh_data();
+    output.extend_from_slice(interface.render_current_view().as_bytes());
+}
 
-    let json: Value = serde_json::from_str(&*raw_event).unwrap_or(Value::Null);
-    if json.is_null() {
-        return 0;
-    }
-    let mut interface = ui.lock().unwrap();
-
-    if json["event_type"] == "room_message" || json["event_type"] == "room_leave"  {
-        let mut stream = s_ref.lock().unwrap();
-        if json["room_id"] == interface.get_current_room_id() {
-            let binding = interface.get_current_view();
-            let mut view = binding.lock().unwrap();
-            view.refresh_data();
-            let updated_view = view.render();
-            stream.write_all(updated_view.as_bytes()).unwrap();
-            stream.flush().unwrap();
 
-        }
-    }
-    else if json["event_type"] == "logout" && json["user_id"] == interface.get_user_id() {
-        return -1;
-    }
+/**
+* sends a session that was removed from its room back to the room list with `notice`,
+* it no longer hears about the room
+*/
+fn remove_from_room(interface: &mut UserInterface, subscription: &Subscription, room_id: i32, notice: &str, output: &mut Vec<u8>) {
+    subscription.leave(Topic::Room(room_id));
+    interface.return_to_rooms(notice);
+    output.extend_from_slice(interface.render_current_view().as_bytes());
+}
 
 
-    else if json["event_type"] == "direct_message" && (json["user_id"] == interface.get_user_id() || json["to_user_id"] == interface.get_user_id()) {
-        let mut stream = s_ref.lock().unwrap();
-        let binding = interface.get_current_view();
-        let mut view = binding.lock().unwrap();
-        view.refresh_data();
-        let updated_view = view.render();
-        println!("TcpStream is locked and ready to write.");
-        stream.write_all(updated_view.as_bytes()).unwrap();
-        stream.flush().unwrap();
+/**
+* updates the session for an event from another session, the re-rendered view is queued in `output`.
+* returns -1 when the session should be closed
+*/
+pub fn handle_broadcast_event(event: BroadcastEvent, interface: &mut UserInterface, subscription: &Subscription, output: &mut Vec<u8>) -> i32 {
+    match event {
+        BroadcastEvent::RoomMessage { room_id } => {
+            if room_id == interface.get_current_room_id() {
+                refresh_current_view(interface, output);
+            }
+        }
+        // updates the who's here line of the room and the online counts of room lists
+        BroadcastEvent::RoomJoin { room_id, .. } | BroadcastEvent::RoomLeave { room_id, .. } | BroadcastEvent::RoomChanged { room_id } => {
+            let follows_presence = interface.get_current_view().lock().unwrap().follows_presence();
+            if room_id == interface.get_current_room_id() || follows_presence {
+                refresh_current_view(interface, output);
+            }
+        }
+        // online status, the "My sessions" screen and the admin session list follow logins and logouts
+        BroadcastEvent::UserLogin { .. } | BroadcastEvent::Logout { .. } | BroadcastEvent::AnonLogout => {
+            if interface.get_current_view().lock().unwrap().follows_presence() {
+                refresh_current_view(interface, output);
+            }
+        }
+        BroadcastEvent::EndSession { session_id, message } => {
+            if session_id == interface.get_session_id() {
+                output.extend_from_slice(format!("\r\n\x1b[1;31m{}\x1b[0m\r\n", message).as_bytes());
+                return -1;
+            }
+        }
+        BroadcastEvent::RoomDeleted { room_id } => {
+            if room_id == interface.get_current_room_id() {
+                presence::leave_room(interface.get_session_id());
+                remove_from_room(interface, subscription, room_id, "The room you were in was deleted by a sysop.", output);
+            }
+            else if interface.get_current_view().lock().unwrap().follows_presence() {
+                refresh_current_view(interface, output);
+            }
+        }
+        BroadcastEvent::RoomKick { room_id, user_id, message } => {
+            if user_id == interface.get_user_id() && room_id == interface.get_current_room_id() {
+                remove_from_room(interface, subscription, room_id, &message, output);
+            }
+        }
+        BroadcastEvent::Announcement { message } => {
+            output_status_line(output, interface, &format!("📢 {}", message));
+        }
+        BroadcastEvent::DirectMessage { user_id, to_user_id, username } => {
+            let me = interface.get_user_id();
+            let other_user_id = if user_id == me { to_user_id } else { user_id };
+            let in_conversation = interface.get_current_view().lock().unwrap()
+                .as_any()
+                .downcast_ref::<DirectMessageView>()
+                .is_some_and(|view| view.to_user_id() == other_user_id);
+            if in_conversation {
+                refresh_current_view(interface, output);
+            }
+            else if to_user_id == me {
+                // list views pick up the unread count, a view taking input keeps what is being typed
+                if !interface.is_in_input_mode() {
+                    refresh_current_view(interface, output);
+                }
+                output_status_line(output, interface, &format!("✉ New message from {}", username));
+            }
+        }
     }
 
     0
-
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use crate::hub::SlowConsumerPolicy;
+
+// config file used when no --config / RBBS_CONFIG is given, it is optional
+const DEFAULT_CONFIG_PATH: &str = "rbbs.toml";
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+
+/**
+* Settings that can be overridden from the environment and the command line,
+* as (config key, command line flag, environment variable)
+*/
+const SETTINGS: [(&str, &str, &str); 26] = [
+    ("server.listen", "--listen", "RBBS_LISTEN"),
+    ("database.path", "--database", "RBBS_DATABASE"),
+    ("database.pool_size", "--db-pool-size", "RBBS_DB_POOL_SIZE"),
+    ("database.busy_timeout_ms", "--db-busy-timeout-ms", "RBBS_DB_BUSY_TIMEOUT_MS"),
+    ("display.max_page_size", "--max-page-size", "RBBS_MAX_PAGE_SIZE"),
+    ("display.password_mask", "--password-mask", "RBBS_PASSWORD_MASK"),
+    ("passwords.bcrypt_cost", "--bcrypt-cost", "RBBS_BCRYPT_COST"),
+    ("passwords.min_length", "--password-min-length", "RBBS_PASSWORD_MIN_LENGTH"),
+    ("passwords.min_classes", "--password-min-classes", "RBBS_PASSWORD_MIN_CLASSES"),
+    ("usernames.min_length", "--username-min-length", "RBBS_USERNAME_MIN_LENGTH"),
+    ("usernames.max_length", "--username-max-length", "RBBS_USERNAME_MAX_LENGTH"),
+    ("usernames.allowed_symbols", "--username-symbols", "RBBS_USERNAME_SYMBOLS"),
+    ("usernames.reserved", "--reserved-usernames", "RBBS_RESERVED_USERNAMES"),
+    ("banner.title", "--banner", "RBBS_BANNER"),
+    ("banner.motd", "--motd", "RBBS_MOTD"),
+    ("limits.max_connections", "--max-connections", "RBBS_MAX_CONNECTIONS"),
+    ("limits.max_connections_per_ip", "--max-connections-per-ip", "RBBS_MAX_CONNECTIONS_PER_IP"),
+    ("limits.idle_timeout_secs", "--idle-timeout", "RBBS_IDLE_TIMEOUT"),
+    ("limits.login_timeout_secs", "--login-timeout", "RBBS_LOGIN_TIMEOUT"),
+    ("limits.keepalive_secs", "--keepalive", "RBBS_KEEPALIVE"),
+    ("limits.subscriber_queue_size", "--subscriber-queue-size", "RBBS_SUBSCRIBER_QUEUE_SIZE"),
+    ("limits.slow_consumer_policy", "--slow-consumer-policy", "RBBS_SLOW_CONSUMER_POLICY"),
+    ("login.backoff_after", "--login-backoff-after", "RBBS_LOGIN_BACKOFF_AFTER"),
+    ("login.max_backoff_secs", "--login-max-backoff", "RBBS_LOGIN_MAX_BACKOFF"),
+    ("login.lockout_after", "--login-lockout-after", "RBBS_LOGIN_LOCKOUT_AFTER"),
+    ("login.lockout_secs", "--login-lockout-secs", "RBBS_LOGIN_LOCKOUT_SECS"),
+];
+
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, std::io::Error),
+    Parse(String, toml::de::Error),
+    Invalid(String),
+    Usage(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "unable to read config file {}: {}", path, e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path, e),
+            ConfigError::Invalid(message) => write!(f, "{}", message),
+            ConfigError::Usage(message) => write!(f, "{}\n\n{}", message, usage()),
+        }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { listen: vec!["0.0.0.0:2323".to_string()] }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub path: String,
+    // connections kept open and shared by all sessions
+    pub pool_size: usize,
+    // how long a query waits on a locked database or a busy pool before giving up
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self { path: "bbs.db".to_string(), pool_size: 8, busy_timeout_ms: 5000 }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    // most rows loaded for one page of rooms, users or messages, however tall the terminal is
+    pub max_page_size: usize,
+    // shown for every character of a password as it is typed, empty shows nothing
+    pub password_mask: String,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self { max_page_size: 100, password_mask: "*".to_string() }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordConfig {
+    pub bcrypt_cost: u32,
+    pub min_length: usize,
+    // kinds of characters a new password needs, out of lowercase, uppercase, digits and symbols
+    pub min_classes: usize,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self { bcrypt_cost: bcrypt::DEFAULT_COST, min_length: 8, min_classes: 2 }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsernameConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    // allowed besides ascii letters and digits
+    pub allowed_symbols: String,
+    // names nobody can register, compared ignoring case
+    pub reserved: Vec<String>,
+}
+
+impl Default for UsernameConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 20,
+            allowed_symbols: "_-.".to_string(),
+            reserved: ["sysop", "admin", "administrator", "root", "system", "guest"].iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BannerConfig {
+    pub title: String,
+    pub motd: String,
+}
+
+impl Default for BannerConfig {
+    fn default() -> Self {
+        Self { title: "Welcome to Friendly Automations Rust BBS!".to_string(), motd: String::new() }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    // 0 allows any number of connections
+    pub max_connections: usize,
+    // connections open at once from one ip address, 0 allows any number
+    pub max_connections_per_ip: usize,
+    // seconds a logged in session can go without input, 0 never times out
+    pub idle_timeout_secs: u64,
+    // seconds a connection has to log in, 0 never times out
+    pub login_timeout_secs: u64,
+    // seconds of silence from the client before a keepalive is sent, 0 sends none
+    pub keepalive_secs: u64,
+    pub subscriber_queue_size: usize,
+    pub slow_consumer_policy: SlowConsumerPolicy,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 0,
+            max_connections_per_ip: 5,
+            idle_timeout_secs: 1800,
+            login_timeout_secs: 120,
+            keepalive_secs: 60,
+            subscriber_queue_size: 64,
+            slow_consumer_policy: SlowConsumerPolicy::DropEvent,
+        }
+    }
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoginConfig {
+    // failed logins for an account or ip before each attempt has to wait, 0 never waits
+    pub backoff_after: u32,
+    // the wait doubles with every further failure up to this many seconds
+    pub max_backoff_secs: u64,
+    // failed logins before the account is locked, 0 never locks
+    pub lockout_after: u32,
+    // seconds an account stays locked, failures older than this are forgotten
+    pub lockout_secs: u64,
+}
+
+impl Default for LoginConfig {
+    fn default() -> Self {
+        Self { backoff_after: 3, max_backoff_secs: 300, lockout_after: 10, lockout_secs: 900 }
+    }
+}
+
+
+/**
+* What the binary does once the config is loaded
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Command {
+    #[default]
+    Serve,
+    // --migrate-only, update the database schema and exit
+    MigrateOnly,
+    // --failed-logins, print the most recent failed logins and exit
+    FailedLogins,
+    // --create-sysop, create the named sysop account (or promote an existing user) and exit
+    CreateSysop(String),
+}
+
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub display: DisplayConfig,
+    pub passwords: PasswordConfig,
+    pub usernames: UsernameConfig,
+    pub banner: BannerConfig,
+    pub limits: LimitsConfig,
+    pub login: LoginConfig,
+    #[serde(skip)]
+    pub command: Command,
+}
+
+
+impl Config {
+
+    /**
+    * builds the config from the defaults, then the config file, then RBBS_* environment
+    * variables, then the command line, and validates the result
+    */
+    pub fn load(args: &[String]) -> Result<Config, ConfigError> {
+        let mut config_path: Option<String> = std::env::var("RBBS_CONFIG").ok();
+        let mut cli_overrides: Vec<(&str, String)> = Vec::new();
+        let mut command = Command::Serve;
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(ConfigError::Usage("".to_string()));
+            }
+            if arg == "--migrate-only" {
+                command = Command::MigrateOnly;
+                continue;
+            }
+            if arg == "--failed-logins" {
+                command = Command::FailedLogins;
+                continue;
+            }
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = || -> Result<String, ConfigError> {
+                match inline_value.clone() {
+                    Some(value) => Ok(value),
+                    None => args_iter.next().cloned().ok_or_else(|| ConfigError::Usage(format!("missing value for {}", flag))),
+                }
+            };
+            if flag == "--config" {
+                config_path = Some(value()?);
+            } else if flag == "--create-sysop" {
+                command = Command::CreateSysop(value()?);
+            } else if let Some((key, _, _)) = SETTINGS.iter().find(|(_, cli_flag, _)| *cli_flag == flag) {
+                cli_overrides.push((key, value()?));
+            } else {
+                return Err(ConfigError::Usage(format!("unknown option {}", arg)));
+            }
+        }
+
+        let mut config = match config_path {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(DEFAULT_CONFIG_PATH)?,
+            None => Config::default(),
+        };
+
+        for (key, _, env_var) in SETTINGS.iter() {
+            if let Ok(value) = std::env::var(env_var) {
+                config.set(key, &value).map_err(|e| ConfigError::Invalid(format!("{} (from {})", e, env_var)))?;
+            }
+        }
+        for (key, value) in cli_overrides {
+            config.set(key, &value)?;
+        }
+
+        config.command = command;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_string(), e))?;
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_string(), e))
+    }
+
+    /**
+    * overrides a single setting with a value given as text
+    */
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "server.listen" => self.server.listen = value.split(',').map(|addr| addr.trim().to_string()).collect(),
+            "database.path" => self.database.path = value.to_string(),
+            "database.pool_size" => self.database.pool_size = parse_number(key, value)?,
+            "database.busy_timeout_ms" => self.database.busy_timeout_ms = parse_number(key, value)?,
+            "display.max_page_size" => self.display.max_page_size = parse_number(key, value)?,
+            "display.password_mask" => self.display.password_mask = value.to_string(),
+            "passwords.bcrypt_cost" => self.passwords.bcrypt_cost = parse_number(key, value)?,
+            "passwords.min_length" => self.passwords.min_length = parse_number(key, value)?,
+            "passwords.min_classes" => self.passwords.min_classes = parse_number(key, value)?,
+            "usernames.min_length" => self.usernames.min_length = parse_number(key, value)?,
+            "usernames.max_length" => self.usernames.max_length = parse_number(key, value)?,
+            "usernames.allowed_symbols" => self.usernames.allowed_symbols = value.to_string(),
+            "usernames.reserved" => self.usernames.reserved = value.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect(),
+            "banner.title" => self.banner.title = value.to_string(),
+            "banner.motd" => self.banner.motd = value.to_string(),
+            "limits.max_connections" => self.limits.max_connections = parse_number(key, value)?,
+            "limits.max_connections_per_ip" => self.limits.max_connections_per_ip = parse_number(key, value)?,
+            "limits.idle_timeout_secs" => self.limits.idle_timeout_secs = parse_number(key, value)?,
+            "limits.login_timeout_secs" => self.limits.login_timeout_secs = parse_number(key, value)?,
+            "limits.keepalive_secs" => self.limits.keepalive_secs = parse_number(key, value)?,
+            "limits.subscriber_queue_size" => self.limits.subscriber_queue_size = parse_number(key, value)?,
+            "limits.slow_consumer_policy" => {
+                self.limits.slow_consumer_policy = match value {
+                    "drop_event" => SlowConsumerPolicy::DropEvent,
+                    "disconnect" => SlowConsumerPolicy::Disconnect,
+                    _ => return Err(ConfigError::Invalid(format!("{} must be \"drop_event\" or \"disconnect\", got \"{}\"", key, value))),
+                }
+            }
+            "login.backoff_after" => self.login.backoff_after = parse_number(key, value)?,
+            "login.max_backoff_secs" => self.login.max_backoff_secs = parse_number(key, value)?,
+            "login.lockout_after" => self.login.lockout_after = parse_number(key, value)?,
+            "login.lockout_secs" => self.login.lockout_secs = parse_number(key, value)?,
+            _ => return Err(ConfigError::Invalid(format!("unknown setting {}", key))),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.listen.is_empty() {
+            return Err(ConfigError::Invalid("server.listen needs at least one address".to_string()));
+        }
+        for addr in self.server.listen.iter() {
+            if addr.parse::<SocketAddr>().is_err() {
+                return Err(ConfigError::Invalid(format!("server.listen: \"{}\" is not a valid ip:port address", addr)));
+            }
+        }
+        if self.database.path.trim().is_empty() {
+            return Err(ConfigError::Invalid("database.path can't be empty".to_string()));
+        }
+        if self.database.pool_size == 0 {
+            return Err(ConfigError::Invalid("database.pool_size must be at least 1".to_string()));
+        }
+        if self.display.max_page_size == 0 {
+            return Err(ConfigError::Invalid("display.max_page_size must be at least 1".to_string()));
+        }
+        if self.display.password_mask.chars().count() > 1 {
+            return Err(ConfigError::Invalid("display.password_mask must be a single character or empty".to_string()));
+        }
+        if !(4..=31).contains(&self.passwords.bcrypt_cost) {
+            return Err(ConfigError::Invalid(format!("passwords.bcrypt_cost must be between 4 and 31, got {}", self.passwords.bcrypt_cost)));
+        }
+        if self.passwords.min_length == 0 {
+            return Err(ConfigError::Invalid("passwords.min_length must be at least 1".to_string()));
+        }
+        if self.passwords.min_classes > 4 {
+            return Err(ConfigError::Invalid(format!("passwords.min_classes must be between 0 and 4, got {}", self.passwords.min_classes)));
+        }
+        if self.usernames.min_length == 0 || self.usernames.max_length < self.usernames.min_length {
+            return Err(ConfigError::Invalid("usernames.min_length must be at least 1 and no more than usernames.max_length".to_string()));
+        }
+        if self.usernames.allowed_symbols.chars().any(|c| c.is_alphanumeric() || c.is_whitespace()) {
+            return Err(ConfigError::Invalid("usernames.allowed_symbols can only list symbols".to_string()));
+        }
+        if self.limits.subscriber_queue_size == 0 {
+            return Err(ConfigError::Invalid("limits.subscriber_queue_size must be at least 1".to_string()));
+        }
+        if self.login.lockout_secs == 0 {
+            return Err(ConfigError::Invalid("login.lockout_secs must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value.trim().parse::<T>().map_err(|_| ConfigError::Invalid(format!("{} must be a whole number, got \"{}\"", key, value)))
+}
+
+
+/**
+* command line help listing every option and its environment variable
+*/
+pub fn usage() -> String {
+    let mut output = String::from("Usage: rbbs [OPTIONS]\n\nOptions:\n");
+    output.push_str(&format!("  {:<32} {:<28} {}\n", "--config <PATH>", "RBBS_CONFIG", "config file (default rbbs.toml)"));
+    for (key, flag, env_var) in SETTINGS.iter() {
+        output.push_str(&format!("  {:<32} {:<28} {}\n", format!("{} <VALUE>", flag), env_var, key));
+    }
+    output.push_str(&format!("  {:<32} {:<28} {}\n", "--migrate-only", "", "update the database schema and exit"));
+    output.push_str(&format!("  {:<32} {:<28} {}\n", "--failed-logins", "", "print the most recent failed logins and exit"));
+    output.push_str(&format!("  {:<32} {:<28} {}\n", "--create-sysop <USERNAME>", "RBBS_SYSOP_PASSWORD", "create or promote a sysop account and exit"));
+    output.push_str(&format!("  {:<32} {:<28} {}\n", "-h, --help", "", "print this help"));
+    output
+}
+
+
+/**
+* stores the loaded config, called once at startup
+*/
+pub fn init(config: Config) {
+    CONFIG.set(config).expect("config already loaded");
+}
+
+
+/**
+* the config loaded at startup
+*/
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("config not loaded")
+}
+
+
+/**
+* loads the default config for unit tests, which run without startup
+*/
+#[cfg(test)]
+pub fn init_defaults() {
+    CONFIG.get_or_init(Config::default);
+}
@@ -0,0 +1,520 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::input_interface::UserInterface;
+use crate::input_interface::Events;
+use crate::config;
+use crate::broadcast_events::{handle_broadcast_event, refresh_current_view, BroadcastEvent};
+use crate::hub::{Hub, Subscription, Topic};
+use crate::permissions::Action;
+use crate::presence::{self, SessionId};
+use crate::views::admin_view::{AdminCommand, AdminView};
+use crate::views::direct_message_view::DirectMessageView;
+use crate::views::room_view::RoomView;
+use crate::views::user_view::UserView;
+use crate::telnet::{self, TelnetCodec, TelnetEvent};
+
+
+/**
+* takes the session out of its room, only the first leave is broadcast
+*/
+fn remove_user_from_room(session_id: SessionId, user_id: i32, hub: &Hub) {
+    if let Some(room_id) = presence::leave_room(session_id) {
+        // broadcast disconnected message
+        hub.publish(&[Topic::Room(room_id), Topic::RoomList], BroadcastEvent::RoomLeave { user_id, room_id });
+    }
+}
+
+fn disconnect_user(session_id: SessionId, user_id: i32, hub: &Hub) {
+    remove_user_from_room(session_id, user_id, hub);
+    presence::close_session(session_id);
+    if user_id > 0 {
+        // broadcast disconnected message, the user's other sessions stay open
+        hub.publish(&[Topic::User(user_id), Topic::Presence], BroadcastEvent::Logout { user_id });
+    }
+    else {
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+    }
+}
+
+/**
+* keeps the session on the room list topic only while its view shows room occupancy,
+* so room joins and leaves don't wake sessions that would ignore them
+*/
+fn sync_room_list(ui: &mut UserInterface, subscription: &Subscription) {
+    if ui.get_current_view().lock().unwrap().follows_presence() {
+        subscription.join(Topic::RoomList);
+    } else {
+        subscription.leave(Topic::RoomList);
+    }
+}
+
+/**
+* queues any pending telnet negotiation bytes for the client
+*/
+fn flush_negotiation(output: &mut Vec<u8>, codec: &mut TelnetCodec) {
+    output.extend(codec.take_outgoing());
+}
+
+/**
+* the server echoes (IAC WILL ECHO) so the client stops echoing keystrokes locally,
+* the server never echoes secret input back so it stays hidden. The echo state from
+* before is kept so `disable_secret_mode` can put it back
+*/
+fn enable_secret_mode(output: &mut Vec<u8>, codec: &mut TelnetCodec, ui: &mut UserInterface) {
+    if ui.is_in_secret_input() {
+        return;
+    }
+    ui.start_secret_input(codec.is_local_enabled(telnet::ECHO));
+    codec.set_local_option(telnet::ECHO, true);
+    flush_negotiation(output, codec);
+}
+
+/**
+* hands echoing back to the client (IAC WONT ECHO) if it was echoing before the secret input
+*/
+fn disable_secret_mode(output: &mut Vec<u8>, codec: &mut TelnetCodec, ui: &mut UserInterface) {
+    if let Some(echo_before) = ui.end_secret_input() {
+        if !echo_before {
+            codec.set_local_option(telnet::ECHO, false);
+            flush_negotiation(output, codec);
+        }
+    }
+}
+
+/**
+* keeps secret mode in step with the current view, so it ends however the view stops asking
+* for a password: submitting it, an error, cancelling or navigating away
+*/
+fn sync_secret_mode(output: &mut Vec<u8>, codec: &mut TelnetCodec, ui: &mut UserInterface) {
+    if ui.wants_secret_input() {
+        enable_secret_mode(output, codec, ui);
+    } else {
+        disable_secret_mode(output, codec, ui);
+    }
+}
+
+/**
+* puts the client in character at a time mode: the server echoes (IAC WILL ECHO), go ahead
+* is suppressed in both directions (IAC WILL SGA, IAC DO SGA) and 8 bit data is allowed
+* both ways (IAC WILL BINARY, IAC DO BINARY) so UTF-8 input is not stripped
+*/
+fn enable_character_mode(output: &mut Vec<u8>, codec: &mut TelnetCodec) {
+    codec.set_local_option(telnet::ECHO, true);
+    codec.set_local_option(telnet::SGA, true);
+    codec.set_remote_option(telnet::SGA, true);
+    codec.set_local_option(telnet::BINARY, true);
+    codec.set_remote_option(telnet::BINARY, true);
+    flush_negotiation(output, codec);
+}
+
+
+// how long before an idle or login timeout the client is warned
+const TIMEOUT_WARNING: Duration = Duration::from_secs(60);
+
+
+/**
+* time left before the session is disconnected, measured from the last input once logged in
+* and from the connection start before that. None when the timeout is turned off
+*/
+fn time_left(ui: &UserInterface, connected_at: Instant) -> Option<Duration> {
+    let limits = &config::get().limits;
+    let (limit, elapsed) = if ui.get_user_id() > 0 {
+        (limits.idle_timeout_secs, presence::idle_for(ui.get_session_id()))
+    } else {
+        (limits.login_timeout_secs, connected_at.elapsed())
+    };
+    if limit == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(limit).saturating_sub(elapsed))
+}
+
+
+/**
+* writes `message` on the bottom row of the terminal and puts the cursor back where it was,
+* the next render of the view clears it
+*/
+pub(crate) fn output_status_line(output: &mut Vec<u8>, ui: &UserInterface, message: &str) {
+    let row = ui.get_window_size().height;
+    output.extend_from_slice(format!("\x1b7\x1b[{};1H\x1b[2K\x1b[1;33m{}\x1b[0m\x1b8", row, message).as_bytes());
+}
+
+
+fn output_goodbye_message(output: &mut Vec<u8>) {
+    output.extend_from_slice("\x1b[1;32mGoodbye!\x1b[0m\r\n\r\n".as_bytes());
+}
+
+
+/**
+* runs the bytes read from the client through the telnet codec and the current view,
+* the response is queued in `output`. Returns false once the session should end
+*/
+fn handle_client_input(ui: &mut UserInterface, codec: &mut TelnetCodec, input: &[u8], hub: &Hub, subscription: &Subscription, output: &mut Vec<u8>) -> bool {
+
+    // strip telnet commands out of the input and answer any negotiation
+    let data = codec.decode(input);
+    flush_negotiation(output, codec);
+    for telnet_event in codec.take_events() {
+        match telnet_event {
+            TelnetEvent::TerminalType(terminal_type) => println!("Client terminal type: {}", terminal_type),
+            TelnetEvent::WindowSize { width, height } => {
+                // re-render the current view to fit the new size
+                ui.set_window_size(width as usize, height as usize);
+                output.extend_from_slice(ui.render_current_view().as_bytes());
+            }
+        }
+    }
+    if data.is_empty() {
+        return true;
+    }
+    // only real input counts as activity, not telnet negotiation or keepalive answers
+    presence::touch(ui.get_session_id());
+
+    let user_event = UserInterface::get_user_event(&data);
+    if user_event == Events::Exit {
+        output_goodbye_message(output);
+        return false;
+    }
+
+    // collect buffer as string
+    let buffer_string = ui.clean_buffer(&data);
+
+    if ui.is_in_input_mode() {
+        ui.handle_input_event(&buffer_string, &user_event)
+    }
+
+    let binding = ui.get_current_view();
+
+    let view_handle_event;
+    if ui.is_in_input_mode() {
+        let mut view = binding.lock().unwrap();
+        view_handle_event = view.handle_event(user_event, ui.get_user_input());
+        if view_handle_event == Events::Enter  || view_handle_event == Events::RoomMessageSent  || view_handle_event == Events::DirectMessageSent {
+            let secret_input = ui.is_in_secret_input();
+            ui.submit_user_input(!secret_input)
+        }
+    }
+    else {
+        let mut view = binding.lock().unwrap();
+        view_handle_event = view.handle_event(user_event, buffer_string);
+    }
+
+    // handle default exit event
+    if view_handle_event == Events::Exit {
+        output_goodbye_message(output);
+        return false;
+    }
+
+    // Handle view navigation event
+    else if view_handle_event == Events::NavigateView {
+        ui.navigate_view();
+    }
+
+
+    else if view_handle_event == Events::InputModeDisable {
+        ui.set_input_mode(false);
+    }
+
+
+
+    else if view_handle_event == Events::InputModeEnable {
+        ui.set_input_mode(true);
+    }
+
+
+    else if view_handle_event == Events::SecretInputModeEnable {
+        ui.set_input_mode(true);
+        enable_secret_mode(output, codec, ui);
+    }
+
+
+    else if view_handle_event == Events::Authenticate {
+        ui.set_user_id();
+        ui.navigate_view();
+        ui.set_input_mode(false);
+        let user_id = ui.get_user_id();
+        presence::login(ui.get_session_id(), user_id, ui.get_username());
+        subscription.join(Topic::User(user_id));
+        hub.publish(&[Topic::User(user_id), Topic::Presence], BroadcastEvent::UserLogin { user_id });
+    }
+
+
+    else if view_handle_event == Events::RoomJoin {
+        let user_id = ui.get_user_id();
+        // banned users are left in the room list
+        if let Some(room_id) = ui.join_room() {
+            presence::enter_room(ui.get_session_id(), room_id);
+            subscription.join(Topic::Room(room_id));
+            hub.publish(&[Topic::Room(room_id), Topic::RoomList], BroadcastEvent::RoomJoin { user_id, room_id });
+            ui.navigate_view();
+        }
+    }
+
+
+
+    else if view_handle_event == Events::RoomLeave {
+        enable_character_mode(output, codec);
+        let user_id = ui.get_user_id();
+        let room_id = ui.get_current_room_id();
+        subscription.leave(Topic::Room(room_id));
+        remove_user_from_room(ui.get_session_id(), user_id, hub);
+        ui.set_current_room_id(-1);
+        ui.navigate_view();
+    }
+
+
+    else if view_handle_event == Events::RoomMessageSent {
+        let room_id = ui.get_current_room_id();
+        hub.publish(&[Topic::Room(room_id)], BroadcastEvent::RoomMessage { room_id });
+    }
+
+    else if view_handle_event == Events::DirectMessageSent {
+        let to_user_id = {
+            let view = binding.lock().unwrap();
+            let user_view = view.as_any().downcast_ref::<DirectMessageView>().unwrap();
+            user_view.to_user_id()
+        };
+        let user_id = ui.get_user_id();
+        hub.publish(&[Topic::User(user_id), Topic::User(to_user_id)], BroadcastEvent::DirectMessage {
+            user_id,
+            to_user_id,
+            username: ui.get_username().to_string(),
+        });
+    }
+
+    // asks another session of the same user to close
+    else if view_handle_event == Events::EndSession {
+        let session_to_end = {
+            let view = binding.lock().unwrap();
+            let user_view = view.as_any().downcast_ref::<UserView>().unwrap();
+            user_view.get_session_to_end()
+        };
+        let user_id = ui.get_user_id();
+        if let Some(session_id) = session_to_end {
+            if presence::session_user(session_id) == Some(user_id) {
+                hub.publish(&[Topic::User(user_id)], BroadcastEvent::EndSession {
+                    session_id,
+                    message: "This session was ended from another session.".to_string(),
+                });
+            }
+        }
+    }
+
+    // the account is gone, so every session of the user ends
+    else if view_handle_event == Events::AccountDeleted {
+        let user_id = ui.get_user_id();
+        for session in presence::sessions_for_user(user_id) {
+            if session.session_id != ui.get_session_id() {
+                hub.publish(&[Topic::User(user_id)], BroadcastEvent::EndSession {
+                    session_id: session.session_id,
+                    message: "Your account was deleted from another session.".to_string(),
+                });
+            }
+        }
+        output.extend_from_slice("\x1b[2J\x1b[HYour account has been deleted.\r\n".as_bytes());
+        output_goodbye_message(output);
+        return false;
+    }
+
+    // a room moderator kicked or banned someone, their sessions leave the room here so the
+    // who's here lines update right away
+    else if view_handle_event == Events::KickUser {
+        let user_to_kick = {
+            let view = binding.lock().unwrap();
+            let room_view = view.as_any().downcast_ref::<RoomView>().unwrap();
+            room_view.get_user_to_kick()
+        };
+        let room_id = ui.get_current_room_id();
+        if let Some((user_id, message)) = user_to_kick {
+            for session in presence::sessions_for_user(user_id) {
+                if session.room_id == Some(room_id) {
+                    remove_user_from_room(session.session_id, user_id, hub);
+                }
+            }
+            hub.publish(&[Topic::User(user_id)], BroadcastEvent::RoomKick { room_id, user_id, message });
+        }
+    }
+
+    // a sysop action from the admin screen that other sessions need to see
+    else if view_handle_event == Events::AdminCommand {
+        let command = {
+            let view = binding.lock().unwrap();
+            let admin_view = view.as_any().downcast_ref::<AdminView>().unwrap();
+            admin_view.get_command()
+        };
+        ui.set_input_mode(false);
+        match command {
+            // a logged in session hears it on its user's topic, only sessions that have not
+            // logged in need presence, which every session follows
+            Some(AdminCommand::EndSessions(session_ids, message)) => {
+                for session_id in session_ids {
+                    let topic = match presence::session_user(session_id) {
+                        Some(owner) => Topic::User(owner),
+                        None => Topic::Presence,
+                    };
+                    hub.publish(&[topic], BroadcastEvent::EndSession { session_id, message: message.clone() });
+                }
+            }
+            Some(AdminCommand::Announce(message)) => hub.publish(&[Topic::Presence], BroadcastEvent::Announcement { message }),
+            Some(AdminCommand::RoomChanged(room_id)) => {
+                hub.publish(&[Topic::Room(room_id), Topic::RoomList], BroadcastEvent::RoomChanged { room_id });
+            }
+            Some(AdminCommand::RoomDeleted(room_id)) => {
+                hub.publish(&[Topic::Room(room_id), Topic::RoomList], BroadcastEvent::RoomDeleted { room_id });
+            }
+            None => {}
+        }
+    }
+
+    // the sysop was demoted while the admin screen was open
+    else if view_handle_event == Events::AccessDenied {
+        ui.return_to_menu(Action::Administer.denied_message());
+    }
+
+    sync_secret_mode(output, codec, ui);
+    sync_room_list(ui, subscription);
+    output.extend_from_slice(ui.render_current_view().as_bytes());
+    true
+}
+
+
+/**
+* drives one client connection: a single task waits on both the socket read half and the
+* session's hub queue, so broadcasts are written as soon as they arrive
+*/
+pub async fn handle_client(stream: TcpStream, remote_addr: SocketAddr, hub: Arc<Hub>) {
+    let (mut reader, mut writer) = stream.into_split();
+
+    // every session follows presence, user and room topics are added as it logs in and joins rooms,
+    // the room list topic while a view that follows presence is open
+    let (subscription, mut events) = hub.subscribe();
+    subscription.join(Topic::Presence);
+
+    // every connection is tracked in the presence registry until it closes
+    let mut ui = UserInterface::new(presence::open_session(&remote_addr.to_string()), remote_addr.ip());
+
+    // telnet protocol state for this connection
+    let mut codec = TelnetCodec::new();
+
+    let mut output: Vec<u8> = Vec::new();
+    enable_character_mode(&mut output, &mut codec);
+    codec.set_remote_option(telnet::TTYPE, true);
+    codec.set_remote_option(telnet::NAWS, true);
+    flush_negotiation(&mut output, &mut codec);
+
+    let mut buffer: Vec<u8> = vec![0; 1024];
+
+    // idle and login timeouts, and keepalives for clients that have gone quiet
+    let keepalive = Duration::from_secs(config::get().limits.keepalive_secs);
+    let connected_at = Instant::now();
+    let mut last_received = Instant::now();
+    let mut last_keepalive = Instant::now();
+    let mut warned = false;
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        if !output.is_empty() {
+            if let Err(e) = writer.write_all(&output).await {
+                println!("Client disconnected ({}).", e);
+                break;
+            }
+            output.clear();
+        }
+
+        tokio::select! {
+            read = reader.read(&mut buffer) => {
+                let bytes_read = match read {
+                    Ok(0) => {
+                        // The client has gracefully disconnected
+                        println!("Client disconnected (EOF).");
+                        break;
+                    }
+                    Ok(n) => {
+                        // Data was read successfully
+                        last_received = Instant::now();
+                        n
+                    }
+                    Err(e) => {
+                        println!("Client disconnected ({}).", e);
+                        break; // Exit on error (client closed the connection)
+                    }
+                };
+
+                // views and the db layer block, keep them off the async worker
+                let keep_going = tokio::task::block_in_place(|| {
+                    handle_client_input(&mut ui, &mut codec, &buffer[..bytes_read], &hub, &subscription, &mut output)
+                });
+                // input pushes the idle timeout back, so warn again next time
+                if time_left(&ui, connected_at).is_some_and(|left| left > TIMEOUT_WARNING) {
+                    warned = false;
+                }
+                if !keep_going {
+                    let _ = writer.write_all(&output).await;
+                    break;
+                }
+            }
+
+            _ = ticker.tick() => {
+                if let Some(left) = time_left(&ui, connected_at) {
+                    if left.is_zero() {
+                        println!("Client disconnected (timed out).");
+                        output.extend_from_slice("\r\n\x1b[1;31mDisconnected for inactivity.\x1b[0m\r\n".as_bytes());
+                        let _ = writer.write_all(&output).await;
+                        break;
+                    }
+                    if left <= TIMEOUT_WARNING && !warned {
+                        warned = true;
+                        let message = if ui.get_user_id() > 0 {
+                            format!("You will be disconnected in {} seconds for inactivity, press any key to stay.", left.as_secs())
+                        } else {
+                            format!("Please log in within {} seconds or you will be disconnected.", left.as_secs())
+                        };
+                        output_status_line(&mut output, &ui, &message);
+                    }
+                }
+
+                // a client that answered keepalives before and stopped is gone
+                if !keepalive.is_zero() && last_received.elapsed() >= keepalive && last_keepalive.elapsed() >= keepalive {
+                    if codec.is_unresponsive() {
+                        println!("Client disconnected (no keepalive answer).");
+                        break;
+                    }
+                    codec.send_keepalive();
+                    flush_negotiation(&mut output, &mut codec);
+                    last_keepalive = Instant::now();
+                }
+            }
+
+            event = events.recv() => {
+                let Some(event) = event else {
+                    // the hub dropped this session for not keeping up
+                    println!("Client disconnected (slow consumer).");
+                    break;
+                };
+                let res = tokio::task::block_in_place(|| {
+                    // events were dropped while the queue was full, reload whatever is on screen
+                    if subscription.take_lagged() {
+                        refresh_current_view(&mut ui, &mut output);
+                    }
+                    let res = handle_broadcast_event(event, &mut ui, &subscription, &mut output);
+                    // the event may have sent the session back to the room list
+                    sync_room_list(&mut ui, &subscription);
+                    res
+                });
+                if res == -1 {
+                    let _ = writer.write_all(&output).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    // unsubscribe before telling everyone else the session is gone
+    drop(subscription);
+    let user_id = ui.get_user_id();
+    let session_id = ui.get_session_id();
+    tokio::task::block_in_place(|| disconnect_user(session_id, user_id, &hub));
+}
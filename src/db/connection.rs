@@ -1,12 +1,105 @@
 use rusqlite::{Connection};
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use once_cell::sync::OnceCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use crate::config;
+use crate::db::error::{DbError, DbResult};
 
-static CONN: Lazy<Mutex<Connection>> = Lazy::new(|| {
-    Mutex::new(Connection::open("bbs.db").expect("Failed to open DB"))
-});
+// statements kept prepared per connection, enough for every constant in db::queries
+const STATEMENT_CACHE_CAPACITY: usize = 64;
 
-pub (crate) fn get_db_connection() -> &'static Mutex<Connection> {
-    &CONN
+static POOL: OnceCell<Pool> = OnceCell::new();
+
+
+/**
+* A fixed set of open connections shared by every session. With WAL journaling readers
+* don't wait for a writer, so one session can load history while another posts
+*/
+struct Pool {
+    idle: Mutex<Vec<Connection>>,
+    returned: Condvar,
+    wait: Duration,
+}
+
+
+/**
+* A connection borrowed from the pool, it goes back to the pool when dropped
+*/
+pub (crate) struct PooledConnection {
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
 }
 
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let (Some(conn), Some(pool)) = (self.conn.take(), POOL.get()) {
+            pool.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(conn);
+            pool.returned.notify_one();
+        }
+    }
+}
+
+
+fn open_connection(path: &str, busy_timeout: Duration) -> DbResult<Connection> {
+    let open_error = |e| DbError::Open(path.to_string(), e);
+    let conn = Connection::open(path).map_err(open_error)?;
+    // wait for locks held by other connections instead of failing straight away
+    conn.busy_timeout(busy_timeout).map_err(open_error)?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0)).map_err(open_error)?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        println!("Database {} is using {} journaling, WAL is not available", path, journal_mode);
+    }
+    // safe with WAL, commits only wait for the log to be written
+    conn.pragma_update(None, "synchronous", "NORMAL").map_err(open_error)?;
+    Ok(conn)
+}
+
+
+/**
+* opens `database.pool_size` connections to the database file, called once by `Manager::setup_db`
+*/
+pub (crate) fn open_db(path: &str) -> DbResult<()> {
+    if POOL.get().is_some() {
+        return Ok(());
+    }
+    let settings = &config::get().database;
+    let busy_timeout = Duration::from_millis(settings.busy_timeout_ms);
+    let mut connections: Vec<Connection> = Vec::new();
+    for _ in 0..settings.pool_size {
+        connections.push(open_connection(path, busy_timeout)?);
+    }
+    let _ = POOL.set(Pool { idle: Mutex::new(connections), returned: Condvar::new(), wait: busy_timeout });
+    Ok(())
+}
+
+
+/**
+* borrows a connection from the pool, waiting up to the busy timeout for one to be returned
+*/
+pub (crate) fn get_db_connection() -> DbResult<PooledConnection> {
+    let pool = POOL.get().ok_or(DbError::NotOpen)?;
+    // a session that panicked while holding the lock does not leave it poisoned for everyone else
+    let idle = pool.idle.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (mut idle, _) = pool.returned
+        .wait_timeout_while(idle, pool.wait, |idle| idle.is_empty())
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match idle.pop() {
+        Some(conn) => Ok(PooledConnection { conn: Some(conn) }),
+        None => Err(DbError::PoolExhausted),
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+use rusqlite::ErrorCode;
+use crate::account_policy::PolicyError;
+use crate::db::migrations::MigrationError;
+
+
+/**
+* Everything that can go wrong in the db layer. `Display` has the details for the server log,
+* `user_message` is the text safe to show to a client
+*/
+#[derive(Debug)]
+pub enum DbError {
+    // the database file could not be opened
+    Open(String, rusqlite::Error),
+    // setup_db has not opened the database yet
+    NotOpen,
+    // another connection holds a lock on the database
+    Busy(rusqlite::Error),
+    // every pooled connection stayed in use for the whole busy timeout
+    PoolExhausted,
+    Sqlite(rusqlite::Error),
+    Migration(MigrationError),
+    Hash(bcrypt::BcryptError),
+    // a unique value such as a username or room name is taken
+    AlreadyExists(&'static str),
+    NotFound(&'static str),
+    InvalidCredentials,
+    // the password was right but a sysop banned the account
+    Banned,
+    // a room moderator banned the user from the room
+    BannedFromRoom,
+    // a room moderator muted the user, with the seconds left
+    Muted(i64),
+    // a new username or password breaks the registration rules
+    Rejected(PolicyError),
+}
+
+pub type DbResult<T> = Result<T, DbError>;
+
+
+impl DbError {
+
+    /**
+    * the message shown in a view, without any database details
+    */
+    pub fn user_message(&self) -> String {
+        match self {
+            DbError::Busy(_) | DbError::PoolExhausted => "The BBS is busy right now, please try again.".to_string(),
+            DbError::AlreadyExists(what) => format!("That {} already exists.", what),
+            DbError::NotFound(what) => format!("That {} could not be found.", what),
+            DbError::InvalidCredentials => "Unable to validate user, maybe wrong password?".to_string(),
+            DbError::Banned => "This account has been banned.".to_string(),
+            DbError::BannedFromRoom => "You are banned from this room.".to_string(),
+            DbError::Muted(seconds) => format!("You are muted in this room for {} more minutes.", (seconds + 59) / 60),
+            DbError::Rejected(e) => e.to_string(),
+            _ => "Something went wrong, please try again later.".to_string(),
+        }
+    }
+
+    /**
+    * logs the error against the action that failed and returns the message for the user,
+    * expected outcomes like a taken name or a wrong password are not logged
+    */
+    pub fn report(&self, action: &str) -> String {
+        match self {
+            DbError::AlreadyExists(_) | DbError::NotFound(_) | DbError::InvalidCredentials | DbError::Banned | DbError::Rejected(_) => {}
+            DbError::BannedFromRoom | DbError::Muted(_) => {}
+            _ => println!("Database error while {}: {}", action, self),
+        }
+        self.user_message()
+    }
+}
+
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Open(path, e) => write!(f, "unable to open {}: {}", path, e),
+            DbError::NotOpen => write!(f, "database has not been opened"),
+            DbError::Busy(e) => write!(f, "{}", e),
+            DbError::PoolExhausted => write!(f, "timed out waiting for a free database connection"),
+            DbError::Sqlite(e) => write!(f, "{}", e),
+            DbError::Migration(e) => write!(f, "{}", e),
+            DbError::Hash(e) => write!(f, "unable to hash password: {}", e),
+            DbError::AlreadyExists(what) => write!(f, "{} already exists", what),
+            DbError::NotFound(what) => write!(f, "{} not found", what),
+            DbError::InvalidCredentials => write!(f, "invalid username or password"),
+            DbError::Banned => write!(f, "account is banned"),
+            DbError::BannedFromRoom => write!(f, "banned from the room"),
+            DbError::Muted(seconds) => write!(f, "muted in the room for {}s", seconds),
+            DbError::Rejected(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+
+impl From<rusqlite::Error> for DbError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => DbError::Busy(error),
+            _ => DbError::Sqlite(error),
+        }
+    }
+}
+
+impl From<MigrationError> for DbError {
+    fn from(error: MigrationError) -> Self {
+        DbError::Migration(error)
+    }
+}
+
+impl From<PolicyError> for DbError {
+    fn from(error: PolicyError) -> Self {
+        DbError::Rejected(error)
+    }
+}
+
+impl From<bcrypt::BcryptError> for DbError {
+    fn from(error: bcrypt::BcryptError) -> Self {
+        DbError::Hash(error)
+    }
+}
+
+
+/**
+* true when the error is a UNIQUE constraint failing, used to turn an insert of a taken
+* name into `DbError::AlreadyExists`
+*/
+pub fn is_unique_violation(error: &rusqlite::Error) -> bool {
+    matches!(error, rusqlite::Error::SqliteFailure(e, _) if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE)
+}
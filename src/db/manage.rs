@@ -1,8 +1,67 @@
 use std::collections::HashMap;
-use bcrypt::{hash, DEFAULT_COST};
+use bcrypt::hash;
 use bcrypt::{verify};
-use crate::db::connection::get_db_connection;
+use once_cell::sync::Lazy;
+use rusqlite::{params, OptionalExtension};
+use crate::account_policy::{self, PolicyError};
+use crate::config;
+use crate::db::connection::{get_db_connection, open_db};
+use crate::db::error::{is_unique_violation, DbError, DbResult};
 use crate::db::queries;
+use crate::db::migrations;
+use crate::permissions::{Role, RoomRole};
+
+
+/**
+* A room message as (message_id, user_id, created_date, username, message)
+*/
+pub type RoomMessageRow = (i32, i32, String, String, String);
+
+/**
+* A message board as (board_id, name, description, threads, threads with unread posts)
+*/
+pub type BoardRow = (i32, String, String, i64, i64);
+
+/**
+* A thread as (thread_id, subject, author, replies, last_post_date, has unread posts)
+*/
+pub type ThreadRow = (i32, String, String, i64, String, bool);
+
+/**
+* A board post as (post_id, parent_post_id, author, created_date, body)
+*/
+pub type PostRow = (i32, Option<i32>, String, String, String);
+
+/**
+* A direct message conversation as (other_user_id, username, last message, its created_date,
+* whether the current user sent it, unread messages)
+*/
+pub type ConversationRow = (i32, String, String, String, bool, i64);
+
+/**
+* A mail in a folder as (mail_id, the other user, subject, created_date, read_date)
+*/
+pub type MailRow = (i32, String, String, String, Option<String>);
+
+/**
+* A search result as (message_id, whether it is a direct message, the room_id or the other user's
+* id, the room name or the other user's name, author, created_date, snippet)
+*/
+pub type SearchRow = (i32, bool, i32, String, String, String, String);
+
+/**
+* mark the matched words in search snippets, the views turn them into highlighting
+*/
+pub const MATCH_START: &str = "\u{2}";
+pub const MATCH_END: &str = "\u{3}";
+
+// words of context in a search snippet
+const SNIPPET_WORDS: i32 = 12;
+
+// checked when a username does not exist, so a failed login takes as long either way
+static DUMMY_PASSWORD_HASH: Lazy<String> = Lazy::new(|| {
+    hash("not a real password", config::get().passwords.bcrypt_cost).expect("bcrypt_cost is checked when the config loads")
+});
 
 
 pub struct Manager;
@@ -11,366 +70,891 @@ pub struct Manager;
 impl Manager {
 
     /**
-    * This method takes a room id and increments its online count by 1
+    * This method takes a username and password and creates a new user,
+    * `DbError::Rejected` if either breaks the registration rules and
+    * `DbError::AlreadyExists` if the username is taken, ignoring case
     */
-    pub fn add_to_room_online(room_id: i32) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::JOIN_ROOM).unwrap();
-        stmt.execute([&room_id]).expect("Failed to add to room");
+    pub fn create_user(username: &str, password: &str) -> DbResult<i32> {
+        account_policy::check_username(username)?;
+        Self::insert_user(username, password)
     }
 
     /**
-    * This method takes a room id and decrements its online count by 1
+    * hashes the password and inserts the user, the username rules are checked by the caller
     */
-    pub fn subtract_from_room_online(room_id: i32) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::LEAVE_ROOM).unwrap();
-        stmt.execute([&room_id]).expect("Failed to subtract from room");
+    fn insert_user(username: &str, password: &str) -> DbResult<i32> {
+        account_policy::check_password(username, password)?;
+        let binding = hash(password, config::get().passwords.bcrypt_cost)?;
+        let password_hash = binding.as_str();
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::CREATE_NEW_USER)?;
+        match stmt.execute([&username, &password_hash]) {
+            Ok(0) => Err(DbError::AlreadyExists("username")),
+            Ok(_) => Ok(conn.last_insert_rowid() as i32),
+            Err(err) if is_unique_violation(&err) => Err(DbError::AlreadyExists("username")),
+            Err(err) => Err(err.into()),
+        }
     }
 
+    /**
+    * This method takes a username and password and validates the password is correct
+    * for the provided username, `DbError::InvalidCredentials` if either is wrong and
+    * `DbError::Banned` if the password is right but the account is banned
+    */
+    pub fn validate_user(username: &str, password: &str) -> DbResult<i32> {
+        // the connection goes back to the pool before the slow bcrypt check
+        let user = {
+            let conn = get_db_connection()?;
+            let mut stmt = conn.prepare_cached(queries::SEARCH_USER)?;
+            stmt.query_row([&username], |row| {
+                let id: i32 = row.get("id")?;
+                let password_hash: String = row.get("password_hash")?;
+                let banned: bool = row.get("banned")?;
+                Ok((id, password_hash, banned))
+            }).optional()?
+        };
+
+        let Some((id, password_hash, banned)) = user else {
+            let _ = verify(password, &DUMMY_PASSWORD_HASH);
+            return Err(DbError::InvalidCredentials);
+        };
+        if !verify(password, &password_hash).unwrap_or(false) {
+            return Err(DbError::InvalidCredentials);
+        }
+        if banned {
+            return Err(DbError::Banned);
+        }
+        Ok(id)
+    }
 
     /**
-    * This method takes a user id and sets their online status to 1 / true
+    * Retrieves the room name for the provided room id
     */
-    pub fn login_user(user_id: i32) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::LOGIN_USER).unwrap();
-        stmt.execute([&user_id]).expect("Failed to login user");
+    pub fn get_room_name_by_id(room_id: i32) -> DbResult<String> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_ROOM_NAME)?;
+        stmt.query_row([&room_id], |row| row.get("name"))
+            .optional()?
+            .ok_or(DbError::NotFound("room"))
     }
 
 
     /**
-    * This method takes a user id and sets their online status to 0 / false
+    * gets up to `limit` rooms as (id, name) ordered by name
+    * offset can be used for pagination
     */
-    pub fn logout_user(user_id: i32) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::LOGOUT_USER).unwrap();
-        stmt.execute([&user_id]).expect("Failed to logout user");
+    pub fn get_rooms(offset: i32, limit: i32) -> DbResult<Vec<(i32, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_ROOMS)?;
+
+        // Execute the query and collect the rows as (id, name)
+        let rooms = stmt
+            .query_map([limit, offset], |row| Ok((row.get("id")?, row.get("name")?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        Ok(rooms)
     }
 
+    /**
+    * retrieves up to `limit` rooms with names matching the `room_query` param
+    * offset can be used for pagination
+    */
+    pub fn search_rooms(room_query: String, offset: i32, limit: i32) -> DbResult<Vec<(i32, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SEARCH_ROOMS)?;
+        let pattern = format!("%{}%", room_query);
+
+        let rooms = stmt
+            .query_map(params![&pattern, limit, offset], |row| Ok((row.get("id")?, row.get("name")?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        Ok(rooms)
+    }
 
     /**
-    * This method takes a username and password and creates a new user
+    * creates a room using the provided `room_name` with `user_id` as its owner,
+    * `DbError::AlreadyExists` if the name is taken
     */
-    pub fn create_user(username: &str, password: &str) -> i32 {
-        let binding = hash(password, DEFAULT_COST).expect("Failed to hash password");
-        let password_hash = binding.as_str();
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::CREATE_NEW_USER).unwrap();
-        match stmt.execute([&username, &password_hash]) {
-            Ok(affected_rows) => {
-                if affected_rows > 0 {
-                    let user_id = conn.last_insert_rowid() as i32;
-                    user_id
-                } else {
-                    println!("No rows were inserted.");
-                    -1 // Or handle this case properly
-                }
-            }
-            Err(err) => {
-                println!("Error inserting user: {:?}", err);
-                -1 // Handle the error case (e.g., return a special value or propagate the error)
-            }
-        }
+    pub fn create_room(room_name: String, user_id: i32) -> DbResult<()> {
+        let mut conn = get_db_connection()?;
+        let tx = conn.transaction()?;
+
+        let room_id = match tx.execute(queries::CREATE_NEW_ROOM, params![&room_name, user_id]) {
+            Ok(_) => tx.last_insert_rowid(),
+            Err(err) if is_unique_violation(&err) => return Err(DbError::AlreadyExists("room")),
+            Err(err) => return Err(err.into()),
+        };
+        tx.execute(queries::SET_ROOM_ROLE, params![room_id, user_id, RoomRole::Owner.as_str()])?;
+        tx.commit()?;
+        println!("Created new room {}", room_name);
+        Ok(())
     }
 
     /**
-    * This method takes a username and password and validates the password is correct
-    * for the provided username
-    */
-    pub fn validate_user(username: &str, password: &str) -> i32 {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::SEARCH_USER).unwrap();
-        let mut rows = stmt.query([&username]).unwrap();
-
-        if let Some(user) = rows.next().unwrap() {
-            let id: i32 = user.get("id").unwrap();
-            let password_hash: String = user.get("password_hash").unwrap();
-            let valid = verify(password, &password_hash).unwrap_or(false);
-            if valid { id }  else { -1 }
-        } else {
-            -1
-        }
+    * opens the db and applies any pending schema migrations
+    */
+    pub fn setup_db() -> DbResult<()> {
+        open_db(&config::get().database.path)?;
+        let mut conn = get_db_connection()?;
+        // brings the schema up to date, refusing databases from a newer build
+        migrations::migrate(&mut conn)?;
+        // hashed now so the first login of an unknown username is not slower than the rest
+        Lazy::force(&DUMMY_PASSWORD_HASH);
+
+        println!("Database setup complete, schema version {} ✅", migrations::latest_version());
+        Ok(())
     }
 
     /**
-    * Retrieves the room name for the provided room id
+    * Retrieve info on a user using the provided `user_id`
     */
-    pub fn get_room_name_by_id(room_id: i32) -> String {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_ROOM_NAME).unwrap();
+    pub fn get_user(user_id: i32) -> DbResult<HashMap<String, String>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_USER)?;
+        let user = stmt.query_row([&user_id], |row| {
+            // here the column is the key and its text the value
+            let mut user: HashMap<String, String> = HashMap::new();
+            for column in ["username", "display_name", "bio", "location", "created_date"] {
+                user.insert(column.to_string(), row.get(column)?);
+            }
+            Ok(user)
+        }).optional()?;
 
-        // Execute the query and collect the rows into a HashMap
-        let mut rows = stmt.query([&room_id]).unwrap();
-        let mut room_name: String = "".to_string();
-        while let Some(row) = rows.next().unwrap() {
-            room_name = row.get("name").unwrap();
-        }
-        room_name
+        user.ok_or(DbError::NotFound("user"))
+    }
 
+    /**
+    * retrieves up to `limit` users as (id, username), the users in `online_user_ids` first
+    * `offset` can be used for pagination
+    */
+    pub fn get_users(online_user_ids: &[i32], offset: i32, limit: i32) -> DbResult<Vec<(i32, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_USERS)?;
+        let online = serde_json::to_string(online_user_ids).unwrap_or_else(|_| "[]".to_string());
+        let users = stmt
+            .query_map(params![online, limit, offset], |row| Ok((row.get("id")?, row.get("username")?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        Ok(users)
     }
 
+    /**
+    * searches for up to `limit` users by username, as (id, username)
+    * `offset` can be used for pagination
+    */
+    pub fn search_users(username_query: String, offset: i32, limit: i32) -> DbResult<Vec<(i32, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SEARCH_USERS)?;
+        let pattern = format!("%{}%", username_query);
+
+        let users = stmt
+            .query_map(params![&pattern, limit, offset], |row| Ok((row.get("id")?, row.get("username")?)))?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+        Ok(users)
+    }
 
     /**
-    * gets the rooms ordered by active user count in descending order
-    * offset can be used for pagination
+    * collects up to `limit` messages for a room ordered by created date in descending order
+    * `offset` can be used for pagination
     */
-    pub fn get_rooms(offset: i32) -> Vec<(String, u32)> {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_ROOMS).unwrap();
+    pub fn get_message_from_room(room_id: i32, offset: i32, limit: i32) -> DbResult<Vec<RoomMessageRow>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_MESSAGES_FOR_ROOM)?;
+
+        let messages = stmt
+            .query_map([&room_id, &limit, &offset], |row| {
+                Ok((row.get("id")?, row.get("user_id")?, row.get("created_date")?, row.get("username")?, row.get("message")?))
+            })?
+            .collect::<Result<Vec<RoomMessageRow>, _>>()?;
+        Ok(messages)
+    }
 
-        // Execute the query and collect the rows into a HashMap
-        let mut rows = stmt.query([offset]).unwrap();
+    /**
+    * publishes a message to a room for the user, `DbError::BannedFromRoom` or `DbError::Muted`
+    * if a room moderator stopped them
+    */
+    pub fn post_message(room_id: i32, message: String, user_id: i32) -> DbResult<()> {
+        if Self::is_banned_from_room(room_id, user_id)? {
+            return Err(DbError::BannedFromRoom);
+        }
+        if let Some(seconds_left) = Self::get_mute_seconds_left(room_id, user_id)? {
+            return Err(DbError::Muted(seconds_left));
+        }
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::POST_MESSAGE_TO_ROOM)?;
+        stmt.execute([&message, &user_id.to_string(), &room_id.to_string()])?;
+        Ok(())
+    }
 
-        // Create an empty HashMap to store the results
-        let mut rooms: Vec<(String, u32)> = Vec::new();
+    /**
+    * saves a direct message between users
+    */
+    pub fn post_direct_message(user_id: i32, to_user_id: i32, message: String) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::POST_DIRECT_MESSAGE)?;
+        stmt.execute([&message, &user_id.to_string(), &to_user_id.to_string()])?;
+        Ok(())
+    }
 
+    /**
+    * retrieves up to `limit` direct messages for between a set of users
+    * `offset` can be used for pagination
+    */
+    pub fn get_direct_messages_for(user_id: i32, to_user_id: i32, offset: i32, limit: i32) -> DbResult<Vec<(i32, String, String, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_MESSAGES_FOR_USER)?;
+
+        // rows as (user_id, created_date, username, message)
+        let messages = stmt
+            .query_map([&user_id, &to_user_id, &to_user_id, &user_id, &limit, &offset], |row| {
+                Ok((row.get("user_id")?, row.get("created_date")?, row.get("username")?, row.get("message")?))
+            })?
+            .collect::<Result<Vec<(i32, String, String, String)>, _>>()?;
+        Ok(messages)
+    }
 
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * records a refused login for `username` from `remote_ip` with a short `reason`
+    */
+    pub fn log_failed_login(username: &str, remote_ip: &str, reason: &str) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::LOG_FAILED_LOGIN)?;
+        stmt.execute([username, remote_ip, reason])?;
+        Ok(())
+    }
 
-            let name: String = row.get("name").unwrap();
-            let online: u32 = row.get("online").unwrap();
+    /**
+    * retrieves up to `limit` failed logins, newest first, as (created_date, username, remote_ip, reason)
+    * `offset` can be used for pagination
+    */
+    pub fn get_failed_logins(offset: i32, limit: i32) -> DbResult<Vec<(String, String, String, String)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_FAILED_LOGINS)?;
+        let attempts = stmt
+            .query_map([limit, offset], |row| {
+                Ok((row.get("created_date")?, row.get("username")?, row.get("remote_ip")?, row.get("reason")?))
+            })?
+            .collect::<Result<Vec<(String, String, String, String)>, _>>()?;
+        Ok(attempts)
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            rooms.push((name, online));
+    /**
+    * checks `password` against the one stored for `user_id` and returns the username,
+    * `DbError::InvalidCredentials` if it is wrong. No connection is held during the bcrypt check
+    */
+    fn verify_password(user_id: i32, password: &str) -> DbResult<String> {
+        let user = {
+            let conn = get_db_connection()?;
+            let mut stmt = conn.prepare_cached(queries::GET_PASSWORD_HASH)?;
+            stmt.query_row([&user_id], |row| {
+                let username: String = row.get("username")?;
+                let password_hash: String = row.get("password_hash")?;
+                Ok((username, password_hash))
+            }).optional()?
+        };
+
+        match user {
+            Some((username, password_hash)) if verify(password, &password_hash).unwrap_or(false) => Ok(username),
+            Some(_) => Err(DbError::InvalidCredentials),
+            None => Err(DbError::NotFound("user")),
         }
-
-        // Return the populated HashMap
-        rooms.reverse();
-        rooms
     }
 
     /**
-    * retrieves rooms with names matching the `room_query` param
-    * offset can be used for pagination
+    * replaces the password of `user_id` once `current_password` is confirmed,
+    * `DbError::Rejected` if the new one breaks the password rules
     */
-    pub fn search_rooms(room_query: String, offset: i32) ->  Vec<(String, u32)> {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::SEARCH_ROOMS).unwrap();
-        let pattern = format!("%{}%", room_query);
-        let mut rows = stmt.query([&pattern, &offset.to_string()]).unwrap();
+    pub fn change_password(user_id: i32, current_password: &str, new_password: &str) -> DbResult<()> {
+        let username = Self::verify_password(user_id, current_password)?;
+        account_policy::check_password(&username, new_password)?;
+
+        let password_hash = hash(new_password, config::get().passwords.bcrypt_cost)?;
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::UPDATE_PASSWORD_HASH)?;
+        stmt.execute(params![password_hash, user_id])?;
+        println!("User {} changed their password", username);
+        Ok(())
+    }
 
-        // Create an empty HashMap to store the results
-        let mut rooms: Vec<(String, u32)> = Vec::new();
+    /**
+    * saves the display name, bio and location of `user_id`, an empty value clears the field.
+    * `DbError::Rejected` if the display name is another user's username or display name
+    */
+    pub fn update_profile(user_id: i32, display_name: &str, bio: &str, location: &str) -> DbResult<()> {
+        account_policy::check_display_name(display_name)?;
+        account_policy::check_profile_field("bio", bio)?;
+        account_policy::check_profile_field("location", location)?;
+
+        let conn = get_db_connection()?;
+        if !display_name.trim().is_empty() {
+            let mut stmt = conn.prepare_cached(queries::IS_DISPLAY_NAME_TAKEN)?;
+            if stmt.exists(params![display_name.trim(), user_id])? {
+                return Err(PolicyError::DisplayNameTaken.into());
+            }
+        }
+        let mut stmt = conn.prepare_cached(queries::UPDATE_PROFILE)?;
+        stmt.execute(params![display_name.trim(), bio.trim(), location.trim(), user_id])?;
+        Ok(())
+    }
 
+    /**
+    * deletes `user_id` once `password` is confirmed, their rooms lose their owner
+    * and their messages stay as from a deleted user
+    */
+    pub fn delete_user(user_id: i32, password: &str) -> DbResult<()> {
+        let username = Self::verify_password(user_id, password)?;
+        let mut conn = get_db_connection()?;
+
+        let tx = conn.transaction()?;
+        tx.execute(queries::RELEASE_OWNED_ROOMS, [&user_id])?;
+        tx.execute(queries::DELETE_USER_ROOM_ROLES, [&user_id])?;
+        tx.execute(queries::DELETE_USER_ROOM_BANS, [&user_id])?;
+        tx.execute(queries::DELETE_USER_ROOM_MUTES, [&user_id])?;
+        tx.execute(queries::DELETE_USER_THREAD_READS, [&user_id])?;
+        tx.execute(queries::DELETE_USER_ROOM_READS, [&user_id])?;
+        tx.execute(queries::DELETE_USER_DM_READS, [&user_id])?;
+        tx.execute(queries::DELETE_USER_MAIL, [&user_id])?;
+        tx.execute(queries::DELETE_USER, [&user_id])?;
+        tx.commit()?;
+        println!("Deleted user {}", username);
+        Ok(())
+    }
 
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * looks up a user id by username, ignoring case
+    */
+    pub fn get_user_id_by_name(username: &str) -> DbResult<i32> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_USER_ID_BY_NAME)?;
+        stmt.query_row([username.trim()], |row| row.get("id"))
+            .optional()?
+            .ok_or(DbError::NotFound("user"))
+    }
 
-            let name: String = row.get("name").unwrap();
-            let online: u32 = row.get("online").unwrap();
+    /**
+    * the BBS wide role of `user_id`, an unknown role in the database counts as a guest
+    */
+    pub fn get_user_role(user_id: i32) -> DbResult<Role> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_USER_ROLE)?;
+        let role: String = stmt.query_row([&user_id], |row| row.get("role"))
+            .optional()?
+            .ok_or(DbError::NotFound("user"))?;
+        Ok(Role::parse(&role).unwrap_or(Role::Guest))
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            rooms.push((name, online));
+    pub fn set_user_role(user_id: i32, role: Role) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SET_USER_ROLE)?;
+        if stmt.execute(params![role.as_str(), user_id])? == 0 {
+            return Err(DbError::NotFound("user"));
         }
+        Ok(())
+    }
 
-        // Return the populated HashMap
-        rooms
-
+    /**
+    * the role of `user_id` in `room_id`, None for everyone but the owner and moderators
+    */
+    pub fn get_room_role(room_id: i32, user_id: i32) -> DbResult<Option<RoomRole>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_ROOM_ROLE)?;
+        let role: Option<String> = stmt.query_row([&room_id, &user_id], |row| row.get("role")).optional()?;
+        Ok(role.and_then(|role| RoomRole::parse(&role)))
     }
 
     /**
-    * creates a room using the provided `room_name` and `user_id`
+    * gives `user_id` a role in `room_id`, or takes it away with None
     */
-    pub fn create_room(room_name: String, user_id: String) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::CREATE_NEW_ROOM).unwrap();
+    pub fn set_room_role(room_id: i32, user_id: i32, role: Option<RoomRole>) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        match role {
+            Some(role) => conn.prepare_cached(queries::SET_ROOM_ROLE)?.execute(params![room_id, user_id, role.as_str()])?,
+            None => conn.prepare_cached(queries::REMOVE_ROOM_ROLE)?.execute(params![room_id, user_id])?,
+        };
+        Ok(())
+    }
 
-        // Execute the query and collect the rows into a HashMap
-        stmt.execute([&room_name, &user_id]).unwrap();
-        println!("Created new room {}", room_name);
+    /**
+    * creates the sysop account `username`, used to bootstrap the BBS from the command line
+    * so reserved names like "sysop" are allowed
+    */
+    pub fn create_sysop(username: &str, password: &str) -> DbResult<i32> {
+        account_policy::check_username_format(username)?;
+        let user_id = Self::insert_user(username, password)?;
+        Self::set_user_role(user_id, Role::Sysop)?;
+        Ok(user_id)
     }
 
     /**
-    * creates the tables in the db if they do not already exist
+    * gets up to `limit` users as (id, username, role, banned) ordered by username, for the admin screen
     */
-    pub fn setup_db() {
-        let conn = get_db_connection().lock().unwrap();
-        // Create users table
-        conn.execute(queries::CREATE_USERS, []).expect("Create users failed");
-        conn.execute(queries::CREATE_ROOMS, []).expect("Create rooms failed");
-        conn.execute(queries::CREATE_ROOM_MESSAGES, []).expect("Create room messages failed");
-        conn.execute(queries::CREATE_DIRECT_MESSAGES, []).expect("Create direct messages failed");
+    pub fn get_users_for_admin(offset: i32, limit: i32) -> DbResult<Vec<(i32, String, Role, bool)>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_USERS_FOR_ADMIN)?;
+        let users = stmt
+            .query_map([limit, offset], |row| {
+                let role: String = row.get("role")?;
+                Ok((row.get("id")?, row.get("username")?, Role::parse(&role).unwrap_or(Role::Guest), row.get("banned")?))
+            })?
+            .collect::<Result<Vec<(i32, String, Role, bool)>, _>>()?;
+        Ok(users)
+    }
 
-        println!("Database setup complete! ✅")
+    /**
+    * bans or unbans `user_id`, a banned user can't log in
+    */
+    pub fn set_banned(user_id: i32, banned: bool) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SET_USER_BANNED)?;
+        if stmt.execute(params![banned, user_id])? == 0 {
+            return Err(DbError::NotFound("user"));
+        }
+        Ok(())
+    }
 
+    /**
+    * sets a new password for `user_id` without the current one, used by sysops.
+    * `DbError::Rejected` if it breaks the password rules
+    */
+    pub fn reset_password(user_id: i32, new_password: &str) -> DbResult<()> {
+        let username: String = get_db_connection()?
+            .prepare_cached(queries::GET_USERNAME)?
+            .query_row([&user_id], |row| row.get("username"))
+            .optional()?
+            .ok_or(DbError::NotFound("user"))?;
+        account_policy::check_password(&username, new_password)?;
+
+        let password_hash = hash(new_password, config::get().passwords.bcrypt_cost)?;
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::UPDATE_PASSWORD_HASH)?;
+        stmt.execute(params![password_hash, user_id])?;
+        println!("Password of user {} was reset", username);
+        Ok(())
     }
 
     /**
-    * Retrieve info on a user using the provided `user_id`
+    * renames `room_id`, `DbError::AlreadyExists` if the name is taken
     */
-    pub fn get_user(user_id: i32)  -> HashMap<String, String> {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_USER).unwrap();
-        let mut rows = stmt.query([&user_id]).unwrap();
-        let mut user: HashMap<String, String> = HashMap::new();
+    pub fn rename_room(room_id: i32, room_name: &str) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::RENAME_ROOM)?;
+        match stmt.execute(params![room_name, room_id]) {
+            Ok(0) => Err(DbError::NotFound("room")),
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => Err(DbError::AlreadyExists("room")),
+            Err(err) => Err(err.into()),
+        }
+    }
 
+    /**
+    * deletes `room_id` together with its messages and roles
+    */
+    pub fn delete_room(room_id: i32) -> DbResult<()> {
+        let mut conn = get_db_connection()?;
+        let tx = conn.transaction()?;
+        tx.execute(queries::DELETE_ROOM_MESSAGES, [&room_id])?;
+        tx.execute(queries::DELETE_ROOM_ROLES, [&room_id])?;
+        tx.execute(queries::DELETE_ROOM_BANS, [&room_id])?;
+        tx.execute(queries::DELETE_ROOM_MUTES, [&room_id])?;
+        tx.execute(queries::DELETE_ROOM_READS, [&room_id])?;
+        if tx.execute(queries::DELETE_ROOM, [&room_id])? == 0 {
+            return Err(DbError::NotFound("room"));
+        }
+        tx.commit()?;
+        println!("Deleted room {}", room_id);
+        Ok(())
+    }
 
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * deletes every message posted in `room_id`, returns how many there were
+    */
+    pub fn purge_room_messages(room_id: i32) -> DbResult<usize> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::DELETE_ROOM_MESSAGES)?;
+        let purged = stmt.execute([&room_id])?;
+        println!("Purged {} messages from room {}", purged, room_id);
+        Ok(purged)
+    }
 
-            let name: String = row.get("username").unwrap();
-            let logged_in: i32 = row.get("logged_in").unwrap();
-            let created_date: String = row.get("created_date").unwrap();
+    /**
+    * deletes one message from `room_id`, `DbError::NotFound` if it is not in that room
+    */
+    pub fn delete_room_message(room_id: i32, message_id: i32) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::DELETE_ROOM_MESSAGE)?;
+        if stmt.execute([&message_id, &room_id])? == 0 {
+            return Err(DbError::NotFound("message"));
+        }
+        Ok(())
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            user.insert("username".to_string(), name);
-            user.insert("online".to_string(), logged_in.to_string());
-            user.insert("created_date".to_string(), created_date);
+    /**
+    * bans `user_id` from joining or posting in `room_id`, or lifts the ban.
+    * `moderator_id` is kept for the record
+    */
+    pub fn set_room_ban(room_id: i32, user_id: i32, moderator_id: i32, banned: bool) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        if banned {
+            conn.prepare_cached(queries::BAN_FROM_ROOM)?.execute([&room_id, &user_id, &moderator_id])?;
+        } else {
+            conn.prepare_cached(queries::UNBAN_FROM_ROOM)?.execute([&room_id, &user_id])?;
         }
+        Ok(())
+    }
 
-        user
+    pub fn is_banned_from_room(room_id: i32, user_id: i32) -> DbResult<bool> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::IS_BANNED_FROM_ROOM)?;
+        Ok(stmt.exists([&room_id, &user_id])?)
     }
 
     /**
-    * retrieves users ordered by their logged in value (1 online, 0 offline)
-    * `offset` can be used for pagination
+    * stops `user_id` posting in `room_id` for `minutes`, or lifts the mute with None
     */
-    pub fn get_online_users(offset: i32) ->  Vec<(String, bool)>  {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_ONLINE_USERS).unwrap();
-        let mut rows = stmt.query([&offset]).unwrap();
-        let mut users: Vec<(String, bool)>  = Vec::new();
+    pub fn set_room_mute(room_id: i32, user_id: i32, moderator_id: i32, minutes: Option<u32>) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        match minutes {
+            Some(minutes) => conn.prepare_cached(queries::MUTE_IN_ROOM)?.execute(params![room_id, user_id, minutes, moderator_id])?,
+            None => conn.prepare_cached(queries::UNMUTE_IN_ROOM)?.execute([&room_id, &user_id])?,
+        };
+        Ok(())
+    }
 
+    /**
+    * the seconds left until `user_id` may post in `room_id` again, None when they are not muted
+    */
+    pub fn get_mute_seconds_left(room_id: i32, user_id: i32) -> DbResult<Option<i64>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_MUTE_SECONDS_LEFT)?;
+        Ok(stmt.query_row([&room_id, &user_id], |row| row.get("seconds_left")).optional()?)
+    }
 
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * gets up to `limit` boards ordered by name, counting the threads `user_id` has not read
+    */
+    pub fn get_boards(user_id: i32, offset: i32, limit: i32) -> DbResult<Vec<BoardRow>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_BOARDS)?;
+        let boards = stmt
+            .query_map([user_id, limit, offset], |row| {
+                Ok((row.get("id")?, row.get("name")?, row.get("description")?, row.get("threads")?, row.get("new_threads")?))
+            })?
+            .collect::<Result<Vec<BoardRow>, _>>()?;
+        Ok(boards)
+    }
 
-            let name: String = row.get("username").unwrap();
-            let logged_in: i32 = row.get("logged_in").unwrap();
-            let online: bool;
-            if logged_in == 1 {
-                online = true;
-            }
-            else {
-                online = false;
+    /**
+    * creates a message board, `DbError::AlreadyExists` if the name is taken
+    */
+    pub fn create_board(name: &str, description: &str) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::CREATE_BOARD)?;
+        match stmt.execute([name.trim(), description.trim()]) {
+            Ok(_) => {
+                println!("Created new board {}", name.trim());
+                Ok(())
             }
-
-            // Insert the result into the HashMap, here id is the key and name is the value
-            users.push((name, online));
+            Err(err) if is_unique_violation(&err) => Err(DbError::AlreadyExists("board")),
+            Err(err) => Err(err.into()),
         }
+    }
 
-        users.reverse();
-        users
+    pub fn get_board_name(board_id: i32) -> DbResult<String> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_BOARD_NAME)?;
+        stmt.query_row([&board_id], |row| row.get("name"))
+            .optional()?
+            .ok_or(DbError::NotFound("board"))
     }
 
     /**
-    * gets the room id for the provided `room_name`, -1 if no match found
+    * gets up to `limit` threads of a board, the most recently active first,
+    * marking the ones with posts `user_id` has not read
     */
-    pub fn get_room_id_by_name(room_name: String) -> i32 {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_ROOM_BY_NAME).unwrap();
-        let mut rows = stmt.query([&room_name]).unwrap();
+    pub fn get_threads(board_id: i32, user_id: i32, offset: i32, limit: i32) -> DbResult<Vec<ThreadRow>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_THREADS)?;
+        let threads = stmt
+            .query_map([user_id, board_id, limit, offset], |row| {
+                Ok((
+                    row.get("id")?,
+                    row.get("subject")?,
+                    row.get("username")?,
+                    row.get("replies")?,
+                    row.get("last_post_date")?,
+                    row.get("is_new")?,
+                ))
+            })?
+            .collect::<Result<Vec<ThreadRow>, _>>()?;
+        Ok(threads)
+    }
 
-        if let Some(room) = rows.next().unwrap() {
-            let id: i32 = room.get("id").unwrap();
-            id
-        } else {
-            -1
-        }
+    /**
+    * the (board_id, subject) of a thread
+    */
+    pub fn get_thread(thread_id: i32) -> DbResult<(i32, String)> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_THREAD)?;
+        stmt.query_row([&thread_id], |row| Ok((row.get("board_id")?, row.get("subject")?)))
+            .optional()?
+            .ok_or(DbError::NotFound("thread"))
     }
 
     /**
-    * gets the user_id for the provided `user_name`, -1 if no match found
+    * starts a thread on `board_id` with its first post, returns the thread id
     */
-    pub fn get_user_id_by_name(user_name: &str) -> i32{
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_USER_BY_NAME).unwrap();
-        let mut rows = stmt.query([&user_name]).unwrap();
+    pub fn create_thread(board_id: i32, user_id: i32, subject: &str, body: &str) -> DbResult<i32> {
+        let mut conn = get_db_connection()?;
+        let tx = conn.transaction()?;
+        tx.execute(queries::CREATE_THREAD, params![board_id, user_id, subject.trim()])?;
+        let thread_id = tx.last_insert_rowid() as i32;
+        tx.execute(queries::CREATE_POST, params![thread_id, None::<i32>, user_id, body])?;
+        let post_id = tx.last_insert_rowid() as i32;
+        tx.execute(queries::SET_THREAD_LAST_POST, params![post_id, thread_id])?;
+        tx.execute(queries::MARK_THREAD_READ, params![user_id, thread_id, post_id])?;
+        tx.commit()?;
+        Ok(thread_id)
+    }
 
-        if let Some(user) = rows.next().unwrap() {
-            let id: i32 = user.get("id").unwrap();
-            id
-        } else {
-            -1
+    /**
+    * adds a reply to `parent_id` in a thread and returns the post id, `DbError::NotFound` if the
+    * parent is not a post of the thread. The author has read their own post, the marker only moves
+    * to it if they had read everything before it, so replies they have not seen stay new
+    */
+    pub fn reply_to_post(thread_id: i32, parent_id: i32, user_id: i32, body: &str) -> DbResult<i32> {
+        let mut conn = get_db_connection()?;
+        let tx = conn.transaction()?;
+        let last_post_id: i32 = tx.query_row(queries::GET_THREAD_LAST_POST, [thread_id], |row| row.get(0))
+            .optional()?
+            .ok_or(DbError::NotFound("thread"))?;
+        if tx.query_row(queries::IS_POST_IN_THREAD, [parent_id, thread_id], |_| Ok(())).optional()?.is_none() {
+            return Err(DbError::NotFound("post"));
         }
+        let last_read: i32 = tx.query_row(queries::GET_LAST_READ_POST, [user_id, thread_id], |row| row.get(0))
+            .optional()?
+            .unwrap_or(0);
+
+        tx.execute(queries::CREATE_POST, params![thread_id, parent_id, user_id, body])?;
+        let post_id = tx.last_insert_rowid() as i32;
+        tx.execute(queries::SET_THREAD_LAST_POST, params![post_id, thread_id])?;
+        if last_read >= last_post_id {
+            tx.execute(queries::MARK_THREAD_READ, params![user_id, thread_id, post_id])?;
+        }
+        tx.commit()?;
+        Ok(post_id)
     }
 
     /**
-    * searches for a user by username
+    * every post of a thread, oldest first
     */
-    pub fn search_users(username_query: String) -> Vec<(String, bool)>  {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::QUERY_BY_USERNAME).unwrap();
-        let pattern = format!("%{}%", username_query);
-        let mut rows = stmt.query([&pattern]).unwrap();
+    pub fn get_posts(thread_id: i32) -> DbResult<Vec<PostRow>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_POSTS)?;
+        let posts = stmt
+            .query_map([thread_id], |row| {
+                Ok((row.get("id")?, row.get("parent_id")?, row.get("username")?, row.get("created_date")?, row.get("body")?))
+            })?
+            .collect::<Result<Vec<PostRow>, _>>()?;
+        Ok(posts)
+    }
 
-        // Create an empty HashMap to store the results
-        let mut users: Vec<(String, bool)>  = Vec::new();
+    /**
+    * the newest post of the thread `user_id` has read, 0 if they never opened it
+    */
+    pub fn get_last_read_post(user_id: i32, thread_id: i32) -> DbResult<i32> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_LAST_READ_POST)?;
+        let post_id = stmt.query_row([user_id, thread_id], |row| row.get("last_read_post_id")).optional()?;
+        Ok(post_id.unwrap_or(0))
+    }
 
+    pub fn mark_thread_read(user_id: i32, thread_id: i32, post_id: i32) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::MARK_THREAD_READ)?;
+        stmt.execute([user_id, thread_id, post_id])?;
+        Ok(())
+    }
 
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * sends a mail to the user named `to_username`, returns the recipient's id
+    */
+    pub fn send_mail(from_user_id: i32, to_username: &str, subject: &str, body: &str) -> DbResult<i32> {
+        let to_user_id = Self::get_user_id_by_name(to_username)?;
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SEND_MAIL)?;
+        stmt.execute(params![from_user_id, to_user_id, subject.trim(), body])?;
+        Ok(to_user_id)
+    }
 
-            let name: String = row.get("username").unwrap();
-            let online: bool = row.get("logged_in").unwrap();
+    /**
+    * the inbox of a user, or the mail they sent when `sent` is true, newest first
+    */
+    pub fn get_mailbox(user_id: i32, sent: bool, offset: i32, limit: i32) -> DbResult<Vec<MailRow>> {
+        let conn = get_db_connection()?;
+        let query = if sent { queries::GET_SENT_MAIL } else { queries::GET_INBOX };
+        let mut stmt = conn.prepare_cached(query)?;
+        let mail = stmt
+            .query_map([user_id, limit, offset], |row| {
+                Ok((row.get("id")?, row.get("username")?, row.get("subject")?, row.get("created_date")?, row.get("read_date")?))
+            })?
+            .collect::<Result<Vec<MailRow>, _>>()?;
+        Ok(mail)
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            users.push((name, online));
-        }
+    /**
+    * a mail the user sent or received, the column is the key and its text the value.
+    * `read_date` is empty while unread and `sender_username` once the sender is deleted
+    */
+    pub fn get_mail(mail_id: i32, user_id: i32) -> DbResult<HashMap<String, String>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_MAIL)?;
+        let mail = stmt.query_row([mail_id, user_id], |row| {
+            let mut mail: HashMap<String, String> = HashMap::new();
+            for column in ["sender", "sender_username", "recipient", "subject", "body", "created_date", "read_date"] {
+                mail.insert(column.to_string(), row.get(column)?);
+            }
+            Ok(mail)
+        }).optional()?;
 
-        // Return the populated HashMap
-        users
+        mail.ok_or(DbError::NotFound("message"))
     }
 
     /**
-    * collects the messages for a room ordered by created date in descending order
-    * `offset` can be used for pagination
+    * records when the recipient first opened a mail, the sender sees it in their sent folder
     */
-    pub fn get_message_from_room(room_id: i32, offset: i32) -> Vec<(i32, String, String, String)> {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_MESSAGES_FOR_ROOM).unwrap();
-        let mut rows = stmt.query([&room_id, &offset]).unwrap();
-
-        // Create an empty HashMap to store the results
-        let mut messages: Vec<(i32, String, String, String)> = Vec::new();
-        while let Some(row) = rows.next().unwrap() {
+    pub fn mark_mail_read(mail_id: i32, user_id: i32) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::MARK_MAIL_READ)?;
+        stmt.execute([mail_id, user_id])?;
+        Ok(())
+    }
 
-            let user_id: i32 = row.get("user_id").unwrap();
-            let name: String = row.get("username").unwrap();
-            let message: String = row.get("message").unwrap();
-            let created_date: String = row.get("created_date").unwrap();
+    pub fn count_unread_mail(user_id: i32) -> DbResult<i64> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::COUNT_UNREAD_MAIL)?;
+        let count = stmt.query_row([user_id], |row| row.get(0))?;
+        Ok(count)
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            messages.push((user_id, created_date, name, message));
+    /**
+    * removes a mail from the user's folder, the row is deleted once the other side deleted it too
+    */
+    pub fn delete_mail(mail_id: i32, user_id: i32) -> DbResult<()> {
+        let mut conn = get_db_connection()?;
+        let tx = conn.transaction()?;
+        let deleted = tx.execute(queries::DELETE_MAIL_FOR_RECIPIENT, [mail_id, user_id])?
+            + tx.execute(queries::DELETE_MAIL_FOR_SENDER, [mail_id, user_id])?;
+        if deleted == 0 {
+            return Err(DbError::NotFound("message"));
         }
-        messages
+        tx.execute(queries::PURGE_DELETED_MAIL, [mail_id])?;
+        tx.commit()?;
+        Ok(())
     }
 
     /**
-    * publishes a message to a room for the user
+    * remembers that the user has seen every message in the room so far
     */
-    pub fn post_message(room_id: i32, message: String, user_id: i32) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::POST_MESSAGE_TO_ROOM).unwrap();
-        stmt.execute([&message, &user_id.to_string(), &room_id.to_string()]).expect("Failed to post to room");
+    pub fn mark_room_read(user_id: i32, room_id: i32) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::MARK_ROOM_READ)?;
+        stmt.execute([user_id, room_id])?;
+        Ok(())
     }
 
     /**
-    * saves a direct message between users
+    * remembers that the user has seen every message `other_user_id` sent them so far
     */
-    pub fn post_direct_message(user_id: i32, to_user_id: i32, message: String) {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::POST_DIRECT_MESSAGE).unwrap();
-        stmt.execute([&message, &user_id.to_string(), &to_user_id.to_string()]).expect("Failed to post to room");
+    pub fn mark_conversation_read(user_id: i32, other_user_id: i32) -> DbResult<()> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::MARK_CONVERSATION_READ)?;
+        stmt.execute([user_id, other_user_id])?;
+        Ok(())
     }
 
     /**
-    * retrieves the direct messages for between a set of users
+    * unread messages by room id, for the rooms the user has been in and has unread messages
     */
-    pub fn get_direct_messages_for(user_id: i32, to_user_id: i32, offset: i32) -> Vec<(i32, String, String, String)> {
-        let conn = get_db_connection().lock().unwrap();
-        let mut stmt = conn.prepare(queries::GET_MESSAGES_FOR_USER).unwrap();
-        let mut rows = stmt.query([&user_id, &to_user_id, &to_user_id, &user_id, &offset]).unwrap();
+    pub fn get_room_unread_counts(user_id: i32) -> DbResult<HashMap<i32, i64>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_ROOM_UNREAD_COUNTS)?;
+        let counts = stmt
+            .query_map([user_id], |row| Ok((row.get("room_id")?, row.get("unread")?)))?
+            .collect::<Result<HashMap<i32, i64>, _>>()?;
+        Ok(counts)
+    }
 
-        // Create an empty HashMap to store the results
-        let mut messages: Vec<(i32, String, String, String)> = Vec::new();
-        while let Some(row) = rows.next().unwrap() {
+    /**
+    * unread direct messages sent to the user, by the id of the sender
+    */
+    pub fn get_dm_unread_counts(user_id: i32) -> DbResult<HashMap<i32, i64>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_DM_UNREAD_COUNTS)?;
+        let counts = stmt
+            .query_map([user_id], |row| Ok((row.get("from_user_id")?, row.get("unread")?)))?
+            .collect::<Result<HashMap<i32, i64>, _>>()?;
+        Ok(counts)
+    }
 
-            let user_id: i32 = row.get("user_id").unwrap();
-            let name: String = row.get("username").unwrap();
-            let message: String = row.get("message").unwrap();
-            let created_date: String = row.get("created_date").unwrap();
+    /**
+    * the users `user_id` has exchanged direct messages with, the most recent conversation first
+    */
+    pub fn get_conversations(user_id: i32, offset: i32, limit: i32) -> DbResult<Vec<ConversationRow>> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::GET_CONVERSATIONS)?;
+        let conversations = stmt
+            .query_map([user_id, limit, offset], |row| {
+                Ok((
+                    row.get("other_user_id")?,
+                    row.get("username")?,
+                    row.get("message")?,
+                    row.get("created_date")?,
+                    row.get("sent_by_me")?,
+                    row.get("unread")?,
+                ))
+            })?
+            .collect::<Result<Vec<ConversationRow>, _>>()?;
+        Ok(conversations)
+    }
 
-            // Insert the result into the HashMap, here id is the key and name is the value
-            messages.push((user_id, created_date, name, message));
+    /**
+    * full-text search over the rooms `user_id` is not banned from and their own direct messages,
+    * the best matches first. Every word has to match, as a word or the start of one
+    */
+    pub fn search_messages(user_id: i32, text: &str, offset: i32, limit: i32) -> DbResult<Vec<SearchRow>> {
+        // quoting each word keeps FTS5 operators and punctuation in the text from being parsed
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|word| word.replace('"', ""))
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"*", word))
+            .collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
         }
-        messages
+
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::SEARCH_MESSAGES)?;
+        let results = stmt
+            .query_map(params![words.join(" "), MATCH_START, MATCH_END, SNIPPET_WORDS, user_id, limit, offset], |row| {
+                Ok((
+                    row.get("message_id")?,
+                    row.get("direct")?,
+                    row.get("target_id")?,
+                    row.get("target_name")?,
+                    row.get("author")?,
+                    row.get("created_date")?,
+                    row.get("snippet")?,
+                ))
+            })?
+            .collect::<Result<Vec<SearchRow>, _>>()?;
+        Ok(results)
+    }
+
+    /**
+    * how many messages were posted in the room after `message_id`
+    */
+    pub fn count_newer_room_messages(room_id: i32, message_id: i32) -> DbResult<i32> {
+        let conn = get_db_connection()?;
+        let mut stmt = conn.prepare_cached(queries::COUNT_NEWER_ROOM_MESSAGES)?;
+        let count = stmt.query_row([room_id, message_id], |row| row.get(0))?;
+        Ok(count)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use rusqlite::{params, Connection};
+use crate::db::queries;
+
+
+/**
+* One step of the schema, applied in order of `version` and never edited once released.
+* Add new tables and columns by appending a migration with the next version
+*/
+struct Migration {
+    version: i32,
+    description: &'static str,
+    statements: &'static [&'static str],
+}
+
+
+const MIGRATIONS: &[Migration] = &[
+    // databases created before migrations existed already have these tables, the
+    // IF NOT EXISTS statements let them pick up version 1 without changes
+    Migration {
+        version: 1,
+        description: "create users, rooms, messages and direct_messages",
+        statements: &[
+            queries::CREATE_USERS,
+            queries::CREATE_ROOMS,
+            queries::CREATE_ROOM_MESSAGES,
+            queries::CREATE_DIRECT_MESSAGES,
+        ],
+    },
+    // room occupancy is tracked by the in-memory presence registry
+    Migration {
+        version: 2,
+        description: "drop rooms.online",
+        statements: &[queries::DROP_ROOMS_ONLINE],
+    },
+    // online status comes from the sessions in the presence registry
+    Migration {
+        version: 3,
+        description: "drop users.logged_in",
+        statements: &[queries::DROP_USERS_LOGGED_IN],
+    },
+    // failed login attempts kept for sysops, the throttling itself is in memory
+    Migration {
+        version: 4,
+        description: "create failed_logins",
+        statements: &[queries::CREATE_FAILED_LOGINS],
+    },
+    Migration {
+        version: 5,
+        description: "add users.display_name, users.bio and users.location",
+        statements: &[queries::ADD_USERS_DISPLAY_NAME, queries::ADD_USERS_BIO, queries::ADD_USERS_LOCATION],
+    },
+    Migration {
+        version: 6,
+        description: "add users.role and room_roles",
+        statements: &[queries::ADD_USERS_ROLE, queries::CREATE_ROOM_ROLES, queries::BACKFILL_ROOM_OWNERS],
+    },
+    Migration {
+        version: 7,
+        description: "add users.banned",
+        statements: &[queries::ADD_USERS_BANNED],
+    },
+    Migration {
+        version: 8,
+        description: "create room_bans and room_mutes",
+        statements: &[queries::CREATE_ROOM_BANS, queries::CREATE_ROOM_MUTES],
+    },
+    Migration {
+        version: 9,
+        description: "create boards, threads, posts and thread_reads",
+        statements: &[
+            queries::CREATE_BOARDS,
+            queries::CREATE_THREADS,
+            queries::CREATE_POSTS,
+            queries::CREATE_THREADS_BOARD_INDEX,
+            queries::CREATE_POSTS_THREAD_INDEX,
+            queries::CREATE_THREAD_READS,
+            queries::SEED_GENERAL_BOARD,
+        ],
+    },
+    Migration {
+        version: 10,
+        description: "create mail",
+        statements: &[
+            queries::CREATE_MAIL,
+            queries::CREATE_MAIL_INBOX_INDEX,
+            queries::CREATE_MAIL_SENT_INDEX,
+        ],
+    },
+    Migration {
+        version: 11,
+        description: "create room_reads and dm_reads",
+        statements: &[
+            queries::CREATE_ROOM_READS,
+            queries::CREATE_DM_READS,
+            queries::CREATE_MESSAGES_ROOM_INDEX,
+            queries::CREATE_DIRECT_MESSAGES_TO_INDEX,
+            queries::BACKFILL_DM_READS,
+        ],
+    },
+    // the rebuilds index the messages written before the triggers existed
+    Migration {
+        version: 12,
+        description: "create messages_fts and direct_messages_fts",
+        statements: &[
+            queries::CREATE_MESSAGES_FTS,
+            queries::CREATE_MESSAGES_FTS_TRIGGERS,
+            queries::REBUILD_MESSAGES_FTS,
+            queries::CREATE_DIRECT_MESSAGES_FTS,
+            queries::CREATE_DIRECT_MESSAGES_FTS_TRIGGERS,
+            queries::REBUILD_DIRECT_MESSAGES_FTS,
+        ],
+    },
+];
+
+
+#[derive(Debug)]
+pub enum MigrationError {
+    // the database was migrated by a newer build
+    TooNew { database_version: i32, latest_version: i32 },
+    Failed { version: i32, error: rusqlite::Error },
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::TooNew { database_version, latest_version } => write!(
+                f,
+                "database schema is at version {} but this build only knows up to version {}, upgrade rbbs before using this database",
+                database_version, latest_version
+            ),
+            MigrationError::Failed { version, error } => write!(f, "migration {} failed: {}", version, error),
+            MigrationError::Sqlite(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(error: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(error)
+    }
+}
+
+
+/**
+* the schema version this build expects
+*/
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+
+/**
+* the schema version recorded in the database, 0 when no migration has run yet
+*/
+pub fn current_version(conn: &Connection) -> Result<i32, MigrationError> {
+    conn.execute(queries::CREATE_SCHEMA_MIGRATIONS, [])?;
+    let version: i32 = conn.query_row(queries::GET_SCHEMA_VERSION, [], |row| row.get(0))?;
+    Ok(version)
+}
+
+
+/**
+* applies every migration newer than the database, each in its own transaction together
+* with the row recording it. Returns the versions that were applied
+*/
+pub fn migrate(conn: &mut Connection) -> Result<Vec<i32>, MigrationError> {
+    let database_version = current_version(conn)?;
+    let latest_version = latest_version();
+    if database_version > latest_version {
+        return Err(MigrationError::TooNew { database_version, latest_version });
+    }
+
+    let mut applied: Vec<i32> = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > database_version) {
+        let failed = |error| MigrationError::Failed { version: migration.version, error };
+        let tx = conn.transaction().map_err(failed)?;
+        for statement in migration.statements {
+            tx.execute_batch(statement).map_err(failed)?;
+        }
+        tx.execute(queries::RECORD_MIGRATION, params![migration.version, migration.description]).map_err(failed)?;
+        tx.commit().map_err(failed)?;
+        println!("Applied migration {}: {}", migration.version, migration.description);
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
@@ -1,3 +1,5 @@
 mod queries;
 mod connection;
+pub mod error;
+pub mod migrations;
 pub mod manage;
@@ -36,42 +36,431 @@ pub const CREATE_DIRECT_MESSAGES: &str = "CREATE TABLE IF NOT EXISTS direct_mess
 )";
 
 
-pub const GET_ROOMS: &str = "SELECT * FROM rooms ORDER BY online DESC LIMIT 20 OFFSET ?";
+pub const CREATE_SCHEMA_MIGRATIONS: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    applied_date DATETIME DEFAULT CURRENT_TIMESTAMP
+)";
+
+pub const GET_SCHEMA_VERSION: &str = "SELECT COALESCE(MAX(version), 0) FROM schema_migrations";
+
+pub const DROP_ROOMS_ONLINE: &str = "ALTER TABLE rooms DROP COLUMN online";
+
+pub const DROP_USERS_LOGGED_IN: &str = "ALTER TABLE users DROP COLUMN logged_in";
+
+pub const CREATE_FAILED_LOGINS: &str = "CREATE TABLE IF NOT EXISTS failed_logins (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL,
+    remote_ip TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP
+)";
+
+pub const ADD_USERS_DISPLAY_NAME: &str = "ALTER TABLE users ADD COLUMN display_name TEXT NOT NULL DEFAULT ''";
+
+pub const ADD_USERS_BIO: &str = "ALTER TABLE users ADD COLUMN bio TEXT NOT NULL DEFAULT ''";
+
+pub const ADD_USERS_LOCATION: &str = "ALTER TABLE users ADD COLUMN location TEXT NOT NULL DEFAULT ''";
+
+pub const ADD_USERS_ROLE: &str = "ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user'";
+
+pub const CREATE_ROOM_ROLES: &str = "CREATE TABLE IF NOT EXISTS room_roles (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+)";
+
+// rooms created before room roles existed keep their creator as owner
+pub const BACKFILL_ROOM_OWNERS: &str = "INSERT OR IGNORE INTO room_roles (room_id, user_id, role) SELECT id, owner_id, 'owner' FROM rooms WHERE owner_id IN (SELECT id FROM users)";
+
+pub const ADD_USERS_BANNED: &str = "ALTER TABLE users ADD COLUMN banned INTEGER NOT NULL DEFAULT 0";
+
+pub const CREATE_ROOM_BANS: &str = "CREATE TABLE IF NOT EXISTS room_bans (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    banned_by INTEGER,
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_ROOM_MUTES: &str = "CREATE TABLE IF NOT EXISTS room_mutes (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    muted_until DATETIME NOT NULL,
+    muted_by INTEGER,
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_BOARDS: &str = "CREATE TABLE IF NOT EXISTS boards (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP
+)";
+
+// last_post_id orders the threads and tells which have posts a user has not read
+pub const CREATE_THREADS: &str = "CREATE TABLE IF NOT EXISTS threads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    board_id INTEGER NOT NULL,
+    user_id INTEGER,
+    subject TEXT NOT NULL,
+    last_post_id INTEGER NOT NULL DEFAULT 0,
+    last_post_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_POSTS: &str = "CREATE TABLE IF NOT EXISTS posts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id INTEGER NOT NULL,
+    parent_id INTEGER,
+    user_id INTEGER,
+    body TEXT NOT NULL,
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (thread_id) REFERENCES threads(id),
+    FOREIGN KEY (parent_id) REFERENCES posts(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_THREADS_BOARD_INDEX: &str = "CREATE INDEX IF NOT EXISTS threads_board_id ON threads (board_id, last_post_id)";
+
+pub const CREATE_POSTS_THREAD_INDEX: &str = "CREATE INDEX IF NOT EXISTS posts_thread_id ON posts (thread_id, id)";
+
+// the newest post of each thread the user has read
+pub const CREATE_THREAD_READS: &str = "CREATE TABLE IF NOT EXISTS thread_reads (
+    user_id INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    last_read_post_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, thread_id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (thread_id) REFERENCES threads(id)
+)";
+
+pub const SEED_GENERAL_BOARD: &str = "INSERT OR IGNORE INTO boards (name, description) VALUES ('General', 'Anything goes')";
+
+// each side deletes the mail from their own folder, it is removed once both have
+pub const CREATE_MAIL: &str = "CREATE TABLE IF NOT EXISTS mail (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    from_user_id INTEGER,
+    to_user_id INTEGER NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_date DATETIME DEFAULT CURRENT_TIMESTAMP,
+    read_date DATETIME,
+    sender_deleted INTEGER NOT NULL DEFAULT 0,
+    recipient_deleted INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (from_user_id) REFERENCES users(id),
+    FOREIGN KEY (to_user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_MAIL_INBOX_INDEX: &str = "CREATE INDEX IF NOT EXISTS mail_to_user_id ON mail (to_user_id, id)";
+
+pub const CREATE_MAIL_SENT_INDEX: &str = "CREATE INDEX IF NOT EXISTS mail_from_user_id ON mail (from_user_id, id)";
+
+// the newest message of each room and conversation the user has seen
+pub const CREATE_ROOM_READS: &str = "CREATE TABLE IF NOT EXISTS room_reads (
+    user_id INTEGER NOT NULL,
+    room_id INTEGER NOT NULL,
+    last_read_message_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, room_id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (room_id) REFERENCES rooms(id)
+)";
+
+pub const CREATE_DM_READS: &str = "CREATE TABLE IF NOT EXISTS dm_reads (
+    user_id INTEGER NOT NULL,
+    other_user_id INTEGER NOT NULL,
+    last_read_message_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, other_user_id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (other_user_id) REFERENCES users(id)
+)";
+
+pub const CREATE_MESSAGES_ROOM_INDEX: &str = "CREATE INDEX IF NOT EXISTS messages_room_id ON messages (room_id, id)";
+
+pub const CREATE_DIRECT_MESSAGES_TO_INDEX: &str = "CREATE INDEX IF NOT EXISTS direct_messages_to_user_id ON direct_messages (to_user_id, user_id, id)";
+
+// direct messages sent before the markers existed count as read
+pub const BACKFILL_DM_READS: &str = "INSERT OR IGNORE INTO dm_reads (user_id, other_user_id, last_read_message_id) \
+    SELECT to_user_id, user_id, MAX(id) FROM direct_messages WHERE to_user_id IS NOT NULL AND user_id IS NOT NULL GROUP BY to_user_id, user_id";
+
+pub const RECORD_MIGRATION: &str = "INSERT INTO schema_migrations (version, description) VALUES (?, ?)";
+
+
+pub const GET_ROOMS: &str = "SELECT id, name FROM rooms ORDER BY name LIMIT ? OFFSET ?";
 
 pub const GET_ROOM_NAME: &str = "SELECT name FROM rooms WHERE id = ? LIMIT 1";
 
-pub const GET_ROOM_BY_NAME: &str = "SELECT id FROM rooms WHERE name = ? LIMIT 1";
+pub const RENAME_ROOM: &str = "UPDATE rooms SET name = ? WHERE id = ?";
 
-pub const SEARCH_ROOMS: &str = "SELECT * FROM rooms where name LIKE ? LIMIT 20 OFFSET ?";
+pub const DELETE_ROOM_MESSAGES: &str = "DELETE FROM messages WHERE room_id = ?";
 
-pub const SEARCH_USER: &str = "SELECT * FROM users WHERE username = ?";
+pub const DELETE_ROOM_ROLES: &str = "DELETE FROM room_roles WHERE room_id = ?";
+
+pub const DELETE_ROOM_BANS: &str = "DELETE FROM room_bans WHERE room_id = ?";
+
+pub const DELETE_ROOM_MUTES: &str = "DELETE FROM room_mutes WHERE room_id = ?";
 
-pub const QUERY_BY_USERNAME: &str = "SELECT * FROM users WHERE username LIKE ?";
+pub const DELETE_ROOM_READS: &str = "DELETE FROM room_reads WHERE room_id = ?";
+
+pub const DELETE_ROOM: &str = "DELETE FROM rooms WHERE id = ?";
+
+pub const SEARCH_ROOMS: &str = "SELECT id, name FROM rooms where name LIKE ? ORDER BY name LIMIT ? OFFSET ?";
+
+pub const SEARCH_USER: &str = "SELECT * FROM users WHERE username = ?";
 
 pub const CREATE_NEW_ROOM: &str = "INSERT INTO rooms (name, owner_id) VALUES (?, ?)";
 
-pub const CREATE_NEW_USER: &str = "INSERT INTO users (username, password_hash) VALUES (?, ?)";
+pub const GET_USER_ID_BY_NAME: &str = "SELECT id FROM users WHERE username = ? COLLATE NOCASE";
+
+pub const GET_USER_ROLE: &str = "SELECT role FROM users WHERE id = ?";
+
+pub const SET_USER_ROLE: &str = "UPDATE users SET role = ? WHERE id = ?";
+
+pub const GET_ROOM_ROLE: &str = "SELECT role FROM room_roles WHERE room_id = ? AND user_id = ?";
+
+pub const SET_ROOM_ROLE: &str = "INSERT INTO room_roles (room_id, user_id, role) VALUES (?, ?, ?) ON CONFLICT (room_id, user_id) DO UPDATE SET role = excluded.role";
+
+pub const REMOVE_ROOM_ROLE: &str = "DELETE FROM room_roles WHERE room_id = ? AND user_id = ?";
+
+// inserts nothing when the name is taken in any case, so "Alice" can't register next to "alice"
+// a username can't be taken by another user's username or display name
+pub const CREATE_NEW_USER: &str = "INSERT INTO users (username, password_hash) SELECT ?1, ?2 \
+    WHERE NOT EXISTS (SELECT 1 FROM users WHERE username = ?1 COLLATE NOCASE OR display_name = ?1 COLLATE NOCASE)";
+
+// the first parameter is a JSON array of the ids of online users, they are listed first
+pub const GET_USERS: &str = "SELECT id, username FROM users ORDER BY id IN (SELECT value FROM json_each(?)) DESC, username LIMIT ? OFFSET ?";
+
+pub const SEARCH_USERS: &str = "SELECT id, username FROM users where username LIKE ? ORDER BY username LIMIT ? OFFSET ?";
+
+pub const GET_USER: &str = "SELECT username, display_name, bio, location, role, created_date FROM users WHERE id = ?";
+
+pub const GET_USERS_FOR_ADMIN: &str = "SELECT id, username, role, banned FROM users ORDER BY username LIMIT ? OFFSET ?";
+
+pub const GET_USERNAME: &str = "SELECT username FROM users WHERE id = ?";
+
+pub const SET_USER_BANNED: &str = "UPDATE users SET banned = ? WHERE id = ?";
+
+pub const GET_PASSWORD_HASH: &str = "SELECT username, password_hash FROM users WHERE id = ?";
 
-pub const LOGIN_USER: &str = "UPDATE users SET logged_in = 1 WHERE id = ?";
+pub const UPDATE_PASSWORD_HASH: &str = "UPDATE users SET password_hash = ? WHERE id = ?";
 
-pub const LOGOUT_USER: &str = "UPDATE users SET logged_in = 0 WHERE id = ?";
+// another user going by the name, as their username or their display name
+pub const IS_DISPLAY_NAME_TAKEN: &str = "SELECT 1 FROM users WHERE id != ?2 AND (username = ?1 COLLATE NOCASE OR display_name = ?1 COLLATE NOCASE)";
 
-pub const JOIN_ROOM: &str = "UPDATE rooms SET online = online + 1 WHERE id = ?";
+pub const UPDATE_PROFILE: &str = "UPDATE users SET display_name = ?, bio = ?, location = ? WHERE id = ?";
 
-pub const LEAVE_ROOM: &str = "UPDATE rooms SET online = online - 1 WHERE id = ?";
+// rooms and messages outlive their author, the messages show as from a deleted user
+pub const RELEASE_OWNED_ROOMS: &str = "UPDATE rooms SET owner_id = NULL WHERE owner_id = ?";
 
-pub const GET_ONLINE_USERS: &str = "SELECT username, logged_in FROM users ORDER BY logged_in LIMIT 20 OFFSET ?";
+pub const DELETE_USER_ROOM_ROLES: &str = "DELETE FROM room_roles WHERE user_id = ?";
 
-pub const SEARCH_USERS: &str = "SELECT * FROM users where username LIKE ? LIMIT 20 OFFSET ?";
+pub const DELETE_USER_ROOM_BANS: &str = "DELETE FROM room_bans WHERE user_id = ?";
 
-pub const GET_USER: &str = "SELECT * FROM users WHERE id = ?";
+pub const DELETE_USER_ROOM_MUTES: &str = "DELETE FROM room_mutes WHERE user_id = ?";
 
-pub const GET_USER_BY_NAME: &str = "SELECT id FROM users WHERE username = ?";
+pub const DELETE_USER_THREAD_READS: &str = "DELETE FROM thread_reads WHERE user_id = ?";
 
-pub const GET_MESSAGES_FOR_ROOM: &str = "SELECT m.user_id, m.created_date, u.username, m.message FROM messages AS m  LEFT JOIN users AS u ON m.user_id = u.id WHERE m.room_id = ? ORDER BY m.created_date DESC LIMIT 20 OFFSET ?";
+pub const DELETE_USER_ROOM_READS: &str = "DELETE FROM room_reads WHERE user_id = ?";
 
-pub const GET_MESSAGES_FOR_USER: &str = "SELECT dms.user_id, dms.to_user_id, u.username, dms.message, dms.created_date FROM direct_messages AS dms LEFT JOIN users AS u on dms.user_id = u.id WHERE (user_id = ? AND to_user_id = ?) OR (user_id = ? AND to_user_id = ?) ORDER BY dms.created_date DESC LIMIT 20 OFFSET ?";
+pub const DELETE_USER_DM_READS: &str = "DELETE FROM dm_reads WHERE user_id = ?1 OR other_user_id = ?1";
+
+// mail the user sent stays in the recipients' inboxes
+pub const DELETE_USER_MAIL: &str = "DELETE FROM mail WHERE to_user_id = ?1 OR (from_user_id = ?1 AND recipient_deleted = 1)";
+
+pub const DELETE_USER: &str = "DELETE FROM users WHERE id = ?";
+
+// messages show the author's display name followed by their username, or just the username
+pub const GET_MESSAGES_FOR_ROOM: &str = "SELECT m.id, m.user_id, m.created_date, COALESCE(NULLIF(u.display_name, '') || ' (' || u.username || ')', u.username, '[deleted user]') AS username, m.message FROM messages AS m  LEFT JOIN users AS u ON m.user_id = u.id WHERE m.room_id = ? ORDER BY m.created_date DESC, m.id DESC LIMIT ? OFFSET ?";
+
+pub const GET_MESSAGES_FOR_USER: &str = "SELECT dms.user_id, dms.to_user_id, COALESCE(NULLIF(u.display_name, '') || ' (' || u.username || ')', u.username, '[deleted user]') AS username, dms.message, dms.created_date FROM direct_messages AS dms LEFT JOIN users AS u on dms.user_id = u.id WHERE (user_id = ? AND to_user_id = ?) OR (user_id = ? AND to_user_id = ?) ORDER BY dms.created_date DESC LIMIT ? OFFSET ?";
 
 pub const POST_MESSAGE_TO_ROOM: &str = "INSERT INTO messages (message, user_id, room_id) VALUES (?, ?, ?)";
 
-pub const POST_DIRECT_MESSAGE: &str = "INSERT INTO direct_messages (message, user_id, to_user_id) VALUES (?, ?, ?)";
\ No newline at end of file
+pub const DELETE_ROOM_MESSAGE: &str = "DELETE FROM messages WHERE id = ? AND room_id = ?";
+
+pub const BAN_FROM_ROOM: &str = "INSERT OR IGNORE INTO room_bans (room_id, user_id, banned_by) VALUES (?, ?, ?)";
+
+pub const UNBAN_FROM_ROOM: &str = "DELETE FROM room_bans WHERE room_id = ? AND user_id = ?";
+
+pub const IS_BANNED_FROM_ROOM: &str = "SELECT 1 FROM room_bans WHERE room_id = ? AND user_id = ?";
+
+// muting again replaces the old end time
+pub const MUTE_IN_ROOM: &str = "INSERT INTO room_mutes (room_id, user_id, muted_until, muted_by) VALUES (?1, ?2, datetime('now', '+' || ?3 || ' minutes'), ?4) ON CONFLICT (room_id, user_id) DO UPDATE SET muted_until = excluded.muted_until, muted_by = excluded.muted_by";
+
+pub const UNMUTE_IN_ROOM: &str = "DELETE FROM room_mutes WHERE room_id = ? AND user_id = ?";
+
+// seconds left on a mute, no row once it has run out
+pub const GET_MUTE_SECONDS_LEFT: &str = "SELECT CAST(strftime('%s', muted_until) - strftime('%s', 'now') AS INTEGER) AS seconds_left FROM room_mutes WHERE room_id = ? AND user_id = ? AND muted_until > datetime('now')";
+
+pub const POST_DIRECT_MESSAGE: &str = "INSERT INTO direct_messages (message, user_id, to_user_id) VALUES (?, ?, ?)";
+
+pub const LOG_FAILED_LOGIN: &str = "INSERT INTO failed_logins (username, remote_ip, reason) VALUES (?, ?, ?)";
+
+pub const GET_FAILED_LOGINS: &str = "SELECT created_date, username, remote_ip, reason FROM failed_logins ORDER BY id DESC LIMIT ? OFFSET ?";
+
+// the first parameter is the user the new thread counts are for
+pub const GET_BOARDS: &str = "SELECT b.id, b.name, b.description, COUNT(t.id) AS threads, \
+    COALESCE(SUM(CASE WHEN t.last_post_id > COALESCE(r.last_read_post_id, 0) THEN 1 ELSE 0 END), 0) AS new_threads \
+    FROM boards AS b LEFT JOIN threads AS t ON t.board_id = b.id \
+    LEFT JOIN thread_reads AS r ON r.thread_id = t.id AND r.user_id = ? \
+    GROUP BY b.id ORDER BY b.name LIMIT ? OFFSET ?";
+
+pub const CREATE_BOARD: &str = "INSERT INTO boards (name, description) VALUES (?, ?)";
+
+pub const GET_BOARD_NAME: &str = "SELECT name FROM boards WHERE id = ?";
+
+// newest activity first, the first parameter is the user the new markers are for
+pub const GET_THREADS: &str = "SELECT t.id, t.subject, COALESCE(NULLIF(u.display_name, ''), u.username, '[deleted user]') AS username, \
+    (SELECT COUNT(*) - 1 FROM posts AS p WHERE p.thread_id = t.id) AS replies, t.last_post_date, \
+    t.last_post_id > COALESCE(r.last_read_post_id, 0) AS is_new \
+    FROM threads AS t LEFT JOIN users AS u ON t.user_id = u.id \
+    LEFT JOIN thread_reads AS r ON r.thread_id = t.id AND r.user_id = ? \
+    WHERE t.board_id = ? ORDER BY t.last_post_id DESC LIMIT ? OFFSET ?";
+
+pub const GET_THREAD: &str = "SELECT board_id, subject FROM threads WHERE id = ?";
+
+pub const CREATE_THREAD: &str = "INSERT INTO threads (board_id, user_id, subject) VALUES (?, ?, ?)";
+
+pub const CREATE_POST: &str = "INSERT INTO posts (thread_id, parent_id, user_id, body) VALUES (?, ?, ?, ?)";
+
+pub const GET_THREAD_LAST_POST: &str = "SELECT last_post_id FROM threads WHERE id = ?";
+
+pub const IS_POST_IN_THREAD: &str = "SELECT 1 FROM posts WHERE id = ? AND thread_id = ?";
+
+pub const SET_THREAD_LAST_POST: &str = "UPDATE threads SET last_post_id = ?, last_post_date = CURRENT_TIMESTAMP WHERE id = ?";
+
+pub const GET_POSTS: &str = "SELECT p.id, p.parent_id, COALESCE(NULLIF(u.display_name, '') || ' (' || u.username || ')', u.username, '[deleted user]') AS username, p.created_date, p.body \
+    FROM posts AS p LEFT JOIN users AS u ON p.user_id = u.id WHERE p.thread_id = ? ORDER BY p.id";
+
+pub const GET_LAST_READ_POST: &str = "SELECT last_read_post_id FROM thread_reads WHERE user_id = ? AND thread_id = ?";
+
+// reading an older post never moves the marker back
+pub const MARK_THREAD_READ: &str = "INSERT INTO thread_reads (user_id, thread_id, last_read_post_id) VALUES (?, ?, ?) \
+    ON CONFLICT (user_id, thread_id) DO UPDATE SET last_read_post_id = MAX(last_read_post_id, excluded.last_read_post_id)";
+
+pub const SEND_MAIL: &str = "INSERT INTO mail (from_user_id, to_user_id, subject, body) VALUES (?, ?, ?, ?)";
+
+pub const GET_INBOX: &str = "SELECT m.id, COALESCE(NULLIF(u.display_name, ''), u.username, '[deleted user]') AS username, m.subject, m.created_date, m.read_date \
+    FROM mail AS m LEFT JOIN users AS u ON m.from_user_id = u.id \
+    WHERE m.to_user_id = ? AND m.recipient_deleted = 0 ORDER BY m.id DESC LIMIT ? OFFSET ?";
+
+pub const GET_SENT_MAIL: &str = "SELECT m.id, COALESCE(NULLIF(u.display_name, ''), u.username, '[deleted user]') AS username, m.subject, m.created_date, m.read_date \
+    FROM mail AS m LEFT JOIN users AS u ON m.to_user_id = u.id \
+    WHERE m.from_user_id = ? AND m.sender_deleted = 0 ORDER BY m.id DESC LIMIT ? OFFSET ?";
+
+// only the sender and the recipient can open a mail, until they delete it
+pub const GET_MAIL: &str = "SELECT COALESCE(NULLIF(f.display_name, '') || ' (' || f.username || ')', f.username, '[deleted user]') AS sender, \
+    COALESCE(f.username, '') AS sender_username, \
+    COALESCE(NULLIF(t.display_name, '') || ' (' || t.username || ')', t.username, '[deleted user]') AS recipient, \
+    m.subject, m.body, m.created_date, COALESCE(m.read_date, '') AS read_date \
+    FROM mail AS m LEFT JOIN users AS f ON m.from_user_id = f.id LEFT JOIN users AS t ON m.to_user_id = t.id \
+    WHERE m.id = ?1 AND ((m.to_user_id = ?2 AND m.recipient_deleted = 0) OR (m.from_user_id = ?2 AND m.sender_deleted = 0))";
+
+pub const MARK_MAIL_READ: &str = "UPDATE mail SET read_date = CURRENT_TIMESTAMP WHERE id = ? AND to_user_id = ? AND read_date IS NULL";
+
+pub const COUNT_UNREAD_MAIL: &str = "SELECT COUNT(*) FROM mail WHERE to_user_id = ? AND read_date IS NULL AND recipient_deleted = 0";
+
+pub const DELETE_MAIL_FOR_RECIPIENT: &str = "UPDATE mail SET recipient_deleted = 1 WHERE id = ? AND to_user_id = ?";
+
+pub const DELETE_MAIL_FOR_SENDER: &str = "UPDATE mail SET sender_deleted = 1 WHERE id = ? AND from_user_id = ?";
+
+pub const PURGE_DELETED_MAIL: &str = "DELETE FROM mail WHERE id = ? AND sender_deleted = 1 AND recipient_deleted = 1";
+
+pub const MARK_ROOM_READ: &str = "INSERT INTO room_reads (user_id, room_id, last_read_message_id) \
+    SELECT ?1, ?2, COALESCE(MAX(id), 0) FROM messages WHERE room_id = ?2 \
+    ON CONFLICT (user_id, room_id) DO UPDATE SET last_read_message_id = MAX(last_read_message_id, excluded.last_read_message_id)";
+
+pub const MARK_CONVERSATION_READ: &str = "INSERT INTO dm_reads (user_id, other_user_id, last_read_message_id) \
+    SELECT ?1, ?2, COALESCE(MAX(id), 0) FROM direct_messages WHERE user_id = ?2 AND to_user_id = ?1 \
+    ON CONFLICT (user_id, other_user_id) DO UPDATE SET last_read_message_id = MAX(last_read_message_id, excluded.last_read_message_id)";
+
+// only rooms the user has been in have a marker, the user's own messages are not counted
+pub const GET_ROOM_UNREAD_COUNTS: &str = "SELECT r.room_id, COUNT(m.id) AS unread FROM room_reads AS r \
+    JOIN messages AS m ON m.room_id = r.room_id AND m.id > r.last_read_message_id AND m.user_id IS NOT r.user_id \
+    WHERE r.user_id = ? GROUP BY r.room_id";
+
+pub const GET_DM_UNREAD_COUNTS: &str = "SELECT dm.user_id AS from_user_id, COUNT(*) AS unread FROM direct_messages AS dm \
+    LEFT JOIN dm_reads AS r ON r.user_id = dm.to_user_id AND r.other_user_id = dm.user_id \
+    WHERE dm.to_user_id = ? AND dm.id > COALESCE(r.last_read_message_id, 0) GROUP BY dm.user_id";
+
+// one row per user the current user has exchanged direct messages with, the latest conversation first.
+// conversations with deleted users are left out
+pub const GET_CONVERSATIONS: &str = "WITH conversations AS ( \
+        SELECT CASE WHEN user_id = ?1 THEN to_user_id ELSE user_id END AS other_user_id, MAX(id) AS last_message_id \
+        FROM direct_messages WHERE user_id = ?1 OR to_user_id = ?1 GROUP BY other_user_id \
+    ) \
+    SELECT c.other_user_id, COALESCE(NULLIF(u.display_name, ''), u.username) AS username, dm.message, dm.created_date, \
+    dm.user_id = ?1 AS sent_by_me, \
+    (SELECT COUNT(*) FROM direct_messages AS n WHERE n.user_id = c.other_user_id AND n.to_user_id = ?1 \
+        AND n.id > COALESCE((SELECT r.last_read_message_id FROM dm_reads AS r WHERE r.user_id = ?1 AND r.other_user_id = c.other_user_id), 0)) AS unread \
+    FROM conversations AS c JOIN direct_messages AS dm ON dm.id = c.last_message_id JOIN users AS u ON u.id = c.other_user_id \
+    ORDER BY c.last_message_id DESC LIMIT ?2 OFFSET ?3";
+
+// full-text indexes over the message tables, the tables keep the text and the triggers keep the
+// indexes in step with every insert, edit and delete
+pub const CREATE_MESSAGES_FTS: &str = "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5( \
+    message, content='messages', content_rowid='id', tokenize='unicode61 remove_diacritics 2')";
+
+pub const CREATE_MESSAGES_FTS_TRIGGERS: &str = "
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts (rowid, message) VALUES (new.id, new.message);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, message) VALUES ('delete', old.id, old.message);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF message ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, message) VALUES ('delete', old.id, old.message);
+    INSERT INTO messages_fts (rowid, message) VALUES (new.id, new.message);
+END";
+
+pub const REBUILD_MESSAGES_FTS: &str = "INSERT INTO messages_fts (messages_fts) VALUES ('rebuild')";
+
+pub const CREATE_DIRECT_MESSAGES_FTS: &str = "CREATE VIRTUAL TABLE IF NOT EXISTS direct_messages_fts USING fts5( \
+    message, content='direct_messages', content_rowid='id', tokenize='unicode61 remove_diacritics 2')";
+
+pub const CREATE_DIRECT_MESSAGES_FTS_TRIGGERS: &str = "
+CREATE TRIGGER IF NOT EXISTS direct_messages_fts_insert AFTER INSERT ON direct_messages BEGIN
+    INSERT INTO direct_messages_fts (rowid, message) VALUES (new.id, new.message);
+END;
+CREATE TRIGGER IF NOT EXISTS direct_messages_fts_delete AFTER DELETE ON direct_messages BEGIN
+    INSERT INTO direct_messages_fts (direct_messages_fts, rowid, message) VALUES ('delete', old.id, old.message);
+END;
+CREATE TRIGGER IF NOT EXISTS direct_messages_fts_update AFTER UPDATE OF message ON direct_messages BEGIN
+    INSERT INTO direct_messages_fts (direct_messages_fts, rowid, message) VALUES ('delete', old.id, old.message);
+    INSERT INTO direct_messages_fts (rowid, message) VALUES (new.id, new.message);
+END";
+
+pub const REBUILD_DIRECT_MESSAGES_FTS: &str = "INSERT INTO direct_messages_fts (direct_messages_fts) VALUES ('rebuild')";
+
+// room messages from rooms the user is not banned from and the user's own direct messages, best
+// match first. ?1 match expression, ?2 / ?3 highlight markers, ?4 words per snippet, ?5 user.
+// direct messages with deleted users are left out, there is no conversation to open
+pub const SEARCH_MESSAGES: &str = "SELECT * FROM ( \
+    SELECT m.id AS message_id, 0 AS direct, m.room_id AS target_id, r.name AS target_name, \
+        COALESCE(NULLIF(u.display_name, '') || ' (' || u.username || ')', u.username, '[deleted user]') AS author, m.created_date, \
+        snippet(messages_fts, 0, ?2, ?3, '…', ?4) AS snippet, bm25(messages_fts) AS score \
+    FROM messages_fts JOIN messages AS m ON m.id = messages_fts.rowid JOIN rooms AS r ON r.id = m.room_id \
+    LEFT JOIN users AS u ON u.id = m.user_id \
+    WHERE messages_fts MATCH ?1 \
+        AND NOT EXISTS (SELECT 1 FROM room_bans AS b WHERE b.room_id = m.room_id AND b.user_id = ?5) \
+    UNION ALL \
+    SELECT dm.id, 1, o.id, COALESCE(NULLIF(o.display_name, ''), o.username), \
+        COALESCE(NULLIF(u.display_name, '') || ' (' || u.username || ')', u.username, '[deleted user]'), dm.created_date, \
+        snippet(direct_messages_fts, 0, ?2, ?3, '…', ?4), bm25(direct_messages_fts) \
+    FROM direct_messages_fts JOIN direct_messages AS dm ON dm.id = direct_messages_fts.rowid \
+    JOIN users AS o ON o.id = CASE WHEN dm.user_id = ?5 THEN dm.to_user_id ELSE dm.user_id END \
+    LEFT JOIN users AS u ON u.id = dm.user_id \
+    WHERE direct_messages_fts MATCH ?1 AND (dm.user_id = ?5 OR dm.to_user_id = ?5) \
+) ORDER BY score, message_id DESC LIMIT ?6 OFFSET ?7";
+
+// how many messages in the room came after the given one, the offset of the page ending at it
+pub const COUNT_NEWER_ROOM_MESSAGES: &str = "SELECT COUNT(*) FROM messages WHERE room_id = ? AND id > ?";
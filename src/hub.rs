@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use serde::Deserialize;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::error::TrySendError;
+use crate::broadcast_events::BroadcastEvent;
+
+
+/**
+* What a session can subscribe to
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Topic {
+    // messages, joins and leaves for a room
+    Room(i32),
+    // direct messages and account events for a user, shared by all their sessions
+    User(i32),
+    // logins, logouts, announcements and sessions that have not logged in, every session gets these
+    Presence,
+    // room joins, leaves and changes, only for sessions on a screen that follows presence
+    RoomList,
+}
+
+
+/**
+* What happens to a subscriber whose queue is full
+*/
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    // the event is dropped and the subscriber is flagged as lagged so it reloads its view
+    DropEvent,
+    // the subscriber is removed from the hub which ends its session
+    Disconnect,
+}
+
+
+struct Subscriber {
+    tx: Sender<BroadcastEvent>,
+    lagged: Arc<AtomicBool>,
+    topics: HashSet<Topic>,
+}
+
+
+struct HubState {
+    next_id: u64,
+    subscribers: HashMap<u64, Subscriber>,
+    topics: HashMap<Topic, HashSet<u64>>,
+}
+
+
+/**
+* Routes broadcast events to the sessions subscribed to a topic. Every subscriber has its
+* own bounded queue so a session that stops reading can't hold up the others
+*/
+pub struct Hub {
+    state: Mutex<HubState>,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+}
+
+
+impl Hub {
+
+    pub fn new(capacity: usize, policy: SlowConsumerPolicy) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(HubState {
+                next_id: 0,
+                subscribers: HashMap::new(),
+                topics: HashMap::new(),
+            }),
+            capacity,
+            policy,
+        })
+    }
+
+    /**
+    * registers a new subscriber with no topics, it is removed from the hub once the
+    * returned `Subscription` is dropped
+    */
+    pub fn subscribe(self: &Arc<Self>) -> (Subscription, Receiver<BroadcastEvent>) {
+        let (tx, rx) = channel(self.capacity);
+        let lagged = Arc::new(AtomicBool::new(false));
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.insert(id, Subscriber { tx, lagged: Arc::clone(&lagged), topics: HashSet::new() });
+        let subscription = Subscription { id, hub: Arc::clone(self), lagged };
+        (subscription, rx)
+    }
+
+    /**
+    * sends the event once to every subscriber of any of the `topics`
+    */
+    pub fn publish(&self, topics: &[Topic], event: BroadcastEvent) {
+        let mut state = self.state.lock().unwrap();
+        let mut recipients: HashSet<u64> = HashSet::new();
+        for topic in topics {
+            if let Some(ids) = state.topics.get(topic) {
+                recipients.extend(ids.iter().copied());
+            }
+        }
+
+        let mut slow: Vec<u64> = Vec::new();
+        for id in recipients {
+            let subscriber = &state.subscribers[&id];
+            match subscriber.tx.try_send(event.clone()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    if self.policy == SlowConsumerPolicy::Disconnect {
+                        slow.push(id);
+                    } else {
+                        subscriber.lagged.store(true, Ordering::SeqCst);
+                    }
+                }
+                // the session is already gone, its subscription will clean up
+                Err(TrySendError::Closed(_)) => {}
+            }
+        }
+
+        for id in slow {
+            println!("Disconnecting slow subscriber {}", id);
+            Self::remove(&mut state, id);
+        }
+    }
+
+    fn add_topic(&self, id: u64, topic: Topic) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(subscriber) = state.subscribers.get_mut(&id) {
+            subscriber.topics.insert(topic);
+            state.topics.entry(topic).or_default().insert(id);
+        }
+    }
+
+    fn remove_topic(&self, id: u64, topic: Topic) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(subscriber) = state.subscribers.get_mut(&id) {
+            subscriber.topics.remove(&topic);
+        }
+        if let Some(ids) = state.topics.get_mut(&topic) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                state.topics.remove(&topic);
+            }
+        }
+    }
+
+    fn remove(state: &mut HubState, id: u64) {
+        if let Some(subscriber) = state.subscribers.remove(&id) {
+            for topic in subscriber.topics {
+                if let Some(ids) = state.topics.get_mut(&topic) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        state.topics.remove(&topic);
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/**
+* A session's handle on the hub, used to change its topics. Dropping it unsubscribes
+* the session from everything
+*/
+pub struct Subscription {
+    id: u64,
+    hub: Arc<Hub>,
+    lagged: Arc<AtomicBool>,
+}
+
+
+impl Subscription {
+
+    pub fn join(&self, topic: Topic) {
+        self.hub.add_topic(self.id, topic);
+    }
+
+    pub fn leave(&self, topic: Topic) {
+        self.hub.remove_topic(self.id, topic);
+    }
+
+    /**
+    * true if events were dropped since the last call because the queue was full
+    */
+    pub fn take_lagged(&self) -> bool {
+        self.lagged.swap(false, Ordering::SeqCst)
+    }
+}
+
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut state = self.hub.state.lock().unwrap();
+        Hub::remove(&mut state, self.id);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::error::TryRecvError;
+
+    fn drain(rx: &mut Receiver<BroadcastEvent>) -> Vec<BroadcastEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn event_on_several_topics_arrives_once() {
+        let hub = Hub::new(8, SlowConsumerPolicy::DropEvent);
+        let (subscription, mut rx) = hub.subscribe();
+        subscription.join(Topic::Room(1));
+        subscription.join(Topic::User(2));
+        subscription.join(Topic::Presence);
+        hub.publish(&[Topic::Room(1), Topic::User(2), Topic::Presence], BroadcastEvent::RoomMessage { room_id: 1 });
+        assert_eq!(drain(&mut rx), vec![BroadcastEvent::RoomMessage { room_id: 1 }]);
+    }
+
+    #[test]
+    fn only_subscribers_of_the_topic_get_the_event() {
+        let hub = Hub::new(8, SlowConsumerPolicy::DropEvent);
+        let (in_room, mut in_room_rx) = hub.subscribe();
+        let (_elsewhere, mut elsewhere_rx) = hub.subscribe();
+        in_room.join(Topic::Room(1));
+        hub.publish(&[Topic::Room(1)], BroadcastEvent::RoomMessage { room_id: 1 });
+        assert_eq!(drain(&mut in_room_rx).len(), 1);
+        assert!(drain(&mut elsewhere_rx).is_empty());
+    }
+
+    #[test]
+    fn leave_unsubscribes_from_the_topic() {
+        let hub = Hub::new(8, SlowConsumerPolicy::DropEvent);
+        let (subscription, mut rx) = hub.subscribe();
+        subscription.join(Topic::Room(1));
+        subscription.join(Topic::Presence);
+        subscription.leave(Topic::Room(1));
+        hub.publish(&[Topic::Room(1)], BroadcastEvent::RoomMessage { room_id: 1 });
+        assert!(drain(&mut rx).is_empty());
+        assert!(!hub.state.lock().unwrap().topics.contains_key(&Topic::Room(1)));
+
+        // the other topics stay
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+        assert_eq!(drain(&mut rx), vec![BroadcastEvent::AnonLogout]);
+    }
+
+    #[test]
+    fn drop_unsubscribes_from_everything() {
+        let hub = Hub::new(8, SlowConsumerPolicy::DropEvent);
+        let (subscription, mut rx) = hub.subscribe();
+        subscription.join(Topic::Room(1));
+        subscription.join(Topic::User(2));
+        drop(subscription);
+        {
+            let state = hub.state.lock().unwrap();
+            assert!(state.subscribers.is_empty());
+            assert!(state.topics.is_empty());
+        }
+        hub.publish(&[Topic::Room(1), Topic::User(2)], BroadcastEvent::RoomMessage { room_id: 1 });
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn full_queue_drops_the_event_and_flags_lag() {
+        let hub = Hub::new(2, SlowConsumerPolicy::DropEvent);
+        let (subscription, mut rx) = hub.subscribe();
+        subscription.join(Topic::Presence);
+        for _ in 0..3 {
+            hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+        }
+        assert!(subscription.take_lagged());
+        // the flag is cleared once taken
+        assert!(!subscription.take_lagged());
+        assert_eq!(drain(&mut rx).len(), 2);
+
+        // still subscribed once the queue has room again
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+        assert_eq!(drain(&mut rx).len(), 1);
+        assert!(!subscription.take_lagged());
+    }
+
+    #[test]
+    fn full_queue_disconnects_the_subscriber() {
+        let hub = Hub::new(2, SlowConsumerPolicy::Disconnect);
+        let (subscription, mut rx) = hub.subscribe();
+        let (other, mut other_rx) = hub.subscribe();
+        subscription.join(Topic::Presence);
+        other.join(Topic::Presence);
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+        assert_eq!(drain(&mut other_rx).len(), 2);
+        hub.publish(&[Topic::Presence], BroadcastEvent::AnonLogout);
+
+        // the queued events are still delivered, then the receiver is closed
+        assert_eq!(drain(&mut rx).len(), 2);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert!(!subscription.take_lagged());
+        // a subscriber that kept up is not affected
+        assert_eq!(drain(&mut other_rx).len(), 1);
+    }
+}
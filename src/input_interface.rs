@@ -1,15 +1,31 @@
 use crate::views::login_register_view::LoginRegisterView;
 use crate::views::rooms_view::RoomsView;
 use crate::views::room_view::RoomView;
-use crate::views::base_view::View;
+use crate::views::base_view::{View, WindowSize};
+use std::net::IpAddr;
 use std::str;
 use std::sync::{Arc, Mutex};
-use crate::db::manage::Manager;
 use crate::views::base_view::NavigateTo;
 use crate::views::menu_view::BBSMenu;
 use crate::views::users_view::UsersView;
 use crate::views::user_view::UserView;
 use crate::views::direct_message_view::DirectMessageView;
+use crate::views::conversations_view::ConversationsView;
+use crate::views::search_view::SearchView;
+use crate::views::account_view::AccountView;
+use crate::views::admin_view::AdminView;
+use crate::views::boards_view::BoardsView;
+use crate::views::threads_view::ThreadsView;
+use crate::views::thread_view::ThreadView;
+use crate::views::compose_view::ComposeView;
+use crate::views::mailbox_view::MailboxView;
+use crate::views::mail_read_view::MailReadView;
+use crate::views::mail_compose_view::MailComposeView;
+use crate::line_editor::LineEditor;
+use crate::config;
+use crate::presence::SessionId;
+use crate::db::error::DbError;
+use crate::db::manage::Manager;
 
 #[derive(PartialEq, Eq)]
 pub enum Events {
@@ -17,16 +33,29 @@ pub enum Events {
     DownArrow,
     LeftArrow,
     RightArrow,
+    Home,
+    End,
+    Delete,
     Enter,
     Exit,
-    TAB,
+    Tab,
+    KeyA,
+    KeyB,
+    KeyD,
+    KeyK,
+    KeyP,
     KeyN,
     KeyS,
     KeyH,
     KeyC,
+    KeyE,
+    KeyF,
+    KeyM,
     KeyR,
     CntrlN,
     CntrlQ,
+    CntrlU,
+    CntrlW,
     Authenticate,
     NavigateView,
     InputModeEnable,
@@ -37,7 +66,12 @@ pub enum Events {
     RoomLeave,
     DirectMessageSent,
     RoomMessageSent,
-    UserView,
+    EndSession,
+    AccountDeleted,
+    AdminCommand,
+    // the user lost the rights for the current view
+    AccessDenied,
+    KickUser,
     BackSpace,
     SpaceBar
 }
@@ -45,20 +79,46 @@ pub enum Events {
 impl Events {
     pub(crate) fn from_int(value: i32) -> Events {
         match value {
+            1  => Events::Home, // Cntrl+A
             3  => Events::Exit, // Cntrl+C
+            5  => Events::End, // Cntrl+E
+            8  => Events::BackSpace, // Cntrl+H
             13 => Events::Enter,
             14 => Events::CntrlN,
             17 => Events::CntrlQ,
-            65 => Events::UpArrow,
-            66 => Events::DownArrow,
+            21 => Events::CntrlU,
+            23 => Events::CntrlW,
+            97 => Events::KeyA,
+            98 => Events::KeyB,
             99 => Events::KeyC,
+            100 => Events::KeyD,
+            101 => Events::KeyE,
+            102 => Events::KeyF,
             104 => Events::KeyH,
+            107 => Events::KeyK,
+            109 => Events::KeyM,
             110 => Events::KeyN,
+            112 => Events::KeyP,
             114 => Events::KeyR,
             115 => Events::KeyS,
             _ => Events::Unknown
         }
     }
+
+    /**
+    * maps the final byte of an `ESC [` or `ESC O` cursor key sequence
+    */
+    pub(crate) fn from_escape_code(value: u8) -> Events {
+        match value {
+            b'A' => Events::UpArrow,
+            b'B' => Events::DownArrow,
+            b'C' => Events::RightArrow,
+            b'D' => Events::LeftArrow,
+            b'H' => Events::Home,
+            b'F' => Events::End,
+            _ => Events::Unknown
+        }
+    }
 }
 
 
@@ -68,62 +128,183 @@ pub struct UserInterface {
     current_view:  Arc<Mutex<dyn View>>,
     current_room: i32,
     input_mode: bool,
+    // while a password is typed, whether the server was echoing before it started
+    echo_before_secret: Option<bool>,
+    session_id: SessionId,
     user_id: i32,
-    user_input: String
+    username: String,
+    line_editor: LineEditor,
+    window_size: WindowSize,
+    // the start of a UTF-8 character whose other bytes have not been read yet
+    utf8_remainder: Vec<u8>,
 }
 
 
 impl UserInterface {
 
-    pub fn new() -> Self {
-        let login_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(LoginRegisterView::new()));
+    pub fn new(session_id: SessionId, remote_ip: IpAddr) -> Self {
+        let login_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(LoginRegisterView::new(remote_ip)));
 
         Self {
+            session_id,
             user_id: -1,
+            username: String::new(),
             current_view:  login_view,
             input_mode: false,
+            echo_before_secret: None,
             current_room: -1,
-            user_input: String::new(),
+            line_editor: LineEditor::new(),
+            window_size: WindowSize::default(),
+            utf8_remainder: Vec::new(),
         }
     }
 
 
-    pub fn clear_user_input(&mut self) {
-        self.user_input = String::new();
+    /**
+    * clears the input after it was sent, remembering it in the input history unless it was secret
+    */
+    pub fn submit_user_input(&mut self, remember: bool) {
+        if remember {
+            self.line_editor.submit();
+        } else {
+            self.line_editor.clear();
+        }
     }
 
     pub fn get_user_input(&self) -> String {
-        self.user_input.clone()
+        self.line_editor.get_text().to_string()
     }
 
-    pub fn handle_input_event(&mut self, buffer_str: &String, event: &Events) {
+    pub fn handle_input_event(&mut self, buffer_str: &str, event: &Events) {
         if *event == Events::Exit || *event == Events::Enter {
-            return;
+            // nothing to collect when the input is submitted or the session exits
+        }
+
+        else if *event == Events::BackSpace {
+            self.line_editor.backspace();
         }
 
-        else if *event == Events::BackSpace && self.user_input.len() > 0 {
-            self.user_input.remove(self.user_input.len() - 1);
+        else if *event == Events::Delete {
+            self.line_editor.delete();
         }
 
+        else if *event == Events::LeftArrow {
+            self.line_editor.move_left();
+        }
+
+        else if *event == Events::RightArrow {
+            self.line_editor.move_right();
+        }
+
+        else if *event == Events::Home {
+            self.line_editor.move_home();
+        }
+
+        else if *event == Events::End {
+            self.line_editor.move_end();
+        }
+
+        else if *event == Events::CntrlW {
+            self.line_editor.delete_word();
+        }
+
+        else if *event == Events::CntrlU {
+            self.line_editor.kill_line();
+        }
+
+        // prompts browse the input history, unless the view scrolls with the arrow keys
+        // or a password is typed, which must not be filled in from the history
+        else if *event == Events::UpArrow || *event == Events::DownArrow {
+            let scrolls = self.is_in_secret_input() || self.current_view.lock().unwrap().scrolls_with_arrows();
+            if !scrolls && *event == Events::UpArrow {
+                self.line_editor.history_previous();
+            }
+            else if !scrolls {
+                self.line_editor.history_next();
+            }
+        }
 
         else if *event == Events::SpaceBar {
-            self.user_input.push_str(" ")
+            self.line_editor.insert(" ")
         }
 
         else {
-            let cleaned_string: String = buffer_str.chars()
-                .filter(|&c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation())  // Retain only printable Unicode characters
-                .collect();
+            self.line_editor.insert(buffer_str);
+        }
 
-            self.user_input.push_str(cleaned_string.as_str());
+    }
+
+    /**
+    * renders the current view and moves the terminal cursor back to the line editor cursor,
+    * secret input is shown as one `display.password_mask` per character after the prompt
+    */
+    pub fn render_current_view(&self) -> String {
+        let view = self.current_view.lock().unwrap();
+        let mut output = view.render(&self.window_size);
+        if self.is_in_secret_input() {
+            let typed = self.line_editor.get_text().chars().count();
+            output.push_str(&config::get().display.password_mask.repeat(typed));
+            return output;
         }
+        let columns = self.line_editor.columns_after_cursor();
+        if self.input_mode && columns > 0 {
+            output.push_str(&format!("\x1b[{}D", columns));
+        }
+        output
+    }
 
+    /**
+    * true when the view is asking for input that must stay hidden, like a password
+    */
+    pub fn wants_secret_input(&self) -> bool {
+        self.input_mode && self.current_view.lock().unwrap().is_secret_input()
+    }
+
+    pub fn is_in_secret_input(&self) -> bool {
+        self.echo_before_secret.is_some()
+    }
+
+    /**
+    * starts hidden input on an empty line, `echo_before` is restored by `end_secret_input`
+    */
+    pub fn start_secret_input(&mut self, echo_before: bool) {
+        self.echo_before_secret = Some(echo_before);
+        self.line_editor.clear();
+    }
+
+    /**
+    * ends hidden input, returning whether the server was echoing before it started
+    */
+    pub fn end_secret_input(&mut self) -> Option<bool> {
+        self.echo_before_secret.take()
+    }
+
+    pub fn get_window_size(&self) -> WindowSize {
+        self.window_size
+    }
+
+    /**
+    * stores the client terminal size and lets the current view reload data to fit it
+    */
+    pub fn set_window_size(&mut self, width: usize, height: usize) {
+        self.window_size = WindowSize { width, height };
+        let binding = self.get_current_view();
+        let mut view = binding.lock().unwrap();
+        view.resize(&self.window_size);
     }
 
     pub fn get_user_id(&self) -> i32 {
         self.user_id
     }
 
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn get_session_id(&self) -> SessionId {
+        self.session_id
+    }
+
     pub fn set_current_room_id(&mut self, room_id: i32) {
         self.current_room = room_id;
     }
@@ -133,17 +314,56 @@ impl UserInterface {
     }
 
 
-    pub fn join_room(&mut self) -> i32{
-        let binding = self.get_current_view();
-        let binding = binding.lock().unwrap();
-        let rooms_view = binding
-            .as_any()
-            .downcast_ref::<RoomsView>().unwrap();
-        let room_name = rooms_view.get_selection();
-        let room_id = Manager::get_room_id_by_name(room_name.to_string());
-        Manager::add_to_room_online(room_id);
-        self.current_room = room_id;
-        room_id
+    /**
+    * sends the session back to the room list with `notice`, used when it is kicked or banned
+    * from its room or the room is deleted
+    */
+    pub fn return_to_rooms(&mut self, notice: &str) {
+        self.current_room = -1;
+        self.set_input_mode(false);
+        let mut rooms_view = RoomsView::new(self.user_id, &self.window_size);
+        rooms_view.show_notice(notice);
+        self.current_view = Arc::new(Mutex::new(rooms_view));
+    }
+
+    /**
+    * sends the session back to the menu with `error`, used when it loses the rights
+    * for the screen it is on
+    */
+    pub fn return_to_menu(&mut self, error: &str) {
+        self.set_input_mode(false);
+        let mut menu_view = BBSMenu::new(self.user_id);
+        menu_view.show_error(error);
+        self.current_view = Arc::new(Mutex::new(menu_view));
+    }
+
+    /**
+    * enters the room picked in the room list or in the search results, users banned from it
+    * are sent to the room list and get None
+    */
+    pub fn join_room(&mut self) -> Option<i32> {
+        let room_id = {
+            let binding = self.get_current_view();
+            let binding = binding.lock().unwrap();
+            match binding.as_any().downcast_ref::<RoomsView>() {
+                Some(rooms_view) => rooms_view.get_selected_room_id(),
+                None => binding.as_any().downcast_ref::<SearchView>().unwrap().get_selected_room_id(),
+            }
+        };
+        match Manager::is_banned_from_room(room_id, self.user_id) {
+            Ok(false) => {
+                self.current_room = room_id;
+                Some(room_id)
+            }
+            Ok(true) => {
+                self.return_to_rooms(&DbError::BannedFromRoom.user_message());
+                None
+            }
+            Err(e) => {
+                self.return_to_rooms(&e.report("joining a room"));
+                None
+            }
+        }
     }
 
     pub fn set_user_id(&mut self) {
@@ -152,14 +372,14 @@ impl UserInterface {
         let login_view = binding
             .as_any()
             .downcast_ref::<LoginRegisterView>().unwrap();
-        let user_id = login_view.get_user_id();
-        self.user_id = user_id;
+        self.user_id = login_view.get_user_id();
+        self.username = login_view.get_username().to_string();
     }
 
     pub fn set_input_mode(&mut self, active: bool) {
         self.input_mode = active;
         if !self.input_mode {
-            self.user_input = String::new();
+            self.line_editor.clear();
         }
     }
 
@@ -172,44 +392,58 @@ impl UserInterface {
     }
 
     pub fn get_user_event(buffer: &[u8]) -> Events {
-        let event: Events;
-        if buffer[0] == 27 && buffer[1] == 91 {
-            event = Events::from_int(buffer[2] as i32)
-        } else if buffer[0] == 13 {
-            event = Events::from_int(buffer[0] as i32)
-        } else if buffer[0] == b'\t' {
-            event = Events::TAB
-        } else if buffer[0] == 0x1b {
-            event = Events::NavigateView
-        } else if buffer[0] == 127 {
-            event = Events::BackSpace
-        } else if buffer[0] == 32 {
-            event = Events::SpaceBar;
-        } else {
-            event =  Events::from_int(buffer[0] as i32)
-        }
-        event
-    }
-
-    pub fn clean_buffer(buffer: &[u8]) -> String {
-        let buffer_string: &str;
-        let cleaned_buffer: Vec<u8> = buffer
-            .iter()                     // Iterate over the slice
-            .filter(|&&x| x != 0)  // Filter out all zeros
-            .copied()                   // Dereference the references to get u8 values
-            .collect();
-        match str::from_utf8(cleaned_buffer.as_slice()) {
-            Ok(v) => {
-                buffer_string = v.trim();
-            },
-            Err(_) => {
-                buffer_string = "";
-            },
-        };
-        buffer_string.trim().to_string()
+        match buffer {
+            [27, 91, b'3', b'~', ..] => Events::Delete,
+            [27, 91, b'1' | b'7', b'~', ..] => Events::Home,
+            [27, 91, b'4' | b'8', b'~', ..] => Events::End,
+            [27, 91 | b'O', code, ..] => Events::from_escape_code(*code),
+            [13, ..] => Events::Enter,
+            [b'\t', ..] => Events::Tab,
+            [0x1b, ..] => Events::NavigateView,
+            [127, ..] => Events::BackSpace,
+            [32, ..] => Events::SpaceBar,
+            [first, ..] => Events::from_int(*first as i32),
+            [] => Events::Unknown,
+        }
+    }
+
+    /**
+    * turns the bytes of one read into text. A character cut off at the end of the read is
+    * kept and completed by the next one, invalid bytes are skipped
+    */
+    pub fn clean_buffer(&mut self, buffer: &[u8]) -> String {
+        let mut cleaned_buffer: Vec<u8> = std::mem::take(&mut self.utf8_remainder);
+        cleaned_buffer.extend(buffer.iter().filter(|&&x| x != 0));
+
+        let mut buffer_string = String::new();
+        let mut rest = cleaned_buffer.as_slice();
+        loop {
+            match str::from_utf8(rest) {
+                Ok(v) => {
+                    buffer_string.push_str(v);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    buffer_string.push_str(str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(invalid) => rest = &after[invalid..],
+                        None => {
+                            // the rest of the character comes with the next read
+                            self.utf8_remainder = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        // spaces are kept, a paste split across reads can have one on either side of the cut
+        buffer_string.trim_matches(char::is_control).to_string()
     }
 
     pub fn navigate_view(&mut self) {
+        // only the views that take text turn input mode back on
+        self.set_input_mode(false);
         let binding = self.get_current_view();
         let view = binding.lock().unwrap();
         let navigate_to = view.get_navigate_to();
@@ -217,47 +451,163 @@ impl UserInterface {
 
 
         if *navigate_to == NavigateTo::RoomsView {
-            let rooms_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(RoomsView::new(user_id)));
+            let rooms_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(RoomsView::new(user_id, &self.window_size)));
             self.current_view= rooms_view
 
         }
         else if *navigate_to == NavigateTo::MenuView {
-            let menu_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(BBSMenu::new()));
+            let menu_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(BBSMenu::new(user_id)));
             self.current_view = menu_view
 
         }
         else if *navigate_to == NavigateTo::PeopleView {
-            let menu_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UsersView::new()));
+            let menu_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UsersView::new(user_id, &self.window_size)));
             self.current_view = menu_view
         }
 
         else if *navigate_to == NavigateTo::MeView {
-            let myself_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UserView::new(user_id, true)));
+            let myself_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UserView::new(user_id, true, self.session_id)));
             self.current_view =  myself_view;
         }
 
+        else if *navigate_to == NavigateTo::AccountView {
+            let account_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(AccountView::new(user_id)));
+            self.current_view = account_view;
+        }
+
+        else if *navigate_to == NavigateTo::AdminView {
+            let admin_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(AdminView::new(user_id, self.session_id, &self.window_size)));
+            self.current_view = admin_view;
+        }
+
+        else if *navigate_to == NavigateTo::BoardsView {
+            let boards_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(BoardsView::new(user_id, &self.window_size)));
+            self.current_view = boards_view;
+        }
+
+        else if let NavigateTo::ThreadsView { board_id } = *navigate_to {
+            let threads_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(ThreadsView::new(board_id, user_id, &self.window_size)));
+            self.current_view = threads_view;
+        }
+
+        else if let NavigateTo::ThreadView { thread_id, post_id } = *navigate_to {
+            let thread_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(ThreadView::new(thread_id, post_id, user_id, &self.window_size)));
+            self.current_view = thread_view;
+        }
+
+        else if let NavigateTo::ComposeView { board_id, thread_id, reply_to } = *navigate_to {
+            let compose_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(ComposeView::new(user_id, board_id, thread_id, reply_to)));
+            self.input_mode = true;
+            self.current_view = compose_view;
+        }
+
+        else if let NavigateTo::MailboxView { sent } = *navigate_to {
+            let mailbox_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(MailboxView::new(user_id, sent, &self.window_size)));
+            self.current_view = mailbox_view;
+        }
+
+        else if let NavigateTo::MailReadView { mail_id, sent } = *navigate_to {
+            let mail_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(MailReadView::new(mail_id, sent, user_id, &self.window_size)));
+            self.current_view = mail_view;
+        }
+
+        else if let NavigateTo::MailComposeView { mail_id, forward } = *navigate_to {
+            let compose_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(MailComposeView::new(user_id, mail_id, forward)));
+            self.input_mode = true;
+            self.current_view = compose_view;
+        }
+
         else if *navigate_to == NavigateTo::UserView {
             let user_view = view.as_any().downcast_ref::<UsersView>().unwrap();
-            let view_user_id = Manager::get_user_id_by_name(user_view.get_selection());
-            let user_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UserView::new(view_user_id, false)));
+            let view_user_id = user_view.get_selected_user_id();
+            let user_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(UserView::new(view_user_id, false, self.session_id)));
             self.current_view = user_view;
         }
 
         else if *navigate_to == NavigateTo::RoomView {
             let room_id = self.get_current_room_id();
-            let room_name = Manager::get_room_name_by_id(room_id);
-            let room_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(RoomView::new(room_id, room_name, user_id)));
+            let mut room_view = RoomView::new(room_id, user_id, &self.window_size);
+            // a search result opens the room at the message it found
+            if let Some(search_view) = view.as_any().downcast_ref::<SearchView>() {
+                room_view.show_message(search_view.get_selected_message_id());
+            }
+            let room_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(room_view));
             self.input_mode = true;
             self.current_view = room_view;
         }
 
+        else if *navigate_to == NavigateTo::ConversationsView {
+            let conversations_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(ConversationsView::new(user_id, &self.window_size)));
+            self.current_view = conversations_view;
+        }
+
+        else if *navigate_to == NavigateTo::SearchView {
+            let search_view: Arc<Mutex<dyn View>> = Arc::new(Mutex::new(SearchView::new(user_id, &self.window_size)));
+            self.input_mode = true;
+            self.current_view = search_view;
+        }
+
         else if *navigate_to == NavigateTo::DirectMessageView {
-            let user_view = view.as_any().downcast_ref::<UserView>().unwrap();
-            let to_user_id = user_view.get_user_id();
-            let dm_view = Arc::new(Mutex::new(DirectMessageView::new(user_id, to_user_id)));
+            // a conversation is opened from a user's profile, the conversation list or a search result
+            let to_user_id = if let Some(user_view) = view.as_any().downcast_ref::<UserView>() {
+                user_view.get_user_id()
+            } else if let Some(conversations_view) = view.as_any().downcast_ref::<ConversationsView>() {
+                conversations_view.get_selected_user_id()
+            } else {
+                view.as_any().downcast_ref::<SearchView>().unwrap().get_selected_user_id()
+            };
+            let dm_view = Arc::new(Mutex::new(DirectMessageView::new(user_id, to_user_id, &self.window_size)));
             self.input_mode = true;
             self.current_view = dm_view;
         }
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn interface() -> UserInterface {
+        UserInterface::new(1, IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    #[test]
+    fn character_split_across_reads() {
+        let mut ui = interface();
+        let text = "né😀";
+        let bytes = text.as_bytes();
+        // cut inside the é, then inside the emoji
+        assert_eq!(ui.clean_buffer(&bytes[..2]), "n");
+        assert_eq!(ui.clean_buffer(&bytes[2..5]), "é");
+        assert_eq!(ui.clean_buffer(&bytes[5..]), "😀");
+    }
+
+    #[test]
+    fn every_split_keeps_the_text() {
+        let text = "añ€😀b".as_bytes();
+        for split in 0..=text.len() {
+            let mut ui = interface();
+            let mut received = ui.clean_buffer(&text[..split]);
+            received.push_str(&ui.clean_buffer(&text[split..]));
+            assert_eq!(received, "añ€😀b", "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn invalid_bytes_are_skipped() {
+        let mut ui = interface();
+        assert_eq!(ui.clean_buffer(&[b'a', 0xff, b'b', 0, b'c']), "abc");
+        // a lone continuation byte is not kept for the next read
+        assert_eq!(ui.clean_buffer(&[b'x', 0x80]), "x");
+        assert_eq!(ui.clean_buffer(b"y"), "y");
+    }
+
+    #[test]
+    fn spaces_at_the_cut_are_kept() {
+        let mut ui = interface();
+        assert_eq!(ui.clean_buffer(b"hello "), "hello ");
+        assert_eq!(ui.clean_buffer(b" world\r"), " world");
+    }
+}
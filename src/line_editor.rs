@@ -0,0 +1,322 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+
+/**
+* Editable line of user input. The cursor is a byte offset into `text` that always sits on a
+* grapheme boundary, so multi-byte characters and emoji are inserted and removed as a whole
+*/
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    draft: String,
+}
+
+
+impl LineEditor {
+
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+            draft: String::new(),
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /**
+    * clears the line, history is kept
+    */
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.history_index = None;
+        self.draft.clear();
+    }
+
+    /**
+    * stores the current line in the history (skipping blanks and repeats) then clears it
+    */
+    pub fn submit(&mut self) {
+        let line = self.text.trim();
+        if !line.is_empty() && self.history.last().map(|last| last.as_str()) != Some(line) {
+            self.history.push(line.to_string());
+        }
+        self.clear();
+    }
+
+    /**
+    * inserts the printable characters of `input` at the cursor
+    */
+    pub fn insert(&mut self, input: &str) {
+        let printable: String = input.chars().filter(|c| !c.is_control()).collect();
+        self.text.insert_str(self.cursor, &printable);
+        self.cursor += printable.len();
+    }
+
+    /**
+    * removes the grapheme before the cursor
+    */
+    pub fn backspace(&mut self) {
+        let start = self.previous_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /**
+    * removes the grapheme under the cursor
+    */
+    pub fn delete(&mut self) {
+        let end = self.next_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.previous_boundary();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_boundary();
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /**
+    * removes the word before the cursor along with any whitespace after it (Ctrl+W)
+    */
+    pub fn delete_word(&mut self) {
+        let before = &self.text[..self.cursor];
+        let trimmed = before.trim_end();
+        let start = trimmed
+            .unicode_word_indices()
+            .next_back()
+            .map(|(idx, _)| idx)
+            .unwrap_or(0);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /**
+    * removes everything before the cursor (Ctrl+U)
+    */
+    pub fn kill_line(&mut self) {
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+    }
+
+    /**
+    * replaces the line with the previous history entry, the line being typed is kept as a draft
+    */
+    pub fn history_previous(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            None => {
+                self.draft = self.text.clone();
+                self.history.len() - 1
+            }
+            Some(0) => 0,
+            Some(idx) => idx - 1,
+        };
+        self.history_index = Some(index);
+        self.set_text(self.history[index].clone());
+    }
+
+    /**
+    * replaces the line with the next history entry, or the draft once past the newest entry
+    */
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(idx) if idx + 1 < self.history.len() => {
+                self.history_index = Some(idx + 1);
+                self.set_text(self.history[idx + 1].clone());
+            }
+            Some(_) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_text(draft);
+            }
+        }
+    }
+
+    /**
+    * display width of the text after the cursor, used to put the terminal cursor back in place
+    */
+    pub fn columns_after_cursor(&self) -> usize {
+        self.text[self.cursor..].width()
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.text = text;
+        self.cursor = self.text.len();
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(idx, _)| idx)
+            .unwrap_or(0)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map(|grapheme| self.cursor + grapheme.len())
+            .unwrap_or(self.cursor)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor_with(text: &str) -> LineEditor {
+        let mut editor = LineEditor::new();
+        editor.insert(text);
+        editor
+    }
+
+    #[test]
+    fn backspace_removes_a_whole_grapheme() {
+        // family emoji joined with ZWJ, then e + combining acute
+        let mut editor = editor_with("a👨‍👩‍👧e\u{301}");
+        editor.backspace();
+        assert_eq!(editor.get_text(), "a👨‍👩‍👧");
+        editor.backspace();
+        assert_eq!(editor.get_text(), "a");
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.get_text(), "");
+    }
+
+    #[test]
+    fn editing_in_the_middle_of_the_line() {
+        let mut editor = editor_with("héllo");
+        editor.move_left();
+        editor.move_left();
+        editor.move_left();
+        editor.backspace();
+        assert_eq!(editor.get_text(), "hllo");
+        editor.insert("é");
+        editor.delete();
+        assert_eq!(editor.get_text(), "hélo");
+        assert_eq!(editor.columns_after_cursor(), 2);
+        editor.move_home();
+        editor.delete();
+        editor.move_end();
+        editor.delete();
+        assert_eq!(editor.get_text(), "élo");
+    }
+
+    #[test]
+    fn control_characters_are_not_inserted() {
+        let editor = editor_with("a\x1b[Ab\tc");
+        assert_eq!(editor.get_text(), "a[Abc");
+    }
+
+    #[test]
+    fn wide_characters_count_two_columns() {
+        let mut editor = editor_with("日本x");
+        editor.move_home();
+        assert_eq!(editor.columns_after_cursor(), 5);
+    }
+
+    #[test]
+    fn delete_word_removes_the_word_and_trailing_spaces() {
+        let mut editor = editor_with("hello big world  ");
+        editor.delete_word();
+        assert_eq!(editor.get_text(), "hello big ");
+        editor.delete_word();
+        assert_eq!(editor.get_text(), "hello ");
+        editor.delete_word();
+        assert_eq!(editor.get_text(), "");
+        editor.delete_word();
+        assert_eq!(editor.get_text(), "");
+    }
+
+    #[test]
+    fn delete_word_keeps_the_text_after_the_cursor() {
+        let mut editor = editor_with("one two three");
+        for _ in 0.."three".len() + 1 {
+            editor.move_left();
+        }
+        editor.delete_word();
+        assert_eq!(editor.get_text(), "one  three");
+        assert_eq!(editor.columns_after_cursor(), " three".len());
+    }
+
+    #[test]
+    fn kill_line_removes_everything_before_the_cursor() {
+        let mut editor = editor_with("abc def");
+        editor.move_left();
+        editor.move_left();
+        editor.kill_line();
+        assert_eq!(editor.get_text(), "ef");
+        assert_eq!(editor.columns_after_cursor(), 2);
+    }
+
+    #[test]
+    fn history_navigation_keeps_the_draft() {
+        let mut editor = LineEditor::new();
+        for line in ["first", "second"] {
+            editor.insert(line);
+            editor.submit();
+        }
+        editor.insert("draft");
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "second");
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "first");
+        // stays on the oldest entry
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "first");
+        editor.history_next();
+        assert_eq!(editor.get_text(), "second");
+        editor.history_next();
+        assert_eq!(editor.get_text(), "draft");
+        editor.history_next();
+        assert_eq!(editor.get_text(), "draft");
+    }
+
+    #[test]
+    fn submit_skips_blank_lines_and_repeats() {
+        let mut editor = LineEditor::new();
+        for line in ["same", "  ", "same ", "other"] {
+            editor.insert(line);
+            editor.submit();
+        }
+        assert_eq!(editor.get_text(), "");
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "other");
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "same");
+        editor.history_previous();
+        assert_eq!(editor.get_text(), "same");
+    }
+
+    #[test]
+    fn history_is_empty_at_first() {
+        let mut editor = editor_with("typed");
+        editor.history_previous();
+        editor.history_next();
+        assert_eq!(editor.get_text(), "typed");
+    }
+}
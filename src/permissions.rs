@@ -0,0 +1,144 @@
+use std::fmt;
+use crate::db::manage::Manager;
+
+
+/**
+* A user's role on the whole BBS, stored in `users.role`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // can read but not post or create rooms
+    Guest,
+    User,
+    // moderates every room
+    Moderator,
+    // can do everything, including administering the BBS
+    Sysop,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Guest => "guest",
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Sysop => "sysop",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        match value {
+            "guest" => Some(Role::Guest),
+            "user" => Some(Role::User),
+            "moderator" => Some(Role::Moderator),
+            "sysop" => Some(Role::Sysop),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+/**
+* A user's role in one room, stored in `room_roles`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomRole {
+    Owner,
+    Moderator,
+}
+
+impl RoomRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomRole::Owner => "owner",
+            RoomRole::Moderator => "moderator",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<RoomRole> {
+        match value {
+            "owner" => Some(RoomRole::Owner),
+            "moderator" => Some(RoomRole::Moderator),
+            _ => None,
+        }
+    }
+}
+
+
+/**
+* Something a user wants to do, checked with `can` before a view does it
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    CreateRoom,
+    PostMessage { room_id: i32 },
+    // delete messages in the room and kick users out of it
+    ModerateRoom { room_id: i32 },
+    // appoint and remove the room's moderators
+    ManageModerators { room_id: i32 },
+    // start threads and reply on the message boards
+    PostToBoard,
+    CreateBoard,
+    SendMail,
+    // the sysop admin screen: sessions, bans, password resets and room cleanup
+    Administer,
+}
+
+impl Action {
+
+    /**
+    * the message shown when the action is not allowed
+    */
+    pub fn denied_message(&self) -> &'static str {
+        match self {
+            Action::CreateRoom => "Guests can't create rooms.",
+            Action::PostMessage { .. } => "Guests can't post messages.",
+            Action::ModerateRoom { .. } => "Only the room's owner and moderators can do that.",
+            Action::ManageModerators { .. } => "Only the room's owner can change its moderators.",
+            Action::PostToBoard => "Guests can't post on the boards.",
+            Action::CreateBoard => "Only sysops can create boards.",
+            Action::SendMail => "Guests can't send mail.",
+            Action::Administer => "Only sysops can administer the BBS.",
+        }
+    }
+}
+
+
+/**
+* true if `user_id` may do `action`. A database error is logged and denies the action
+*/
+pub fn can(user_id: i32, action: Action) -> bool {
+    match allowed(user_id, action) {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            e.report("checking a permission");
+            false
+        }
+    }
+}
+
+
+fn allowed(user_id: i32, action: Action) -> crate::db::error::DbResult<bool> {
+    let role = Manager::get_user_role(user_id)?;
+    if role == Role::Sysop {
+        return Ok(true);
+    }
+
+    let allowed = match action {
+        Action::CreateRoom | Action::PostMessage { .. } | Action::PostToBoard | Action::SendMail => role >= Role::User,
+        Action::ModerateRoom { room_id } => {
+            role == Role::Moderator || Manager::get_room_role(room_id, user_id)?.is_some()
+        }
+        Action::ManageModerators { room_id } => {
+            Manager::get_room_role(room_id, user_id)? == Some(RoomRole::Owner)
+        }
+        Action::CreateBoard | Action::Administer => false,
+    };
+    Ok(allowed)
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+
+
+/**
+* Identifies one connected client, a user logged in twice has two sessions
+*/
+pub type SessionId = u64;
+
+
+struct Session {
+    user_id: i32,
+    username: String,
+    room_id: Option<i32>,
+    remote_addr: String,
+    connected_at: Instant,
+    last_activity: Instant,
+}
+
+
+/**
+* What the "My sessions" and admin screens show about a session
+*/
+#[derive(Clone, Debug)]
+pub struct SessionInfo {
+    pub session_id: SessionId,
+    // -1 and empty until the session logs in
+    pub user_id: i32,
+    pub username: String,
+    pub room_id: Option<i32>,
+    pub remote_addr: String,
+    pub connected_for: Duration,
+    pub idle_for: Duration,
+}
+
+
+struct Registry {
+    next_id: SessionId,
+    sessions: HashMap<SessionId, Session>,
+}
+
+
+// lives only in memory so every server start begins with empty rooms
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(|| {
+    Mutex::new(Registry { next_id: 1, sessions: HashMap::new() })
+});
+
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+/**
+* registers a new anonymous session for a client connected from `remote_addr`
+*/
+pub fn open_session(remote_addr: &str) -> SessionId {
+    let mut registry = registry();
+    let id = registry.next_id;
+    registry.next_id += 1;
+    let now = Instant::now();
+    registry.sessions.insert(id, Session {
+        user_id: -1,
+        username: String::new(),
+        room_id: None,
+        remote_addr: remote_addr.to_string(),
+        connected_at: now,
+        last_activity: now,
+    });
+    id
+}
+
+
+/**
+* records that the client sent something
+*/
+pub fn touch(session_id: SessionId) {
+    if let Some(session) = registry().sessions.get_mut(&session_id) {
+        session.last_activity = Instant::now();
+    }
+}
+
+
+/**
+* records the user a session logged in as
+*/
+pub fn login(session_id: SessionId, user_id: i32, username: &str) {
+    if let Some(session) = registry().sessions.get_mut(&session_id) {
+        session.user_id = user_id;
+        session.username = username.to_string();
+    }
+}
+
+
+/**
+* puts the session in `room_id`, leaving any room it was in
+*/
+pub fn enter_room(session_id: SessionId, room_id: i32) {
+    if let Some(session) = registry().sessions.get_mut(&session_id) {
+        session.room_id = Some(room_id);
+    }
+}
+
+
+/**
+* takes the session out of its room, returns the room it left or None if it wasn't in one,
+* so a second leave is a no-op
+*/
+pub fn leave_room(session_id: SessionId) -> Option<i32> {
+    registry().sessions.get_mut(&session_id).and_then(|session| session.room_id.take())
+}
+
+
+/**
+* forgets the session, returns the room it was in
+*/
+pub fn close_session(session_id: SessionId) -> Option<i32> {
+    registry().sessions.remove(&session_id).and_then(|session| session.room_id)
+}
+
+
+/**
+* how long since the client of the session last sent input
+*/
+pub fn idle_for(session_id: SessionId) -> Duration {
+    registry().sessions.get(&session_id).map(|session| session.last_activity.elapsed()).unwrap_or_default()
+}
+
+
+/**
+* true while the user has at least one session
+*/
+pub fn is_online(user_id: i32) -> bool {
+    user_id > 0 && registry().sessions.values().any(|session| session.user_id == user_id)
+}
+
+
+/**
+* the ids of every user with at least one session
+*/
+pub fn online_user_ids() -> HashSet<i32> {
+    registry().sessions.values().map(|session| session.user_id).filter(|user_id| *user_id > 0).collect()
+}
+
+
+/**
+* the sessions `user_id` has open, oldest first
+*/
+pub fn sessions_for_user(user_id: i32) -> Vec<SessionInfo> {
+    let mut sessions = all_sessions();
+    sessions.retain(|session| session.user_id == user_id);
+    sessions
+}
+
+
+/**
+* every open session including anonymous ones, oldest first
+*/
+pub fn all_sessions() -> Vec<SessionInfo> {
+    let registry = registry();
+    let mut sessions: Vec<SessionInfo> = registry.sessions
+        .iter()
+        .map(|(session_id, session)| SessionInfo {
+            session_id: *session_id,
+            user_id: session.user_id,
+            username: session.username.clone(),
+            room_id: session.room_id,
+            remote_addr: session.remote_addr.clone(),
+            connected_for: session.connected_at.elapsed(),
+            idle_for: session.last_activity.elapsed(),
+        })
+        .collect();
+    sessions.sort_by_key(|session| session.session_id);
+    sessions
+}
+
+
+/**
+* the user a session is logged in as, None for an unknown or anonymous session
+*/
+pub fn session_user(session_id: SessionId) -> Option<i32> {
+    registry().sessions.get(&session_id).map(|session| session.user_id).filter(|user_id| *user_id > 0)
+}
+
+
+/**
+* the number of distinct users in each occupied room
+*/
+pub fn room_counts() -> HashMap<i32, usize> {
+    let registry = registry();
+    let mut users_by_room: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for session in registry.sessions.values() {
+        if let Some(room_id) = session.room_id {
+            users_by_room.entry(room_id).or_default().insert(session.user_id);
+        }
+    }
+    users_by_room.into_iter().map(|(room_id, users)| (room_id, users.len())).collect()
+}
+
+
+/**
+* the names of the users in `room_id` in alphabetical order, each listed once
+*/
+pub fn users_in_room(room_id: i32) -> Vec<String> {
+    let registry = registry();
+    let users: BTreeSet<&str> = registry.sessions
+        .values()
+        .filter(|session| session.room_id == Some(room_id))
+        .map(|session| session.username.as_str())
+        .collect();
+    users.into_iter().map(|name| name.to_string()).collect()
+}
@@ -0,0 +1,494 @@
+use std::collections::HashSet;
+
+// telnet commands (RFC 854)
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+pub const NOP: u8 = 241;
+
+// telnet options
+pub const BINARY: u8 = 0;
+pub const ECHO: u8 = 1;
+pub const SGA: u8 = 3;
+pub const TIMING_MARK: u8 = 6;
+pub const TTYPE: u8 = 24;
+pub const NAWS: u8 = 31;
+pub const LINEMODE: u8 = 34;
+
+// TTYPE subnegotiation commands (RFC 1091)
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+// options the server is willing to perform itself
+const SUPPORTED_LOCAL: [u8; 3] = [BINARY, ECHO, SGA];
+
+// options the server is willing to let the client perform
+const SUPPORTED_REMOTE: [u8; 4] = [BINARY, SGA, TTYPE, NAWS];
+
+// longest subnegotiation kept, NAWS needs 5 bytes and terminal type names are short
+const MAX_SUBNEGOTIATION: usize = 64;
+
+
+/**
+* Things learned from the client through telnet subnegotiation
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub enum TelnetEvent {
+    TerminalType(String),
+    WindowSize { width: u16, height: u16 },
+}
+
+
+#[derive(PartialEq, Eq)]
+enum ParseState {
+    Data,
+    Iac,
+    Negotiate(u8),
+    Subnegotiation,
+    SubnegotiationIac,
+    CarriageReturn,
+}
+
+
+/**
+* Per connection telnet state. Raw bytes from the socket go through `decode`, which strips
+* out IAC command sequences, answers option negotiation and returns only the data bytes
+*/
+pub struct TelnetCodec {
+    state: ParseState,
+    subnegotiation: Vec<u8>,
+    // the subnegotiation went past MAX_SUBNEGOTIATION and is dropped when it ends
+    subnegotiation_oversized: bool,
+    local_options: HashSet<u8>,
+    remote_options: HashSet<u8>,
+    pending_local: HashSet<u8>,
+    pending_remote: HashSet<u8>,
+    outgoing: Vec<u8>,
+    events: Vec<TelnetEvent>,
+    // a keepalive TIMING-MARK is waiting for its answer
+    timing_mark_outstanding: bool,
+    // the client has answered a TIMING-MARK before, so a missing answer means it is gone
+    answers_timing_mark: bool,
+}
+
+
+impl TelnetCodec {
+
+    pub fn new() -> Self {
+        Self {
+            state: ParseState::Data,
+            subnegotiation: Vec::new(),
+            subnegotiation_oversized: false,
+            local_options: HashSet::new(),
+            remote_options: HashSet::new(),
+            pending_local: HashSet::new(),
+            pending_remote: HashSet::new(),
+            outgoing: Vec::new(),
+            events: Vec::new(),
+            timing_mark_outstanding: false,
+            answers_timing_mark: false,
+        }
+    }
+
+    /**
+    * feeds bytes read from the client through the state machine and returns the data bytes.
+    * CR LF and CR NUL are both collapsed into a single CR
+    */
+    pub fn decode(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(input.len());
+        for &byte in input {
+            match self.state {
+                ParseState::Data => {
+                    if byte == IAC {
+                        self.state = ParseState::Iac;
+                    } else if byte == b'\r' {
+                        data.push(byte);
+                        self.state = ParseState::CarriageReturn;
+                    } else {
+                        data.push(byte);
+                    }
+                }
+                ParseState::CarriageReturn => {
+                    self.state = ParseState::Data;
+                    if byte == IAC {
+                        self.state = ParseState::Iac;
+                    } else if byte != b'\n' && byte != 0 {
+                        data.push(byte);
+                    }
+                }
+                ParseState::Iac => {
+                    if byte == IAC {
+                        // escaped 255 data byte
+                        self.state = ParseState::Data;
+                        data.push(IAC);
+                    } else {
+                        self.start_command(byte);
+                    }
+                }
+                ParseState::Negotiate(command) => {
+                    self.state = ParseState::Data;
+                    self.handle_negotiation(command, byte);
+                }
+                ParseState::Subnegotiation => {
+                    if byte == IAC {
+                        self.state = ParseState::SubnegotiationIac;
+                    } else {
+                        self.push_subnegotiation(byte);
+                    }
+                }
+                ParseState::SubnegotiationIac => {
+                    if byte == SE {
+                        self.state = ParseState::Data;
+                        self.handle_subnegotiation();
+                    } else if byte == IAC {
+                        // IAC IAC inside a subnegotiation is an escaped 255
+                        self.push_subnegotiation(IAC);
+                        self.state = ParseState::Subnegotiation;
+                    } else {
+                        // any other command means the subnegotiation was never terminated
+                        self.subnegotiation.clear();
+                        self.subnegotiation_oversized = false;
+                        self.start_command(byte);
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    /**
+    * returns and clears the bytes that need to be written back to the client
+    */
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /**
+    * returns and clears the subnegotiation events collected while decoding
+    */
+    pub fn take_events(&mut self) -> Vec<TelnetEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /**
+    * queues a keepalive: IAC NOP, which fails to write once the connection is broken, and
+    * IAC DO TIMING-MARK, which a live telnet client always answers with WILL or WONT
+    */
+    pub fn send_keepalive(&mut self) {
+        self.outgoing.extend_from_slice(&[IAC, NOP]);
+        self.send_command(DO, TIMING_MARK);
+        self.timing_mark_outstanding = true;
+    }
+
+    /**
+    * true if the client answered TIMING-MARK before but not the last keepalive
+    */
+    pub fn is_unresponsive(&self) -> bool {
+        self.answers_timing_mark && self.timing_mark_outstanding
+    }
+
+    pub fn is_local_enabled(&self, option: u8) -> bool {
+        self.local_options.contains(&option)
+    }
+
+    pub fn is_remote_enabled(&self, option: u8) -> bool {
+        self.remote_options.contains(&option)
+    }
+
+    /**
+    * asks to enable (IAC WILL) or disable (IAC WONT) an option on the server side,
+    * nothing is sent if the option is already in the requested state
+    */
+    pub fn set_local_option(&mut self, option: u8, enable: bool) {
+        if self.is_local_enabled(option) == enable || self.pending_local.contains(&option) {
+            return;
+        }
+        if enable {
+            self.pending_local.insert(option);
+            self.send_command(WILL, option);
+        } else {
+            self.local_options.remove(&option);
+            self.send_command(WONT, option);
+        }
+    }
+
+    /**
+    * asks the client to enable (IAC DO) or disable (IAC DONT) an option on its side,
+    * nothing is sent if the option is already in the requested state
+    */
+    pub fn set_remote_option(&mut self, option: u8, enable: bool) {
+        if self.is_remote_enabled(option) == enable || self.pending_remote.contains(&option) {
+            return;
+        }
+        if enable {
+            self.pending_remote.insert(option);
+            self.send_command(DO, option);
+        } else {
+            self.remote_options.remove(&option);
+            self.send_command(DONT, option);
+        }
+    }
+
+    fn send_command(&mut self, command: u8, option: u8) {
+        self.outgoing.extend_from_slice(&[IAC, command, option]);
+    }
+
+    // the command byte after an IAC, other than an escaped 255
+    fn start_command(&mut self, command: u8) {
+        self.state = ParseState::Data;
+        if command == WILL || command == WONT || command == DO || command == DONT {
+            self.state = ParseState::Negotiate(command);
+        } else if command == SB {
+            self.subnegotiation.clear();
+            self.subnegotiation_oversized = false;
+            self.state = ParseState::Subnegotiation;
+        }
+        // any other command (NOP, GA, AYT, ...) is dropped
+    }
+
+    // a client that never sends IAC SE can't grow the buffer past MAX_SUBNEGOTIATION
+    fn push_subnegotiation(&mut self, byte: u8) {
+        if self.subnegotiation.len() < MAX_SUBNEGOTIATION {
+            self.subnegotiation.push(byte);
+        } else {
+            self.subnegotiation_oversized = true;
+        }
+    }
+
+    fn handle_negotiation(&mut self, command: u8, option: u8) {
+        // TIMING-MARK is never kept on (RFC 860), it only marks a point in the stream
+        if option == TIMING_MARK {
+            if command == WILL || command == WONT {
+                self.timing_mark_outstanding = false;
+                self.answers_timing_mark = true;
+            } else if command == DO {
+                // everything before the mark has been processed by the time this is read
+                self.send_command(WILL, TIMING_MARK);
+            }
+            return;
+        }
+
+        let requested = if command == DO || command == DONT {
+            self.pending_local.remove(&option)
+        } else {
+            self.pending_remote.remove(&option)
+        };
+
+        if command == DO {
+            if !SUPPORTED_LOCAL.contains(&option) {
+                self.send_command(WONT, option);
+            } else if self.local_options.insert(option) && !requested {
+                self.send_command(WILL, option);
+            }
+        }
+        else if command == DONT {
+            if self.local_options.remove(&option) && !requested {
+                self.send_command(WONT, option);
+            }
+        }
+        else if command == WILL {
+            // LINEMODE is always refused so the client stays in character at a time mode
+            if option == LINEMODE || !SUPPORTED_REMOTE.contains(&option) {
+                self.send_command(DONT, option);
+            } else if self.remote_options.insert(option) {
+                if !requested {
+                    self.send_command(DO, option);
+                }
+                if option == TTYPE {
+                    self.outgoing.extend_from_slice(&[IAC, SB, TTYPE, TTYPE_SEND, IAC, SE]);
+                }
+            }
+        }
+        else if command == WONT && self.remote_options.remove(&option) && !requested {
+            self.send_command(DONT, option);
+        }
+    }
+
+    fn handle_subnegotiation(&mut self) {
+        let payload = std::mem::take(&mut self.subnegotiation);
+        if std::mem::take(&mut self.subnegotiation_oversized) {
+            return;
+        }
+        match payload.as_slice() {
+            [TTYPE, TTYPE_IS, name @ ..] => {
+                let terminal_type = String::from_utf8_lossy(name).to_string();
+                self.events.push(TelnetEvent::TerminalType(terminal_type));
+            }
+            [NAWS, w1, w2, h1, h2] => {
+                let width = u16::from_be_bytes([*w1, *w2]);
+                let height = u16::from_be_bytes([*h1, *h2]);
+                // a zero dimension means the client does not know its size
+                if width > 0 && height > 0 {
+                    self.events.push(TelnetEvent::WindowSize { width, height });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iac_iac_is_a_data_byte() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(&[b'a', IAC, IAC, b'b']), vec![b'a', IAC, b'b']);
+        assert!(codec.take_outgoing().is_empty());
+    }
+
+    #[test]
+    fn iac_iac_split_across_reads() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(&[b'a', IAC]), vec![b'a']);
+        assert_eq!(codec.decode(&[IAC, b'b']), vec![IAC, b'b']);
+    }
+
+    #[test]
+    fn other_commands_are_dropped() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(&[b'x', IAC, NOP, b'y']), vec![b'x', b'y']);
+    }
+
+    #[test]
+    fn carriage_return_nul_and_line_feed_collapse() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(b"a\r\0b\r\nc"), b"a\rb\rc".to_vec());
+    }
+
+    #[test]
+    fn carriage_return_split_across_reads() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(b"a\r"), b"a\r".to_vec());
+        assert_eq!(codec.decode(b"\nb"), b"b".to_vec());
+        assert_eq!(codec.decode(b"\r"), b"\r".to_vec());
+        assert_eq!(codec.decode(&[0]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn bare_carriage_return_keeps_the_next_byte() {
+        let mut codec = TelnetCodec::new();
+        assert_eq!(codec.decode(b"\rx"), b"\rx".to_vec());
+    }
+
+    #[test]
+    fn naws_sets_the_window_size() {
+        let mut codec = TelnetCodec::new();
+        let data = codec.decode(&[IAC, SB, NAWS, 0, 120, 0, 40, IAC, SE, b'k']);
+        assert_eq!(data, vec![b'k']);
+        assert_eq!(codec.take_events(), vec![TelnetEvent::WindowSize { width: 120, height: 40 }]);
+    }
+
+    #[test]
+    fn naws_with_escaped_255() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, SB, NAWS, 0, IAC, IAC, 1, 0, IAC, SE]);
+        assert_eq!(codec.take_events(), vec![TelnetEvent::WindowSize { width: 255, height: 256 }]);
+    }
+
+    #[test]
+    fn naws_of_zero_is_ignored() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, SB, NAWS, 0, 0, 0, 24, IAC, SE]);
+        assert!(codec.take_events().is_empty());
+    }
+
+    #[test]
+    fn subnegotiation_split_across_reads() {
+        let mut codec = TelnetCodec::new();
+        let input = [IAC, SB, NAWS, 0, 80, 0, 24, IAC, SE, b'z'];
+        for split in 1..input.len() {
+            let mut data = codec.decode(&input[..split]);
+            data.extend(codec.decode(&input[split..]));
+            assert_eq!(data, vec![b'z'], "split at {}", split);
+            assert_eq!(codec.take_events(), vec![TelnetEvent::WindowSize { width: 80, height: 24 }], "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn oversized_subnegotiation_is_dropped() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, SB, TTYPE, TTYPE_IS]);
+        for _ in 0..1000 {
+            codec.decode(&[b'x'; 1000]);
+        }
+        assert!(codec.subnegotiation.len() <= MAX_SUBNEGOTIATION);
+        assert_eq!(codec.decode(&[IAC, SE, b'k']), vec![b'k']);
+        assert!(codec.take_events().is_empty());
+
+        // the next subnegotiation is read normally
+        codec.decode(&[IAC, SB, NAWS, 0, 80, 0, 24, IAC, SE]);
+        assert_eq!(codec.take_events(), vec![TelnetEvent::WindowSize { width: 80, height: 24 }]);
+    }
+
+    #[test]
+    fn command_inside_subnegotiation_aborts_it() {
+        let mut codec = TelnetCodec::new();
+        // the WILL after the unterminated NAWS is negotiated, not kept as payload
+        let data = codec.decode(&[IAC, SB, NAWS, 0, 80, IAC, WILL, NAWS, b'k']);
+        assert_eq!(data, vec![b'k']);
+        assert_eq!(codec.take_outgoing(), vec![IAC, DO, NAWS]);
+        codec.decode(&[0, 24, IAC, SE]);
+        assert!(codec.take_events().is_empty());
+
+        // a NOP aborts it too and the bytes after it are data again
+        assert_eq!(codec.decode(&[IAC, SB, NAWS, 0, IAC, NOP, b'z']), vec![b'z']);
+        assert!(codec.take_events().is_empty());
+    }
+
+    #[test]
+    fn terminal_type_is_reported() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, SB, TTYPE, TTYPE_IS]);
+        codec.decode(b"XTERM");
+        codec.decode(&[IAC, SE]);
+        assert_eq!(codec.take_events(), vec![TelnetEvent::TerminalType("XTERM".to_string())]);
+    }
+
+    #[test]
+    fn unsupported_options_are_refused() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, DO, TTYPE, IAC, WILL, LINEMODE]);
+        assert_eq!(codec.take_outgoing(), vec![IAC, WONT, TTYPE, IAC, DONT, LINEMODE]);
+        assert!(!codec.is_local_enabled(TTYPE));
+        assert!(!codec.is_remote_enabled(LINEMODE));
+    }
+
+    #[test]
+    fn requested_option_is_not_acknowledged_twice() {
+        let mut codec = TelnetCodec::new();
+        codec.set_local_option(ECHO, true);
+        assert_eq!(codec.take_outgoing(), vec![IAC, WILL, ECHO]);
+        codec.decode(&[IAC, DO, ECHO]);
+        assert!(codec.take_outgoing().is_empty());
+        assert!(codec.is_local_enabled(ECHO));
+    }
+
+    #[test]
+    fn client_offering_naws_is_accepted() {
+        let mut codec = TelnetCodec::new();
+        codec.decode(&[IAC, WILL, NAWS]);
+        assert_eq!(codec.take_outgoing(), vec![IAC, DO, NAWS]);
+        assert!(codec.is_remote_enabled(NAWS));
+    }
+
+    #[test]
+    fn timing_mark_answers_keep_the_client_alive() {
+        let mut codec = TelnetCodec::new();
+        codec.send_keepalive();
+        assert_eq!(codec.take_outgoing(), vec![IAC, NOP, IAC, DO, TIMING_MARK]);
+        // a client that never answered is not judged
+        assert!(!codec.is_unresponsive());
+        codec.decode(&[IAC, WONT, TIMING_MARK]);
+        codec.send_keepalive();
+        assert!(codec.is_unresponsive());
+        codec.decode(&[IAC, WILL, TIMING_MARK]);
+        assert!(!codec.is_unresponsive());
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use crate::config;
+
+
+/**
+* Failed logins counted for one account or one ip address
+*/
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    blocked_until: Option<Instant>,
+}
+
+
+struct Throttle {
+    accounts: HashMap<String, Failures>,
+    ips: HashMap<IpAddr, Failures>,
+    connections: HashMap<IpAddr, usize>,
+}
+
+
+// lives only in memory, a restart clears every backoff and lockout
+static THROTTLE: Lazy<Mutex<Throttle>> = Lazy::new(|| {
+    Mutex::new(Throttle { accounts: HashMap::new(), ips: HashMap::new(), connections: HashMap::new() })
+});
+
+
+fn throttle() -> std::sync::MutexGuard<'static, Throttle> {
+    THROTTLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+/**
+* Why a login attempt was refused before the password was checked
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    // too many recent failures, wait before trying again
+    Backoff(Duration),
+    // the account is locked for a while after many failures
+    Locked(Duration),
+}
+
+impl Refusal {
+    pub fn user_message(&self) -> String {
+        match self {
+            Refusal::Backoff(wait) => format!("Too many failed logins, please wait {} seconds and try again.", wait.as_secs().max(1)),
+            Refusal::Locked(wait) => format!("This account is locked after too many failed logins, try again in {} minutes.", wait.as_secs().div_ceil(60).max(1)),
+        }
+    }
+
+    /**
+    * short reason stored in the failed login log
+    */
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Refusal::Backoff(_) => "throttled",
+            Refusal::Locked(_) => "account locked",
+        }
+    }
+}
+
+
+// usernames are looked up exactly, but counting them case-insensitively stops
+// an attacker from dodging the counter by changing case
+fn account_key(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+
+// a count is forgotten once nothing failed for the length of a lockout
+fn forget_stale<K>(failures: &mut HashMap<K, Failures>, now: Instant, window: Duration) {
+    failures.retain(|_, record| {
+        now.duration_since(record.last_failure) < window
+            || record.blocked_until.is_some_and(|until| until > now)
+    });
+}
+
+
+/**
+* seconds to wait after `count` failures, doubling from 1 second once
+* `backoff_after` failures are reached and capped at `max_backoff_secs`
+*/
+fn backoff_for(count: u32) -> Option<Duration> {
+    let login = &config::get().login;
+    if login.backoff_after == 0 || count < login.backoff_after {
+        return None;
+    }
+    let doublings = (count - login.backoff_after).min(31);
+    let seconds = (1u64 << doublings).min(login.max_backoff_secs);
+    Some(Duration::from_secs(seconds))
+}
+
+
+/**
+* checks whether `username` may try to log in from `ip` right now and counts the attempt as
+* a failure until `record_success` clears it. Checking and counting under one lock stops
+* parallel connections from each getting a guess in before any failure is counted
+*/
+pub fn check_login(username: &str, ip: IpAddr) -> Result<(), Refusal> {
+    let now = Instant::now();
+    let mut throttle = throttle();
+    let window = Duration::from_secs(config::get().login.lockout_secs);
+    forget_stale(&mut throttle.accounts, now, window);
+    forget_stale(&mut throttle.ips, now, window);
+
+    let lockout_after = config::get().login.lockout_after;
+    if let Some(record) = throttle.accounts.get(&account_key(username)) {
+        if let Some(until) = record.blocked_until.filter(|until| *until > now) {
+            if lockout_after > 0 && record.count >= lockout_after {
+                return Err(Refusal::Locked(until - now));
+            }
+            return Err(Refusal::Backoff(until - now));
+        }
+    }
+    if let Some(until) = throttle.ips.get(&ip).and_then(|record| record.blocked_until).filter(|until| *until > now) {
+        return Err(Refusal::Backoff(until - now));
+    }
+    record_failure(&mut throttle, username, ip, now);
+    Ok(())
+}
+
+
+/**
+* counts a failure for `username` and `ip`, blocking further attempts for a growing
+* delay and locking the account once `login.lockout_after` is reached
+*/
+fn record_failure(throttle: &mut Throttle, username: &str, ip: IpAddr, now: Instant) {
+    let login = &config::get().login;
+
+    let account = throttle.accounts.entry(account_key(username)).or_insert(Failures {
+        count: 0,
+        last_failure: now,
+        blocked_until: None,
+    });
+    account.count += 1;
+    account.last_failure = now;
+    if login.lockout_after > 0 && account.count >= login.lockout_after {
+        account.blocked_until = Some(now + Duration::from_secs(login.lockout_secs));
+        println!("Account \"{}\" locked for {}s after {} failed logins.", username, login.lockout_secs, account.count);
+    }
+    else if let Some(wait) = backoff_for(account.count) {
+        account.blocked_until = Some(now + wait);
+    }
+
+    // one ip guessing many accounts backs off too, but is never locked out
+    let address = throttle.ips.entry(ip).or_insert(Failures { count: 0, last_failure: now, blocked_until: None });
+    address.count += 1;
+    address.last_failure = now;
+    if let Some(wait) = backoff_for(address.count) {
+        address.blocked_until = Some(now + wait);
+    }
+}
+
+
+/**
+* clears the failures counted against `username` after a good login. The address only gets
+* back the attempt `check_login` counted, its other failures expire on their own so logging
+* into an account of your own can't reset the backoff of an address spraying passwords
+*/
+pub fn record_success(username: &str, ip: IpAddr) {
+    let mut throttle = throttle();
+    throttle.accounts.remove(&account_key(username));
+    if let Some(address) = throttle.ips.get_mut(&ip) {
+        address.count = address.count.saturating_sub(1);
+        // a backoff started by this attempt is lifted, one started by other failures stays
+        if backoff_for(address.count).is_none() {
+            address.blocked_until = None;
+        }
+    }
+}
+
+
+/**
+* counts a new connection from `ip`, false if it already has `limits.max_connections_per_ip` open
+*/
+pub fn open_connection(ip: IpAddr) -> bool {
+    let max_per_ip = config::get().limits.max_connections_per_ip;
+    let mut throttle = throttle();
+    let open = throttle.connections.entry(ip).or_insert(0);
+    if max_per_ip > 0 && *open >= max_per_ip {
+        return false;
+    }
+    *open += 1;
+    true
+}
+
+
+/**
+* forgets a connection counted by `open_connection`
+*/
+pub fn close_connection(ip: IpAddr) {
+    let mut throttle = throttle();
+    if let Some(open) = throttle.connections.get_mut(&ip) {
+        *open = open.saturating_sub(1);
+        if *open == 0 {
+            throttle.connections.remove(&ip);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    // tests share the global throttle, so each one uses its own names and addresses
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    fn fail(username: &str, ip: IpAddr, times: u32) {
+        for _ in 0..times {
+            record_failure(&mut throttle(), username, ip, Instant::now());
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        config::init_defaults();
+        // defaults: backoff after 3 failures, at most 300 seconds
+        assert_eq!(backoff_for(2), None);
+        assert_eq!(backoff_for(3), Some(Duration::from_secs(1)));
+        assert_eq!(backoff_for(4), Some(Duration::from_secs(2)));
+        assert_eq!(backoff_for(6), Some(Duration::from_secs(8)));
+        assert_eq!(backoff_for(20), Some(Duration::from_secs(300)));
+        assert_eq!(backoff_for(u32::MAX), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn failures_below_the_threshold_are_not_throttled() {
+        config::init_defaults();
+        fail("below", ip(1), 2);
+        assert_eq!(check_login("below", ip(1)), Ok(()));
+    }
+
+    #[test]
+    fn repeated_failures_back_off() {
+        config::init_defaults();
+        fail("backoff", ip(2), 3);
+        match check_login("backoff", ip(3)) {
+            Err(Refusal::Backoff(wait)) => assert!(wait <= Duration::from_secs(1)),
+            other => panic!("expected a backoff, got {:?}", other),
+        }
+        fail("backoff", ip(2), 1);
+        match check_login("BackOff", ip(3)) {
+            Err(Refusal::Backoff(wait)) => assert!(wait > Duration::from_secs(1) && wait <= Duration::from_secs(2)),
+            other => panic!("expected a backoff, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failing_ip_backs_off_for_every_account() {
+        config::init_defaults();
+        fail("spray-a", ip(4), 1);
+        fail("spray-b", ip(4), 1);
+        fail("spray-c", ip(4), 1);
+        assert!(matches!(check_login("spray-d", ip(4)), Err(Refusal::Backoff(_))));
+        assert_eq!(check_login("spray-d", ip(5)), Ok(()));
+    }
+
+    #[test]
+    fn account_locks_after_too_many_failures() {
+        config::init_defaults();
+        fail("locked", ip(6), 10);
+        match check_login("locked", ip(7)) {
+            Err(Refusal::Locked(wait)) => assert!(wait > Duration::from_secs(890) && wait <= Duration::from_secs(900)),
+            other => panic!("expected a lockout, got {:?}", other),
+        }
+        assert_eq!(Refusal::Locked(Duration::from_secs(899)).user_message(), "This account is locked after too many failed logins, try again in 15 minutes.");
+    }
+
+    #[test]
+    fn checking_counts_the_attempt() {
+        config::init_defaults();
+        // the third attempt starts the backoff before its password is even checked
+        for _ in 0..3 {
+            assert_eq!(check_login("parallel", ip(11)), Ok(()));
+        }
+        assert!(matches!(check_login("parallel", ip(12)), Err(Refusal::Backoff(_))));
+    }
+
+    #[test]
+    fn success_clears_the_account_failures() {
+        config::init_defaults();
+        fail("cleared", ip(8), 3);
+        assert!(check_login("cleared", ip(13)).is_err());
+        record_success("Cleared", ip(13));
+        assert_eq!(check_login("cleared", ip(13)), Ok(()));
+    }
+
+    #[test]
+    fn success_does_not_reset_the_address() {
+        config::init_defaults();
+        fail("spray-e", ip(14), 1);
+        fail("spray-f", ip(14), 1);
+        // a good login of the attacker's own account only takes back its own attempt
+        assert_eq!(check_login("own-account", ip(14)), Ok(()));
+        record_success("own-account", ip(14));
+        assert_eq!(check_login("spray-g", ip(14)), Ok(()));
+        assert!(matches!(check_login("spray-h", ip(14)), Err(Refusal::Backoff(_))));
+    }
+
+    #[test]
+    fn good_logins_do_not_back_off() {
+        config::init_defaults();
+        for _ in 0..5 {
+            assert_eq!(check_login("regular", ip(15)), Ok(()));
+            record_success("regular", ip(15));
+        }
+    }
+
+    #[test]
+    fn connections_per_ip_are_limited() {
+        config::init_defaults();
+        // default limit is 5
+        for _ in 0..5 {
+            assert!(open_connection(ip(9)));
+        }
+        assert!(!open_connection(ip(9)));
+        assert!(open_connection(ip(10)));
+        close_connection(ip(9));
+        assert!(open_connection(ip(9)));
+    }
+}
@@ -0,0 +1,268 @@
+use std::any::Any;
+use std::collections::HashMap;
+use crate::account_policy::{self, PolicyError};
+use crate::db::manage::Manager;
+use crate::input_interface::Events;
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
+
+// the profile fields that can be edited, as (column, label)
+const PROFILE_FIELDS: [(&str, &str); 3] = [("display_name", "display name"), ("bio", "bio"), ("location", "location")];
+
+
+/**
+* What the account screen is asking for
+*/
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Step {
+    Choosing,
+    CurrentPassword,
+    NewPassword,
+    ConfirmPassword,
+    // index into PROFILE_FIELDS
+    EditField(usize),
+    DeletePassword,
+}
+
+
+/**
+* Lets the logged in user change their password, edit their profile and delete their account
+*/
+pub struct AccountView {
+    user_id: i32,
+    navigate_to: NavigateTo,
+    options: Vec<&'static str>,
+    selected_index: usize,
+    step: Step,
+    profile: HashMap<String, String>,
+    input: String,
+    current_password: String,
+    new_password: String,
+    error: Option<String>,
+    // confirms the last change that was saved
+    notice: Option<String>,
+}
+
+
+impl AccountView {
+    pub fn new(user_id: i32) -> Self {
+        let mut view = Self {
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            options: vec!["Change password", "Change display name", "Edit bio", "Edit location", "Delete account"],
+            selected_index: 0,
+            step: Step::Choosing,
+            profile: HashMap::new(),
+            input: String::new(),
+            current_password: String::new(),
+            new_password: String::new(),
+            error: None,
+            notice: None,
+        };
+        view.refresh_data();
+        view
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index < self.options.len() - 1 {
+            self.selected_index += 1;
+        }
+    }
+
+    fn profile_value(&self, column: &str) -> &str {
+        self.profile.get(column).map(|value| value.as_str()).unwrap_or("")
+    }
+
+    /**
+    * starts the step for the selected option and puts the client in (secret) input mode
+    */
+    fn handle_selection(&mut self) -> Events {
+        self.error = None;
+        self.notice = None;
+        match self.selected_index {
+            0 => {
+                self.step = Step::CurrentPassword;
+                Events::SecretInputModeEnable
+            }
+            4 => {
+                self.step = Step::DeletePassword;
+                Events::SecretInputModeEnable
+            }
+            field => {
+                self.step = Step::EditField(field - 1);
+                Events::InputModeEnable
+            }
+        }
+    }
+
+    /**
+    * goes back to the option list, clearing anything typed so far
+    */
+    fn finish(&mut self) -> Events {
+        self.step = Step::Choosing;
+        self.input.clear();
+        self.current_password.clear();
+        self.new_password.clear();
+        Events::InputModeDisable
+    }
+
+    fn fail(&mut self, message: String) -> Events {
+        self.error = Some(message);
+        self.finish()
+    }
+
+    /**
+    * handles Enter on the current step, `Events::Enter` moves on to the next prompt
+    */
+    fn submit(&mut self) -> Events {
+        match self.step {
+            Step::CurrentPassword => {
+                self.current_password = std::mem::take(&mut self.input);
+                self.step = Step::NewPassword;
+                Events::Enter
+            }
+            Step::NewPassword => {
+                self.new_password = std::mem::take(&mut self.input);
+                let username = self.profile_value("username").to_string();
+                if let Err(e) = account_policy::check_password(&username, &self.new_password) {
+                    return self.fail(e.to_string());
+                }
+                self.step = Step::ConfirmPassword;
+                Events::Enter
+            }
+            Step::ConfirmPassword => {
+                if self.input != self.new_password {
+                    return self.fail(PolicyError::PasswordMismatch.to_string());
+                }
+                match Manager::change_password(self.user_id, &self.current_password, &self.new_password) {
+                    Ok(()) => self.notice = Some("Your password was changed.".to_string()),
+                    Err(e) => self.error = Some(e.report("changing a password")),
+                }
+                self.finish()
+            }
+            Step::EditField(field) => {
+                let mut values: Vec<String> = PROFILE_FIELDS.iter().map(|(column, _)| self.profile_value(column).to_string()).collect();
+                values[field] = self.input.clone();
+                match Manager::update_profile(self.user_id, &values[0], &values[1], &values[2]) {
+                    Ok(()) => self.notice = Some(format!("Your {} was saved.", PROFILE_FIELDS[field].1)),
+                    Err(e) => self.error = Some(e.report("updating a profile")),
+                }
+                self.refresh_data();
+                self.finish()
+            }
+            Step::DeletePassword => {
+                match Manager::delete_user(self.user_id, &self.input) {
+                    Ok(()) => Events::AccountDeleted,
+                    Err(e) => {
+                        let message = e.report("deleting an account");
+                        self.fail(message)
+                    }
+                }
+            }
+            Step::Choosing => Events::Unknown,
+        }
+    }
+
+    fn render_prompt(&self) -> String {
+        match self.step {
+            Step::Choosing => String::new(),
+            Step::CurrentPassword => "\x1b[1;32m> Current password: ".to_string(),
+            Step::NewPassword => "\x1b[1;32m> New password: ".to_string(),
+            Step::ConfirmPassword => "\x1b[1;32m> Confirm new password: ".to_string(),
+            Step::EditField(field) => format!(
+                "An empty {} clears it, [CNTRL+Q] cancels\r\n\x1b[1;32m> New {}:\x1b[0m {}",
+                PROFILE_FIELDS[field].1,
+                PROFILE_FIELDS[field].1,
+                self.input
+            ),
+            Step::DeletePassword => "\x1b[1;31mThis deletes your account for good, your messages stay as from a deleted user.\x1b[0m\r\n\
+                \x1b[1;32m> Enter your password to confirm: ".to_string(),
+        }
+    }
+}
+
+
+impl View for AccountView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, _window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str("\x1b[1;32mAccount\x1b[0m\r\n\r\n");
+        output.push_str(&render_error(&self.error));
+        if let Some(notice) = &self.notice {
+            output.push_str(&format!("\x1b[1;33m{}\x1b[0m\r\n\r\n", notice));
+        }
+
+        output.push_str(&format!("username: {}\r\n", self.profile_value("username")));
+        for (column, label) in PROFILE_FIELDS.iter() {
+            output.push_str(&format!("{}: {}\r\n", label, self.profile_value(column)));
+        }
+        output.push_str("\r\n");
+
+        if self.step != Step::Choosing {
+            output.push_str(&self.render_prompt());
+            return output;
+        }
+
+        for (idx, option) in self.options.iter().enumerate() {
+            if idx == self.selected_index {
+                output.push_str(&format!("\x1b[1;33m> {} \x1b[0m\r\n", option));
+            } else {
+                output.push_str(&format!("  {}\r\n", option));
+            }
+        }
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to select\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_user(self.user_id) {
+            Ok(profile) => self.profile = profile,
+            Err(e) => self.error = Some(e.report("loading an account")),
+        }
+    }
+
+    fn is_secret_input(&self) -> bool {
+        matches!(self.step, Step::CurrentPassword | Step::NewPassword | Step::ConfirmPassword | Step::DeletePassword)
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if self.step == Step::Choosing {
+            if event == Events::UpArrow {
+                self.move_up();
+            }
+            else if event == Events::DownArrow {
+                self.move_down();
+            }
+            else if event == Events::Enter {
+                return self.handle_selection();
+            }
+            else if event == Events::CntrlQ {
+                self.navigate_to = NavigateTo::MeView;
+                return Events::NavigateView;
+            }
+            return Events::Unknown;
+        }
+
+        if event == Events::CntrlQ {
+            return self.finish();
+        }
+        else if event == Events::Enter {
+            return self.submit();
+        }
+        self.input = buffer_string;
+        Events::Unknown
+    }
+}
@@ -0,0 +1,686 @@
+use std::any::Any;
+use std::collections::HashMap;
+use crate::db::manage::Manager;
+use crate::input_interface::Events;
+use crate::permissions::{self, Action, Role};
+use crate::presence::{self, SessionId, SessionInfo};
+use crate::views::base_view::{format_duration, render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title, the notice and the help text below the lists
+const RESERVED_ROWS: usize = 12;
+
+
+/**
+* The list the admin screen is showing, cycled with Tab
+*/
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Section {
+    Sessions,
+    Users,
+    Rooms,
+    FailedLogins,
+}
+
+impl Section {
+    fn next(&self) -> Section {
+        match self {
+            Section::Sessions => Section::Users,
+            Section::Users => Section::Rooms,
+            Section::Rooms => Section::FailedLogins,
+            Section::FailedLogins => Section::Sessions,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Section::Sessions => "Sessions",
+            Section::Users => "Users",
+            Section::Rooms => "Rooms",
+            Section::FailedLogins => "Failed logins",
+        }
+    }
+}
+
+
+/**
+* What the admin screen is asking the sysop to type
+*/
+#[derive(PartialEq, Eq, Clone)]
+enum Prompt {
+    ResetPassword { user_id: i32, username: String },
+    RenameRoom { room_id: i32, room_name: String },
+    Announcement,
+}
+
+
+/**
+* A room change that waits for the same key a second time
+*/
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Confirm {
+    DeleteRoom(i32),
+    PurgeRoom(i32),
+}
+
+
+/**
+* Something the admin screen needs the other sessions to know about, the connection
+* reads it with `get_command` and publishes it on the hub
+*/
+#[derive(Clone)]
+pub enum AdminCommand {
+    // close the sessions, showing them the message
+    EndSessions(Vec<SessionId>, String),
+    Announce(String),
+    // the room was renamed or its messages purged
+    RoomChanged(i32),
+    RoomDeleted(i32),
+}
+
+
+/**
+* The sysop screen: connected sessions, users, rooms and failed logins with the actions to
+* end sessions, ban users, reset passwords and clean up rooms
+*/
+pub struct AdminView {
+    user_id: i32,
+    session_id: SessionId,
+    navigate_to: NavigateTo,
+    section: Section,
+    selected_index: usize,
+    offset: i32,
+    page_size: i32,
+    sessions: Vec<SessionInfo>,
+    users: Vec<(i32, String, Role, bool)>,
+    rooms: Vec<(i32, String)>,
+    // (created_date, username, remote_ip, reason), newest first
+    failed_logins: Vec<(String, String, String, String)>,
+    // room names for the sessions list
+    room_names: HashMap<i32, String>,
+    prompt: Option<Prompt>,
+    input: String,
+    confirming: Option<Confirm>,
+    // the user to make a sysop once [S] is pressed again
+    promoting: Option<i32>,
+    command: Option<AdminCommand>,
+    error: Option<String>,
+    // confirms the last action
+    notice: Option<String>,
+}
+
+
+impl AdminView {
+    pub fn new(user_id: i32, session_id: SessionId, window: &WindowSize) -> Self {
+        let mut view = Self {
+            user_id,
+            session_id,
+            navigate_to: NavigateTo::NoneView,
+            section: Section::Sessions,
+            selected_index: 0,
+            offset: 0,
+            page_size: window.page_size(RESERVED_ROWS),
+            sessions: Vec::new(),
+            users: Vec::new(),
+            rooms: Vec::new(),
+            failed_logins: Vec::new(),
+            room_names: HashMap::new(),
+            prompt: None,
+            input: String::new(),
+            confirming: None,
+            promoting: None,
+            command: None,
+            error: None,
+            notice: None,
+        };
+        view.refresh_data();
+        view
+    }
+
+    /**
+    * the command for the other sessions left by the last event, if any
+    */
+    pub fn get_command(&self) -> Option<AdminCommand> {
+        self.command.clone()
+    }
+
+    /**
+    * whether the user is still a sysop, checked before every action since they can be
+    * demoted while the screen is open
+    */
+    fn check_can_administer(&self) -> bool {
+        permissions::can(self.user_id, Action::Administer)
+    }
+
+    fn list_len(&self) -> usize {
+        match self.section {
+            Section::Sessions => self.sessions.len(),
+            Section::Users => self.users.len(),
+            Section::Rooms => self.rooms.len(),
+            Section::FailedLogins => self.failed_logins.len(),
+        }
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.list_len() {
+            self.selected_index += 1;
+        }
+    }
+
+    fn next_page(&mut self) {
+        if self.list_len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+    }
+
+    fn previous_page(&mut self) {
+        if self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+    }
+
+    fn selected_user(&self) -> Option<(i32, String, Role, bool)> {
+        self.users.get(self.selected_index).cloned()
+    }
+
+    fn selected_room(&self) -> Option<(i32, String)> {
+        self.rooms.get(self.selected_index).cloned()
+    }
+
+    /**
+    * asks the connection to close every session of `user_id`
+    */
+    fn end_user_sessions(&mut self, user_id: i32, message: &str) -> bool {
+        let sessions: Vec<SessionId> = presence::sessions_for_user(user_id).iter().map(|session| session.session_id).collect();
+        if sessions.is_empty() {
+            return false;
+        }
+        self.command = Some(AdminCommand::EndSessions(sessions, message.to_string()));
+        true
+    }
+
+    fn end_selected_session(&mut self) -> Events {
+        let Some((session_id, user_id, username)) = self.sessions
+            .get(self.selected_index)
+            .map(|session| (session.session_id, session.user_id, session.username.clone())) else {
+            return Events::Unknown;
+        };
+        if session_id == self.session_id {
+            self.error = Some("Use Quit from the menu to end your own session.".to_string());
+            return Events::Unknown;
+        }
+        if user_id > 0 && self.refuse_sysop(user_id, &username) {
+            return Events::Unknown;
+        }
+        self.notice = Some(format!("Ended session #{}.", session_id));
+        self.command = Some(AdminCommand::EndSessions(vec![session_id], "You were disconnected by a sysop.".to_string()));
+        Events::AdminCommand
+    }
+
+    fn kick_selected_user(&mut self) -> Events {
+        let Some((user_id, username, _, _)) = self.selected_user() else {
+            return Events::Unknown;
+        };
+        if user_id == self.user_id {
+            self.error = Some("You can't kick yourself.".to_string());
+            return Events::Unknown;
+        }
+        if self.refuse_sysop(user_id, &username) {
+            return Events::Unknown;
+        }
+        if !self.end_user_sessions(user_id, "You were disconnected by a sysop.") {
+            self.notice = Some(format!("{} is not online.", username));
+            return Events::Unknown;
+        }
+        self.notice = Some(format!("Kicked {}.", username));
+        Events::AdminCommand
+    }
+
+    /**
+    * bans the selected user and closes their sessions, or lifts the ban
+    */
+    fn toggle_ban(&mut self) -> Events {
+        let Some((user_id, username, _, banned)) = self.selected_user() else {
+            return Events::Unknown;
+        };
+        if user_id == self.user_id {
+            self.error = Some("You can't ban yourself.".to_string());
+            return Events::Unknown;
+        }
+        if self.refuse_sysop(user_id, &username) {
+            return Events::Unknown;
+        }
+        if let Err(e) = Manager::set_banned(user_id, !banned) {
+            self.error = Some(e.report("banning a user"));
+            return Events::Unknown;
+        }
+        self.refresh_data();
+        if banned {
+            self.notice = Some(format!("{} is no longer banned.", username));
+            return Events::Unknown;
+        }
+        println!("User {} was banned", username);
+        self.notice = Some(format!("{} is banned.", username));
+        if self.end_user_sessions(user_id, "Your account has been banned.") {
+            return Events::AdminCommand;
+        }
+        Events::Unknown
+    }
+
+    /**
+    * true (with an error shown) if `user_id` is another sysop, sysops can't kick, ban or
+    * change the role of each other from here
+    */
+    fn refuse_sysop(&mut self, user_id: i32, username: &str) -> bool {
+        if user_id == self.user_id || !permissions::can(user_id, Action::Administer) {
+            return false;
+        }
+        self.error = Some(format!("{} is a sysop, sysops can't kick, ban or change the role of each other.", username));
+        true
+    }
+
+    /**
+    * moves the selected user to the next role, moderator wraps around to guest.
+    * Sysops are made with `promote_to_sysop`
+    */
+    fn cycle_role(&mut self) {
+        let Some((user_id, username, role, _)) = self.selected_user() else {
+            return;
+        };
+        if user_id == self.user_id {
+            self.error = Some("You can't change your own role.".to_string());
+            return;
+        }
+        if self.refuse_sysop(user_id, &username) {
+            return;
+        }
+        let new_role = match role {
+            Role::Guest => Role::User,
+            Role::User => Role::Moderator,
+            Role::Moderator | Role::Sysop => Role::Guest,
+        };
+        match Manager::set_user_role(user_id, new_role) {
+            Ok(()) => self.notice = Some(format!("{} is now a {}.", username, new_role)),
+            Err(e) => self.error = Some(e.report("changing a user role")),
+        }
+        self.refresh_data();
+    }
+
+    /**
+    * makes the selected user a sysop once [S] is pressed twice, other sysops can't undo it
+    */
+    fn promote_to_sysop(&mut self) {
+        let Some((user_id, username, _, _)) = self.selected_user() else {
+            return;
+        };
+        if user_id == self.user_id {
+            self.error = Some("You can't change your own role.".to_string());
+            return;
+        }
+        if self.refuse_sysop(user_id, &username) {
+            return;
+        }
+        if self.promoting != Some(user_id) {
+            self.notice = Some(format!("Press [S] again to make {} a sysop, other sysops won't be able to demote them.", username));
+            self.promoting = Some(user_id);
+            return;
+        }
+
+        self.promoting = None;
+        match Manager::set_user_role(user_id, Role::Sysop) {
+            Ok(()) => {
+                println!("User {} was made a sysop", username);
+                self.notice = Some(format!("{} is now a {}.", username, Role::Sysop));
+            }
+            Err(e) => self.error = Some(e.report("changing a user role")),
+        }
+        self.refresh_data();
+    }
+
+    /**
+    * deletes the selected room or purges its messages once the same key is pressed twice
+    */
+    fn confirm(&mut self, confirm: Confirm) -> Events {
+        let Some((_, room_name)) = self.selected_room() else {
+            return Events::Unknown;
+        };
+        if self.confirming != Some(confirm) {
+            self.notice = Some(match confirm {
+                Confirm::DeleteRoom(_) => format!("Press [D] again to delete {} and all of its messages.", room_name),
+                Confirm::PurgeRoom(_) => format!("Press [P] again to delete every message in {}.", room_name),
+            });
+            self.confirming = Some(confirm);
+            return Events::Unknown;
+        }
+
+        self.confirming = None;
+        let result = match confirm {
+            Confirm::DeleteRoom(room_id) => Manager::delete_room(room_id).map(|()| {
+                self.command = Some(AdminCommand::RoomDeleted(room_id));
+                format!("Deleted {}.", room_name)
+            }),
+            Confirm::PurgeRoom(room_id) => Manager::purge_room_messages(room_id).map(|purged| {
+                self.command = Some(AdminCommand::RoomChanged(room_id));
+                format!("Deleted {} messages from {}.", purged, room_name)
+            }),
+        };
+        self.refresh_data();
+        match result {
+            Ok(notice) => {
+                self.notice = Some(notice);
+                Events::AdminCommand
+            }
+            Err(e) => {
+                self.error = Some(e.report("cleaning up a room"));
+                Events::Unknown
+            }
+        }
+    }
+
+    fn start_prompt(&mut self, prompt: Prompt) -> Events {
+        let secret = matches!(prompt, Prompt::ResetPassword { .. });
+        self.prompt = Some(prompt);
+        self.input.clear();
+        if secret {
+            Events::SecretInputModeEnable
+        } else {
+            Events::InputModeEnable
+        }
+    }
+
+    /**
+    * handles Enter on a prompt, the client leaves input mode either way
+    */
+    fn submit(&mut self) -> Events {
+        let input = std::mem::take(&mut self.input);
+        let result_event = match self.prompt.take() {
+            Some(Prompt::ResetPassword { user_id, username }) => {
+                match Manager::reset_password(user_id, &input) {
+                    Ok(()) => self.notice = Some(format!("The password of {} was reset.", username)),
+                    Err(e) => self.error = Some(e.report("resetting a password")),
+                }
+                Events::InputModeDisable
+            }
+            Some(Prompt::RenameRoom { room_id, room_name }) if !input.trim().is_empty() => {
+                match Manager::rename_room(room_id, input.trim()) {
+                    Ok(()) => {
+                        self.notice = Some(format!("Renamed {} to {}.", room_name, input.trim()));
+                        self.command = Some(AdminCommand::RoomChanged(room_id));
+                        Events::AdminCommand
+                    }
+                    Err(e) => {
+                        self.error = Some(e.report("renaming a room"));
+                        Events::InputModeDisable
+                    }
+                }
+            }
+            Some(Prompt::Announcement) if !input.trim().is_empty() => {
+                println!("Sysop announcement: {}", input.trim());
+                self.notice = Some("Your announcement was sent.".to_string());
+                self.command = Some(AdminCommand::Announce(input.trim().to_string()));
+                Events::AdminCommand
+            }
+            _ => Events::InputModeDisable,
+        };
+        self.refresh_data();
+        result_event
+    }
+
+    fn render_sessions(&self, output: &mut String) {
+        for (idx, session) in self.sessions.iter().enumerate() {
+            let username = if session.user_id > 0 { session.username.as_str() } else { "(not logged in)" };
+            let room = session.room_id
+                .and_then(|room_id| self.room_names.get(&room_id))
+                .map(|room_name| format!(" in {}", room_name))
+                .unwrap_or_default();
+            let this_session = if session.session_id == self.session_id { " (this session)" } else { "" };
+            let line = format!(
+                "#{} {} from {}{}, idle {}{}",
+                session.session_id,
+                username,
+                session.remote_addr,
+                room,
+                format_duration(session.idle_for),
+                this_session
+            );
+            push_row(output, &line, idx == self.selected_index);
+        }
+    }
+
+    fn render_users(&self, output: &mut String) {
+        for (idx, (_, username, role, banned)) in self.users.iter().enumerate() {
+            let online = if presence::is_online(self.users[idx].0) { ", online" } else { "" };
+            let banned = if *banned { ", \x1b[1;31mbanned\x1b[0m" } else { "" };
+            let line = format!("{} ({}{}{})", username, role, online, banned);
+            push_row(output, &line, idx == self.selected_index);
+        }
+    }
+
+    fn render_rooms(&self, output: &mut String) {
+        let online = presence::room_counts();
+        for (idx, (room_id, room_name)) in self.rooms.iter().enumerate() {
+            let line = format!("{}: {} online", room_name, online.get(room_id).copied().unwrap_or(0));
+            push_row(output, &line, idx == self.selected_index);
+        }
+    }
+
+    fn render_failed_logins(&self, output: &mut String) {
+        if self.failed_logins.is_empty() && self.offset == 0 {
+            output.push_str("  No failed logins recorded.\r\n");
+        }
+        for (idx, (created_date, username, remote_ip, reason)) in self.failed_logins.iter().enumerate() {
+            let line = format!("[{}] {} from {}: {}", created_date, username, remote_ip, reason);
+            push_row(output, &line, idx == self.selected_index);
+        }
+    }
+
+    fn render_prompt(&self) -> String {
+        match &self.prompt {
+            Some(Prompt::ResetPassword { username, .. }) => format!("\x1b[1;32m> New password for {}: ", username),
+            Some(Prompt::RenameRoom { room_name, .. }) => format!("\x1b[1;32m> New name for {} (CNTRL+Q to cancel):\x1b[0m {}", room_name, self.input),
+            Some(Prompt::Announcement) => format!("\x1b[1;32m> Announcement to everyone online (CNTRL+Q to cancel):\x1b[0m {}", self.input),
+            None => String::new(),
+        }
+    }
+}
+
+
+fn push_row(output: &mut String, line: &str, selected: bool) {
+    if selected {
+        output.push_str(&format!("\x1b[1;33m> {}\x1b[0m\r\n", line));
+    } else {
+        output.push_str(&format!("  {}\r\n", line));
+    }
+}
+
+
+impl View for AdminView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, _window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str("\x1b[1;32mAdmin\x1b[0m  ");
+        for section in [Section::Sessions, Section::Users, Section::Rooms, Section::FailedLogins] {
+            if section == self.section {
+                output.push_str(&format!("\x1b[1;33m[{}]\x1b[0m ", section.title()));
+            } else {
+                output.push_str(&format!(" {}  ", section.title()));
+            }
+        }
+        output.push_str("\r\n\r\n");
+        output.push_str(&render_error(&self.error));
+        if let Some(notice) = &self.notice {
+            output.push_str(&format!("\x1b[1;33m{}\x1b[0m\r\n\r\n", notice));
+        }
+
+        if self.prompt.is_some() {
+            output.push_str(&self.render_prompt());
+            return output;
+        }
+
+        match self.section {
+            Section::Sessions => self.render_sessions(&mut output),
+            Section::Users => self.render_users(&mut output),
+            Section::Rooms => self.render_rooms(&mut output),
+            Section::FailedLogins => self.render_failed_logins(&mut output),
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) to select, ← / → to change page, [TAB] next list\r\n");
+        match self.section {
+            Section::Sessions => output.push_str("[E] End session\r\n"),
+            Section::Users => output.push_str("[K] Kick  [B] Ban / unban  [P] Reset password  [R] Change role  [S] Make sysop\r\n"),
+            Section::Rooms => output.push_str("[N] Rename  [D] Delete  [P] Purge messages\r\n"),
+            Section::FailedLogins => {}
+        }
+        output.push_str("[A] Announcement\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        let limit = self.page_size;
+        let loaded = match self.section {
+            Section::Sessions => {
+                self.sessions = presence::all_sessions().into_iter().skip(self.offset as usize).take(limit as usize).collect();
+                let room_ids: Vec<i32> = self.sessions.iter().filter_map(|session| session.room_id).collect();
+                room_ids.into_iter().try_for_each(|room_id| {
+                    let room_name = Manager::get_room_name_by_id(room_id)?;
+                    self.room_names.insert(room_id, room_name);
+                    Ok(())
+                })
+            }
+            Section::Users => Manager::get_users_for_admin(self.offset, limit).map(|users| self.users = users),
+            Section::Rooms => Manager::get_rooms(self.offset, limit).map(|rooms| self.rooms = rooms),
+            Section::FailedLogins => Manager::get_failed_logins(self.offset, limit).map(|attempts| self.failed_logins = attempts),
+        };
+        if let Err(e) = loaded {
+            self.error = Some(e.report("loading the admin screen"));
+        }
+        if self.selected_index >= self.list_len() {
+            self.selected_index = self.list_len().saturating_sub(1);
+        }
+    }
+
+    fn follows_presence(&self) -> bool {
+        true
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn is_secret_input(&self) -> bool {
+        matches!(self.prompt, Some(Prompt::ResetPassword { .. }))
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        self.command = None;
+        if self.prompt.is_some() {
+            if event == Events::CntrlQ {
+                self.prompt = None;
+                self.input.clear();
+                return Events::InputModeDisable;
+            }
+            else if event == Events::Enter && !self.check_can_administer() {
+                self.prompt = None;
+                self.input.clear();
+                return Events::AccessDenied;
+            }
+            else if event == Events::Enter {
+                return self.submit();
+            }
+            self.input = buffer_string;
+            return Events::Unknown;
+        }
+
+        // any other key cancels a delete, purge or promotion waiting for confirmation
+        let confirming = self.confirming.take();
+        let promoting = self.promoting.take();
+        self.error = None;
+        self.notice = None;
+
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::MenuView;
+            return Events::NavigateView;
+        }
+        else if event == Events::Tab {
+            self.section = self.section.next();
+            self.selected_index = 0;
+            self.offset = 0;
+            self.refresh_data();
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow {
+            self.next_page();
+        }
+        else if event == Events::LeftArrow {
+            self.previous_page();
+        }
+        else if !self.check_can_administer() {
+            return Events::AccessDenied;
+        }
+        else if event == Events::KeyA {
+            return self.start_prompt(Prompt::Announcement);
+        }
+        else if event == Events::KeyE && self.section == Section::Sessions {
+            return self.end_selected_session();
+        }
+        else if event == Events::KeyK && self.section == Section::Users {
+            return self.kick_selected_user();
+        }
+        else if event == Events::KeyB && self.section == Section::Users {
+            return self.toggle_ban();
+        }
+        else if event == Events::KeyR && self.section == Section::Users {
+            self.cycle_role();
+        }
+        else if event == Events::KeyS && self.section == Section::Users {
+            self.promoting = promoting;
+            self.promote_to_sysop();
+        }
+        else if event == Events::KeyP && self.section == Section::Users {
+            if let Some((user_id, username, _, _)) = self.selected_user() {
+                return self.start_prompt(Prompt::ResetPassword { user_id, username });
+            }
+        }
+        else if event == Events::KeyN && self.section == Section::Rooms {
+            if let Some((room_id, room_name)) = self.selected_room() {
+                return self.start_prompt(Prompt::RenameRoom { room_id, room_name });
+            }
+        }
+        else if event == Events::KeyD && self.section == Section::Rooms {
+            if let Some((room_id, _)) = self.selected_room() {
+                self.confirming = confirming;
+                return self.confirm(Confirm::DeleteRoom(room_id));
+            }
+        }
+        else if event == Events::KeyP && self.section == Section::Rooms {
+            if let Some((room_id, _)) = self.selected_room() {
+                self.confirming = confirming;
+                return self.confirm(Confirm::PurgeRoom(room_id));
+            }
+        }
+        Events::Unknown
+    }
+}
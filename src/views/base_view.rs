@@ -1,8 +1,12 @@
 use std::any::Any;
+use std::time::Duration;
+use unicode_width::UnicodeWidthChar;
+use crate::config;
 use crate::input_interface::Events;
 
 
 #[derive(PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum NavigateTo {
     MenuView,
     RoomsView,
@@ -10,21 +14,273 @@ pub enum NavigateTo {
     PeopleView,
     MeView,
     DirectMessageView,
+    ConversationsView,
+    SearchView,
     UserView,
+    AccountView,
+    AdminView,
+    BoardsView,
+    ThreadsView { board_id: i32 },
+    // opens at `post_id`, or at the first unread post when None
+    ThreadView { thread_id: i32, post_id: Option<i32> },
+    // a new thread when thread_id is None, otherwise a reply to the post reply_to
+    ComposeView { board_id: i32, thread_id: Option<i32>, reply_to: Option<i32> },
+    MailboxView { sent: bool },
+    MailReadView { mail_id: i32, sent: bool },
+    // a new mail when mail_id is None, otherwise a reply to or a forward of it
+    MailComposeView { mail_id: Option<i32>, forward: bool },
     NoneView
 }
 
 
+/**
+* The client terminal size in columns and rows, reported through telnet NAWS
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WindowSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        Self { width: 80, height: 24 }
+    }
+}
+
+impl WindowSize {
+
+    /**
+    * the number of rows left for content once `reserved` rows of titles and help text are drawn
+    */
+    pub fn rows_after(&self, reserved: usize) -> usize {
+        self.height.saturating_sub(reserved).max(1)
+    }
+
+    /**
+    * how many rows to load for one page, the rows that fit on screen capped by display.max_page_size
+    */
+    pub fn page_size(&self, reserved: usize) -> i32 {
+        self.rows_after(reserved).min(config::get().display.max_page_size) as i32
+    }
+}
+
+
+/**
+* the red error line shown under a view title, empty when there is no error
+*/
+pub fn render_error(error: &Option<String>) -> String {
+    match error {
+        Some(message) => format!("\x1b[1;31m{}\x1b[0m\r\n\r\n", message),
+        None => String::new(),
+    }
+}
+
+
+/**
+* how many terminal rows `output` takes on a screen `width` columns wide, counting long lines
+* as the rows they wrap to. Color codes take no room and a final line break starts no row
+*/
+pub fn screen_rows(output: &str, width: usize) -> usize {
+    let width = width.max(1);
+    let mut lines: Vec<usize> = vec![0];
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip the escape sequence up to its final letter
+            for code in chars.by_ref() {
+                if code.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+        else if c == '\n' {
+            lines.push(0);
+        }
+        else if let Some(last) = lines.last_mut() {
+            *last += c.width().unwrap_or(0);
+        }
+    }
+    if lines.len() > 1 && lines.last() == Some(&0) {
+        lines.pop();
+    }
+    lines.iter().map(|line_width| line_width.div_ceil(width).max(1)).sum()
+}
+
+
+/**
+* short form of a duration like `2h 05m`, `4m 10s` or `12s`
+*/
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60)
+    }
+    else if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+    else {
+        format!("{}s", seconds)
+    }
+}
+
+
+/**
+* word wraps `text` so the first line fits in `first_width` columns and the rest in `width` columns,
+* words longer than a line are split
+*/
+pub fn wrap_text(text: &str, first_width: usize, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    let mut max_width = first_width.max(1);
+
+    for word in text.split(' ') {
+        let word_width: usize = word.chars().map(|c| c.width().unwrap_or(0)).sum();
+        let space = if line.is_empty() { 0 } else { 1 };
+
+        if line_width + space + word_width <= max_width {
+            if space == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            line_width += space + word_width;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+            max_width = width.max(1);
+        }
+
+        // split words that do not fit on a line of their own
+        for c in word.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if line_width + char_width > max_width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+                max_width = width.max(1);
+            }
+            line.push(c);
+            line_width += char_width;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+
+/**
+* a line of a post or mail body followed by a line break, quoted lines starting with `>` are dimmed
+*/
+pub fn render_body_line(line: &str) -> String {
+    if line.starts_with('>') {
+        format!("\x1b[2m{}\x1b[0m\r\n", line)
+    } else {
+        format!("{}\r\n", line)
+    }
+}
+
+
+/**
+* quotes up to `max_lines` lines of `body` for a reply, leaving out the lines `author` quoted themselves
+*/
+pub fn quote_lines(author: &str, body: &str, max_lines: usize) -> Vec<String> {
+    let own_lines: Vec<&str> = body.split('\n').filter(|line| !line.starts_with('>')).collect();
+    let mut quoted = vec![format!("> {} wrote:", author)];
+    quoted.extend(own_lines.iter().take(max_lines).map(|line| format!("> {}", line)));
+    if own_lines.len() > max_lines {
+        quoted.push(String::from("> ..."));
+    }
+    quoted
+}
+
+
+/**
+* the last `rows` lines of a post or mail being written, then the prompt with the line being typed
+*/
+pub fn render_draft(lines: &[String], input: &str, rows: usize) -> String {
+    let mut output = String::new();
+    for line in lines.iter().skip(lines.len().saturating_sub(rows)) {
+        output.push_str(&render_body_line(line));
+    }
+    output.push_str("\x1b[2mEnter adds a line, Enter on an empty line sends, CNTRL+Q cancels\x1b[0m\r\n");
+    output.push_str(&format!("\x1b[1;32m>\x1b[0m {}", input));
+    output
+}
+
+
+/**
+* renders chat style `(user_id, created_date, username, message)` rows wrapped to the window width,
+* keeping only the newest lines that fit in `rows`. Messages from `user_id` are highlighted and
+* the date of the `selected` message is shown in reverse video
+*/
+pub fn render_messages(messages: &[(i32, String, String, String)], user_id: i32, window: &WindowSize, rows: usize, selected: Option<usize>) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for (index, (author_id, created_date, user, message)) in messages.iter().enumerate() {
+        let user_color = if *author_id == user_id { "\x1b[1;35m" } else { "\x1b[38;5;214m" };
+        let date_style = if selected == Some(index) { "\x1b[7m" } else { "\x1b[1;32m" };
+        let prefix = format!("{}[{}]\x1b[0m {}{}\x1b[0m  ", date_style, created_date, user_color, user);
+        let prefix_width = created_date.chars().count() + user.chars().count() + 5;
+
+        let wrapped = wrap_text(message, window.width.saturating_sub(prefix_width), window.width);
+        for (idx, line) in wrapped.into_iter().enumerate() {
+            if idx == 0 {
+                lines.push(format!("{}{}", prefix, line));
+            } else {
+                lines.push(line);
+            }
+        }
+    }
+
+    let skip = lines.len().saturating_sub(rows);
+    let mut output = String::new();
+    for line in lines.iter().skip(skip) {
+        output.push_str(line);
+        output.push_str("\r\n");
+    }
+    output
+}
+
+
 pub trait View: Send {
 
-    fn as_any(&self) -> &(dyn Any);
+    fn as_any(&self) -> &dyn Any;
 
     fn get_navigate_to(&self) -> &NavigateTo;
 
-    fn render(&self) -> String;
+    fn render(&self, window: &WindowSize) -> String;
 
     fn refresh_data(&mut self) {}
 
+    /**
+    * called when the client terminal is resized, views that page their data reload it here
+    */
+    fn resize(&mut self, _window: &WindowSize) {}
+
+    /**
+    * true when the view uses ↑ / ↓ for scrolling while in input mode, otherwise they browse the input history
+    */
+    fn scrolls_with_arrows(&self) -> bool {
+        false
+    }
+
+    /**
+    * true if the view shows who is online or in which room, so it is refreshed whenever
+    * anyone logs in, logs out, joins or leaves a room
+    */
+    fn follows_presence(&self) -> bool {
+        false
+    }
+
+    /**
+    * true while the view is collecting input that must not be shown or remembered, like a password
+    */
+    fn is_secret_input(&self) -> bool {
+        false
+    }
+
     fn handle_event(&mut self,  event: Events, buffer_string: String) -> Events;
 
 }
\ No newline at end of file
@@ -0,0 +1,215 @@
+use std::any::Any;
+use crate::db::manage::{BoardRow, Manager};
+use crate::input_interface::Events;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title and the help text below the board list
+const RESERVED_ROWS: usize = 9;
+
+// longest board name in characters
+const MAX_BOARD_NAME: usize = 40;
+
+
+/**
+* What the sysop is typing while creating a board
+*/
+#[derive(PartialEq, Eq, Clone)]
+enum CreateStep {
+    Name,
+    // the name typed in the first step
+    Description(String),
+}
+
+
+/**
+* Lists the message boards with how many threads have posts the user has not read
+*/
+pub struct BoardsView {
+    user_id: i32,
+    navigate_to: NavigateTo,
+    boards: Vec<BoardRow>,
+    selected_index: usize,
+    offset: i32,
+    page_size: i32,
+    can_create: bool,
+    creating: Option<CreateStep>,
+    input: String,
+    error: Option<String>,
+    notice: Option<String>,
+}
+
+
+impl BoardsView {
+    pub fn new(user_id: i32, window: &WindowSize) -> Self {
+        let mut view = Self {
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            boards: Vec::new(),
+            selected_index: 0,
+            offset: 0,
+            page_size: window.page_size(RESERVED_ROWS),
+            can_create: permissions::can(user_id, Action::CreateBoard),
+            creating: None,
+            input: String::new(),
+            error: None,
+            notice: None,
+        };
+        view.refresh_data();
+        view
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.boards.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    /**
+    * handles Enter while creating a board, the name is asked first and then the description
+    */
+    fn submit(&mut self) -> Events {
+        let input = std::mem::take(&mut self.input);
+        match self.creating.take() {
+            Some(CreateStep::Name) if input.trim().is_empty() => Events::InputModeDisable,
+            Some(CreateStep::Name) if input.trim().chars().count() > MAX_BOARD_NAME => {
+                self.error = Some(format!("Board names can be at most {} characters long.", MAX_BOARD_NAME));
+                Events::InputModeDisable
+            }
+            Some(CreateStep::Name) => {
+                self.creating = Some(CreateStep::Description(input.trim().to_string()));
+                Events::Enter
+            }
+            Some(CreateStep::Description(name)) => {
+                match Manager::create_board(&name, &input) {
+                    Ok(()) => self.notice = Some(format!("Created the {} board.", name)),
+                    Err(e) => self.error = Some(e.report("creating a board")),
+                }
+                self.refresh_data();
+                Events::InputModeDisable
+            }
+            None => Events::Unknown,
+        }
+    }
+}
+
+
+impl View for BoardsView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, _window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str("\x1b[1;32mBoards\x1b[0m\r\n\r\n");
+        output.push_str(&render_error(&self.error));
+        if let Some(notice) = &self.notice {
+            output.push_str(&format!("\x1b[1;33m{}\x1b[0m\r\n\r\n", notice));
+        }
+
+        match &self.creating {
+            Some(CreateStep::Name) => {
+                output.push_str(&format!("\x1b[1;32m> Board name (CNTRL+Q to cancel):\x1b[0m {}", self.input));
+                return output;
+            }
+            Some(CreateStep::Description(name)) => {
+                output.push_str(&format!("\x1b[1;32m> Description of {} (CNTRL+Q to cancel):\x1b[0m {}", name, self.input));
+                return output;
+            }
+            None => {}
+        }
+
+        for (idx, (_, name, description, threads, new_threads)) in self.boards.iter().enumerate() {
+            let new = if *new_threads > 0 { format!(", {} new", new_threads) } else { String::new() };
+            let line = format!("{} ({} threads{})  {}", name, threads, new, description);
+            if idx == self.selected_index {
+                output.push_str(&format!("\x1b[1;33m> {}\x1b[0m\r\n", line));
+            } else {
+                output.push_str(&format!("  {}\r\n", line));
+            }
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to open a board, ← / → to change page\r\n");
+        if self.can_create {
+            output.push_str("[C] Create board\r\n");
+        }
+        output.push_str("[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_boards(self.user_id, self.offset, self.page_size) {
+            Ok(boards) => self.boards = boards,
+            Err(e) => self.error = Some(e.report("loading boards")),
+        }
+        if self.selected_index >= self.boards.len() {
+            self.selected_index = self.boards.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if self.creating.is_some() {
+            if event == Events::CntrlQ {
+                self.creating = None;
+                self.input.clear();
+                return Events::InputModeDisable;
+            }
+            else if event == Events::Enter {
+                return self.submit();
+            }
+            self.input = buffer_string;
+            return Events::Unknown;
+        }
+
+        self.error = None;
+        self.notice = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::MenuView;
+            return Events::NavigateView;
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow && self.boards.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Enter && !self.boards.is_empty() {
+            self.navigate_to = NavigateTo::ThreadsView { board_id: self.boards[self.selected_index].0 };
+            return Events::NavigateView;
+        }
+        else if event == Events::KeyC && !self.can_create {
+            self.error = Some(Action::CreateBoard.denied_message().to_string());
+        }
+        else if event == Events::KeyC {
+            self.creating = Some(CreateStep::Name);
+            return Events::InputModeEnable;
+        }
+        Events::Unknown
+    }
+}
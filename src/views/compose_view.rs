@@ -0,0 +1,190 @@
+use std::any::Any;
+use crate::db::error::DbResult;
+use crate::db::manage::Manager;
+use crate::input_interface::Events;
+use crate::views::base_view::{quote_lines, render_draft, render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title, the subject and the help text around the lines written so far
+const RESERVED_ROWS: usize = 9;
+
+const MAX_SUBJECT: usize = 80;
+const MAX_LINES: usize = 100;
+const MAX_BODY: usize = 4000;
+
+// lines of the parent post quoted in a reply
+const QUOTE_LINES: usize = 5;
+
+
+/**
+* Writes a new thread or a reply line by line, an empty line posts it
+*/
+pub struct ComposeView {
+    user_id: i32,
+    board_id: i32,
+    // None while starting a new thread
+    thread_id: Option<i32>,
+    reply_to: Option<i32>,
+    title: String,
+    // None until the subject of a new thread is entered
+    subject: Option<String>,
+    lines: Vec<String>,
+    // how many of the lines are the quote of the parent post
+    quoted: usize,
+    input: String,
+    navigate_to: NavigateTo,
+    error: Option<String>,
+}
+
+
+impl ComposeView {
+    pub fn new(user_id: i32, board_id: i32, thread_id: Option<i32>, reply_to: Option<i32>) -> Self {
+        let mut view = Self {
+            user_id,
+            board_id,
+            thread_id,
+            reply_to,
+            title: String::from("New thread"),
+            subject: None,
+            lines: Vec::new(),
+            quoted: 0,
+            input: String::new(),
+            navigate_to: NavigateTo::NoneView,
+            error: None,
+        };
+        if let (Some(thread_id), Some(reply_to)) = (thread_id, reply_to) {
+            if let Err(e) = view.quote_parent(thread_id, reply_to) {
+                view.error = Some(e.report("loading the post to reply to"));
+            }
+        }
+        view
+    }
+
+    /**
+    * starts a reply with the first lines of the parent post, leaving out what it quoted itself
+    */
+    fn quote_parent(&mut self, thread_id: i32, reply_to: i32) -> DbResult<()> {
+        let (_, subject) = Manager::get_thread(thread_id)?;
+        self.title = format!("Reply to {}", subject);
+        self.subject = Some(subject);
+        let posts = Manager::get_posts(thread_id)?;
+        if let Some((_, _, author, _, body)) = posts.iter().find(|post| post.0 == reply_to) {
+            self.lines = quote_lines(author, body, QUOTE_LINES);
+            self.quoted = self.lines.len();
+        }
+        Ok(())
+    }
+
+    /**
+    * where to go when the post is cancelled
+    */
+    fn go_back(&mut self) -> Events {
+        self.navigate_to = match self.thread_id {
+            Some(thread_id) => NavigateTo::ThreadView { thread_id, post_id: self.reply_to },
+            None => NavigateTo::ThreadsView { board_id: self.board_id },
+        };
+        Events::NavigateView
+    }
+
+    fn submit_subject(&mut self) -> Events {
+        let subject = self.input.trim().to_string();
+        if subject.is_empty() {
+            self.error = Some(String::from("The thread needs a subject."));
+            return Events::Unknown;
+        }
+        if subject.chars().count() > MAX_SUBJECT {
+            self.error = Some(format!("Subjects can be at most {} characters long.", MAX_SUBJECT));
+            return Events::Unknown;
+        }
+        self.subject = Some(subject);
+        self.input.clear();
+        Events::Enter
+    }
+
+    fn submit_line(&mut self) -> Events {
+        let written: usize = self.lines.iter().map(|line| line.len() + 1).sum();
+        if self.lines.len() >= MAX_LINES || written + self.input.len() > MAX_BODY {
+            self.error = Some(format!("Posts can be at most {} lines or {} characters long.", MAX_LINES, MAX_BODY));
+            return Events::Unknown;
+        }
+        self.lines.push(std::mem::take(&mut self.input));
+        Events::Enter
+    }
+
+    fn post(&mut self) -> Events {
+        if self.lines.len() == self.quoted {
+            self.error = Some(String::from("Write something before posting."));
+            return Events::Unknown;
+        }
+        let body = self.lines.join("\n");
+        let posted = match (self.thread_id, self.reply_to, &self.subject) {
+            (Some(thread_id), Some(reply_to), _) => {
+                Manager::reply_to_post(thread_id, reply_to, self.user_id, &body).map(|post_id| (thread_id, Some(post_id)))
+            }
+            (_, _, Some(subject)) => {
+                Manager::create_thread(self.board_id, self.user_id, subject, &body).map(|thread_id| (thread_id, None))
+            }
+            _ => return Events::Unknown,
+        };
+        match posted {
+            Ok((thread_id, post_id)) => {
+                self.navigate_to = NavigateTo::ThreadView { thread_id, post_id };
+                Events::NavigateView
+            }
+            Err(e) => {
+                self.error = Some(e.report("posting to a board"));
+                Events::Unknown
+            }
+        }
+    }
+}
+
+
+impl View for ComposeView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.title));
+        output.push_str(&render_error(&self.error));
+
+        let subject = match &self.subject {
+            Some(subject) => subject,
+            None => {
+                output.push_str(&format!("\x1b[1;32m> Subject (CNTRL+Q to cancel):\x1b[0m {}", self.input));
+                return output;
+            }
+        };
+        if self.thread_id.is_none() {
+            output.push_str(&format!("Subject: {}\r\n\r\n", subject));
+        }
+
+        // only the last lines are shown once the post is longer than the screen
+        output.push_str(&render_draft(&self.lines, &self.input, window.rows_after(RESERVED_ROWS)));
+        output
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if event == Events::CntrlQ {
+            return self.go_back();
+        }
+        else if event == Events::Enter {
+            self.error = None;
+            if self.subject.is_none() {
+                return self.submit_subject();
+            }
+            else if self.input.is_empty() {
+                return self.post();
+            }
+            return self.submit_line();
+        }
+        self.input = buffer_string;
+        Events::Unknown
+    }
+}
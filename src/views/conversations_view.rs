@@ -0,0 +1,149 @@
+use std::any::Any;
+use crate::db::manage::{ConversationRow, Manager};
+use crate::input_interface::Events;
+use crate::views::base_view::{render_error, wrap_text, NavigateTo, View, WindowSize};
+
+// rows used by the title and the help text below the conversation list
+const RESERVED_ROWS: usize = 7;
+
+
+/**
+* Lists the user's direct message conversations, the most recent first, with a preview of the
+* last message and how many are unread
+*/
+pub struct ConversationsView {
+    user_id: i32,
+    navigate_to: NavigateTo,
+    conversations: Vec<ConversationRow>,
+    selected_index: usize,
+    selected_user_id: i32,
+    offset: i32,
+    page_size: i32,
+    error: Option<String>,
+}
+
+
+impl ConversationsView {
+    pub fn new(user_id: i32, window: &WindowSize) -> Self {
+        let mut view = Self {
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            conversations: Vec::new(),
+            selected_index: 0,
+            selected_user_id: -1,
+            offset: 0,
+            page_size: window.page_size(RESERVED_ROWS),
+            error: None,
+        };
+        view.refresh_data();
+        view
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.conversations.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    /**
+    * the user whose conversation was picked with Enter
+    */
+    pub fn get_selected_user_id(&self) -> i32 {
+        self.selected_user_id
+    }
+}
+
+
+impl View for ConversationsView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str("\x1b[1;32mMessages\x1b[0m\r\n\r\n");
+        output.push_str(&render_error(&self.error));
+
+        if self.conversations.is_empty() {
+            output.push_str("No conversations yet, send someone a message from People.\r\n");
+        }
+        for (idx, (_, username, message, created_date, sent_by_me, unread)) in self.conversations.iter().enumerate() {
+            let unread = if *unread > 0 { format!(" ({} unread)", unread) } else { String::new() };
+            let mut line = format!("{}{}  [{}]  {}", username, unread, created_date, if *sent_by_me { "you: " } else { "" });
+
+            // the preview of the last message fills the rest of the row
+            let used = line.chars().count() + 2;
+            let room = window.width.saturating_sub(used + 1).max(1);
+            let preview = wrap_text(&message.replace('\n', " "), room, room);
+            line.push_str(&preview[0]);
+            if preview.len() > 1 {
+                line.push('…');
+            }
+
+            if idx == self.selected_index {
+                output.push_str(&format!("\x1b[1;33m> {}\x1b[0m\r\n", line));
+            } else {
+                output.push_str(&format!("  {}\r\n", line));
+            }
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to open a conversation, ← / → to change page\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_conversations(self.user_id, self.offset, self.page_size) {
+            Ok(conversations) => self.conversations = conversations,
+            Err(e) => self.error = Some(e.report("loading conversations")),
+        }
+        if self.selected_index >= self.conversations.len() {
+            self.selected_index = self.conversations.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
+        self.error = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::MenuView;
+            return Events::NavigateView;
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow && self.conversations.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Enter && !self.conversations.is_empty() {
+            self.selected_user_id = self.conversations[self.selected_index].0;
+            self.navigate_to = NavigateTo::DirectMessageView;
+            return Events::NavigateView;
+        }
+        Events::Unknown
+    }
+}
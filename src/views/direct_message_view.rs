@@ -1,30 +1,38 @@
 use std::any::Any;
 use crate::db::manage::Manager;
 use crate::input_interface::Events;
-use crate::views::base_view::{NavigateTo, View};
+use crate::views::base_view::{render_error, render_messages, NavigateTo, View, WindowSize};
+
+// rows used by the title and the message prompt
+const RESERVED_ROWS: usize = 4;
 
 pub struct DirectMessageView {
     query_offset: i32,
+    page_size: i32,
     user_id: i32,
     user_id_2: i32,
     navigate_to: NavigateTo,
     message: String,
     messages: Vec<(i32, String, String, String)>,
+    error: Option<String>,
 }
 
 
 impl DirectMessageView {
-    pub fn new(user_id: i32, user_id_2: i32) -> Self {
-        let mut messages = Manager::get_direct_messages_for(user_id, user_id_2, 0);
-        messages.reverse();
-        Self {
+    pub fn new(user_id: i32, user_id_2: i32, window: &WindowSize) -> Self {
+        let page_size = window.page_size(RESERVED_ROWS);
+        let mut view = Self {
             user_id,
             user_id_2,
             navigate_to: NavigateTo::NoneView,
-            messages,
+            messages: Vec::new(),
             message: String::new(),
-            query_offset: 0
-        }
+            query_offset: 0,
+            page_size,
+            error: None,
+        };
+        view.refresh_data();
+        view
     }
 
     /**
@@ -39,7 +47,7 @@ impl DirectMessageView {
 
 impl View for DirectMessageView {
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
@@ -47,20 +55,13 @@ impl View for DirectMessageView {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H");
         output.push_str("\x1b[1;32mMessages\x1b[0m\r\n\r\n");
+        output.push_str(&render_error(&self.error));
 
 
-        // Append sorted rooms to output
-        for (user_id, created_date, user, message) in self.messages.iter() {
-            if *user_id == self.user_id {
-                output.push_str(&format!("\x1b[1;32m[{}]\x1b[0m \x1b[1;35m{}\x1b[0m  {}\r\n", created_date, user, message));
-            }
-            else {
-                output.push_str(&format!("\x1b[1;32m[{}]\x1b[0m \x1b[38;5;214m{}\x1b[0m  {}\r\n", created_date, user, message));
-            }
-        }
+        output.push_str(&render_messages(&self.messages, self.user_id, window, window.page_size(RESERVED_ROWS) as usize, None));
         output.push_str("\n\x1b[1;35m>>>\x1b[0m ");
         output.push_str(self.message.as_str());
         output
@@ -68,14 +69,35 @@ impl View for DirectMessageView {
 
 
     fn refresh_data(&mut self) {
-        self.messages = Manager::get_direct_messages_for(self.user_id, self.user_id_2, self.query_offset);
-        self.messages.reverse();
+        match Manager::get_direct_messages_for(self.user_id, self.user_id_2, self.query_offset, self.page_size) {
+            Ok(mut messages) => {
+                messages.reverse();
+                self.messages = messages;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.report("loading direct messages")),
+        }
+        // messages that arrive while the user pages back through history have not been on screen
+        if self.query_offset == 0 {
+            if let Err(e) = Manager::mark_conversation_read(self.user_id, self.user_id_2) {
+                self.error = Some(e.report("marking messages read"));
+            }
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn scrolls_with_arrows(&self) -> bool {
+        true
     }
 
     fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
         let mut result_event: Events = Events::Unknown;
 
-        if event == Events::UpArrow  && self.messages.len() == 20 {
+        if event == Events::UpArrow  && self.messages.len() == self.page_size as usize {
             self.query_offset += 1;
             self.refresh_data();
         }
@@ -86,10 +108,16 @@ impl View for DirectMessageView {
         }
 
         else if event == Events::Enter {
-            if self.message != "" {
-                Manager::post_direct_message(self.user_id, self.user_id_2, self.message.clone());
-                result_event = Events::DirectMessageSent;
-                self.message.clear();
+            if !self.message.is_empty() {
+                // the message is kept in the prompt if it could not be saved
+                match Manager::post_direct_message(self.user_id, self.user_id_2, self.message.clone()) {
+                    Ok(()) => {
+                        result_event = Events::DirectMessageSent;
+                        self.message.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e.report("sending a direct message")),
+                }
             }
             else {
                 result_event = Events::Unknown;
@@ -1,37 +1,49 @@
 use std::any::Any;
+use std::net::IpAddr;
+use crate::account_policy::{self, PolicyError};
+use crate::db::error::DbError;
 use crate::db::manage::Manager;
 use crate::input_interface::Events;
-use crate::views::base_view::{NavigateTo, View};
+use crate::throttle;
+use crate::views::base_view::{NavigateTo, View, WindowSize};
 use crate::views::base_view::NavigateTo::NoneView;
 
 pub struct LoginRegisterView {
     input_mode: bool,
     username: String,
     password: String,
+    // the password typed a second time when registering
+    confirm_password: String,
     error: bool,
-    error_message: &'static str,
+    error_message: String,
     collecting_username: bool,
     collecting_password: bool,
+    collecting_confirmation: bool,
     is_login: bool,
     is_create: bool,
     options: Vec<&'static str>,
     navigate_to: NavigateTo,
     selected_index: usize,
-    user_id: i32
+    user_id: i32,
+    // failed logins are counted against the account and this address
+    remote_ip: IpAddr,
 }
 
 
 impl LoginRegisterView {
-    pub fn new() -> LoginRegisterView {
+    pub fn new(remote_ip: IpAddr) -> LoginRegisterView {
         Self {
+            remote_ip,
             input_mode: false,
             username: "".to_string(),
             password: "".to_string(),
+            confirm_password: "".to_string(),
             user_id: -1,
             error: false,
-            error_message: "",
+            error_message: String::new(),
             collecting_username: false,
             collecting_password: false,
+            collecting_confirmation: false,
             options: vec!["Login", "Register"],
             selected_index: 0,
             navigate_to:NoneView,
@@ -45,32 +57,77 @@ impl LoginRegisterView {
         self.user_id
     }
 
-    fn validate_credentials(&mut self) -> bool {
-        let mut error_msg: &str = "";
-        let user_id: i32;
-        if self.is_login {
-            user_id = Manager::validate_user(self.username.as_str(), self.password.as_str());
-            error_msg = "Unable to validate user, maybe wrong password?";
-        }
-        else if self.is_create {
-            user_id =  Manager::create_user(self.username.as_str(), self.password.as_str());
-            error_msg = "Unable to create user, maybe username already taken";
-        }
-        else {
-            user_id = -1
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    /**
+    * writes a refused login to the failed login log, a failure to log is only printed
+    */
+    fn log_failed_login(&self, reason: &str) {
+        println!("Failed login for \"{}\" from {}: {}", self.username, self.remote_ip, reason);
+        if let Err(e) = Manager::log_failed_login(&self.username, &self.remote_ip.to_string(), reason) {
+            e.report("logging a failed login");
         }
+    }
 
+    fn show_error(&mut self, message: String) {
+        self.error = true;
+        self.error_message = message;
+    }
+
+    /**
+    * registration checks each answer as soon as it is entered, true when the rules are met
+    */
+    fn check_policy(&mut self, result: Result<(), PolicyError>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.show_error(e.to_string());
+                false
+            }
+        }
+    }
 
-        if user_id < 0 {
-            self.error = true;
-            self.error_message = error_msg;
-            false
+    fn validate_credentials(&mut self) -> bool {
+        let user_id = if self.is_login {
+            // refuse without checking the password while the account or address is backing off,
+            // otherwise the attempt counts as a failure until the password turns out right
+            if let Err(refusal) = throttle::check_login(&self.username, self.remote_ip) {
+                self.log_failed_login(refusal.reason());
+                self.show_error(refusal.user_message());
+                return false;
+            }
+            let result = Manager::validate_user(self.username.as_str(), self.password.as_str());
+            match result {
+                Ok(_) => throttle::record_success(&self.username, self.remote_ip),
+                Err(DbError::InvalidCredentials) => self.log_failed_login("wrong username or password"),
+                Err(DbError::Banned) => self.log_failed_login("banned"),
+                Err(_) => {}
+            }
+            result
+        }
+        else if self.is_create && self.password != self.confirm_password {
+            return self.check_policy(Err(PolicyError::PasswordMismatch));
+        }
+        else if self.is_create {
+            Manager::create_user(self.username.as_str(), self.password.as_str())
         }
         else {
-            Manager::login_user(user_id);
-            self.user_id = user_id;
-            self.navigate_to = NavigateTo::MenuView;
-            true
+            return false;
+        };
+
+        match user_id {
+            Ok(user_id) => {
+                self.user_id = user_id;
+                self.navigate_to = NavigateTo::MenuView;
+                true
+            }
+            Err(e) => {
+                let message = e.report("logging in");
+                self.show_error(message);
+                false
+            }
         }
     }
 
@@ -107,11 +164,13 @@ impl LoginRegisterView {
         self.input_mode = false;
         self.username = "".to_string();
         self.password = "".to_string();
+        self.confirm_password = "".to_string();
         self.is_login = false;
         self.is_create = false;
         self.user_id = -1;
         self.collecting_username = false;
         self.collecting_password = false;
+        self.collecting_confirmation = false;
     }
 
 }
@@ -119,24 +178,26 @@ impl LoginRegisterView {
 impl View for LoginRegisterView {
 
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
     fn get_navigate_to(&self) -> &NavigateTo {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, _window: &WindowSize) -> String {
         // title of view
         let mut output = String::from("\x1b[2J\x1b[H");
 
 
         // error message to display if error is true
+        let title = if self.is_create { "Register" } else { "Login" };
         if self.error {
-            output.push_str("\x1b[1;31mLogin ERROR\x1b[0m\r\n\r\n");
-            output.push_str(self.error_message);
+            output.push_str(&format!("\x1b[1;31m{} ERROR\x1b[0m\r\n\r\n", title));
+            output.push_str(&self.error_message);
+            output.push_str("\r\n\r\nPress Enter to go back");
         } else {
-            output.push_str("\x1b[1;32mLogin\x1b[0m\r\n\r\n");
+            output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", title));
         }
 
         // input prompts if in input mode
@@ -148,6 +209,9 @@ impl View for LoginRegisterView {
         else if self.collecting_password && !self.error {
             output.push_str("\x1b[1;32m> Password: ");
         }
+        else if self.collecting_confirmation && !self.error {
+            output.push_str("\x1b[1;32m> Confirm password: ");
+        }
 
 
         // if not in input mode and no error exists display login options
@@ -165,6 +229,10 @@ impl View for LoginRegisterView {
     }
 
 
+    fn is_secret_input(&self) -> bool {
+        !self.error && (self.collecting_password || self.collecting_confirmation)
+    }
+
     fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
         let mut view_event: Events = Events::Unknown;
 
@@ -173,17 +241,28 @@ impl View for LoginRegisterView {
             self.reset_view_state();
         }
 
-        // validate credentials if enter is pressed while collecting password
-        else if self.input_mode && event == Events::Enter && self.collecting_password {
+        // validate credentials if enter is pressed while collecting password, or its confirmation when registering
+        else if self.input_mode && event == Events::Enter && (self.collecting_confirmation || (self.collecting_password && self.is_login)) {
            if self.validate_credentials() {
                view_event = Events::Authenticate
             }
         }
 
+        // a new password has to meet the rules before it is asked for again
+        else if self.input_mode && event == Events::Enter && self.collecting_password {
+            if self.check_policy(account_policy::check_password(&self.username, &self.password)) {
+                self.collecting_password = false;
+                self.collecting_confirmation = true;
+            }
+        }
+
         // change state to collecting password if enter is hit during collecting username
         else if self.input_mode && self.collecting_username  && event == Events::Enter {
-            self.collecting_username = false;
-            self.collecting_password = true;
+            if self.is_login || self.check_policy(account_policy::check_username(&self.username)) {
+                self.collecting_username = false;
+                self.collecting_password = true;
+                view_event = Events::SecretInputModeEnable;
+            }
         }
 
         // handle arrow key selection when not in input mode
@@ -213,6 +292,10 @@ impl View for LoginRegisterView {
             self.password = buffer_string;
         }
 
+        else if self.input_mode && self.collecting_confirmation {
+            self.confirm_password = buffer_string;
+        }
+
 
 
         // if the view defined a new event returns that, else returns the original event passed
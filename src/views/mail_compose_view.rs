@@ -0,0 +1,208 @@
+use std::any::Any;
+use crate::db::error::DbResult;
+use crate::db::manage::Manager;
+use crate::input_interface::Events;
+use crate::views::base_view::{quote_lines, render_draft, render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title, the header and the help text around the lines written so far
+const RESERVED_ROWS: usize = 10;
+
+const MAX_SUBJECT: usize = 80;
+const MAX_LINES: usize = 100;
+const MAX_BODY: usize = 4000;
+
+// lines of the mail quoted in a reply
+const QUOTE_LINES: usize = 5;
+
+
+/**
+* Writes a new mail, a reply or a forward. Asks for the recipient and subject unless they are
+* known, then the body line by line until an empty line sends it
+*/
+pub struct MailComposeView {
+    user_id: i32,
+    // the mail replied to or forwarded
+    mail_id: Option<i32>,
+    forward: bool,
+    title: String,
+    to: Option<String>,
+    subject: Option<String>,
+    lines: Vec<String>,
+    // how many of the lines quote the mail replied to
+    quoted: usize,
+    input: String,
+    navigate_to: NavigateTo,
+    error: Option<String>,
+}
+
+
+impl MailComposeView {
+    pub fn new(user_id: i32, mail_id: Option<i32>, forward: bool) -> Self {
+        let mut view = Self {
+            user_id,
+            mail_id,
+            forward,
+            title: String::from("New mail"),
+            to: None,
+            subject: None,
+            lines: Vec::new(),
+            quoted: 0,
+            input: String::new(),
+            navigate_to: NavigateTo::NoneView,
+            error: None,
+        };
+        if let Some(mail_id) = mail_id {
+            if let Err(e) = view.start_from(mail_id) {
+                view.error = Some(e.report("loading mail"));
+            }
+        }
+        view
+    }
+
+    /**
+    * a reply goes back to the sender quoting their first lines, a forward carries the whole mail
+    */
+    fn start_from(&mut self, mail_id: i32) -> DbResult<()> {
+        let mail = Manager::get_mail(mail_id, self.user_id)?;
+        let subject = &mail["subject"];
+        if self.forward {
+            self.title = String::from("Forward mail");
+            self.subject = Some(format!("Fwd: {}", subject));
+            self.lines.push(format!("---- Forwarded mail from {}, {} ----", mail["sender"], mail["created_date"]));
+            self.lines.extend(mail["body"].split('\n').map(|line| format!("> {}", line)));
+        } else {
+            self.title = format!("Reply to {}", mail["sender"]);
+            self.to = Some(mail["sender_username"].clone());
+            let subject = if subject.starts_with("Re: ") { subject.clone() } else { format!("Re: {}", subject) };
+            self.subject = Some(subject);
+            self.lines = quote_lines(&mail["sender"], &mail["body"], QUOTE_LINES);
+            self.quoted = self.lines.len();
+        }
+        Ok(())
+    }
+
+    fn cancel(&mut self) -> Events {
+        self.navigate_to = match self.mail_id {
+            Some(mail_id) if !self.forward => NavigateTo::MailReadView { mail_id, sent: false },
+            _ => NavigateTo::MailboxView { sent: false },
+        };
+        Events::NavigateView
+    }
+
+    fn submit_recipient(&mut self) -> Events {
+        let username = self.input.trim().to_string();
+        if username.is_empty() {
+            return Events::Unknown;
+        }
+        match Manager::get_user_id_by_name(&username) {
+            Ok(_) => {
+                self.to = Some(username);
+                self.input.clear();
+                Events::Enter
+            }
+            Err(e) => {
+                self.error = Some(e.report("looking up a recipient"));
+                Events::Unknown
+            }
+        }
+    }
+
+    fn submit_subject(&mut self) -> Events {
+        let subject = self.input.trim().to_string();
+        if subject.is_empty() {
+            self.error = Some(String::from("The mail needs a subject."));
+            return Events::Unknown;
+        }
+        if subject.chars().count() > MAX_SUBJECT {
+            self.error = Some(format!("Subjects can be at most {} characters long.", MAX_SUBJECT));
+            return Events::Unknown;
+        }
+        self.subject = Some(subject);
+        self.input.clear();
+        Events::Enter
+    }
+
+    fn submit_line(&mut self) -> Events {
+        let written: usize = self.lines.iter().map(|line| line.len() + 1).sum();
+        if self.lines.len() >= MAX_LINES || written + self.input.len() > MAX_BODY {
+            self.error = Some(format!("Mail can be at most {} lines or {} characters long.", MAX_LINES, MAX_BODY));
+            return Events::Unknown;
+        }
+        self.lines.push(std::mem::take(&mut self.input));
+        Events::Enter
+    }
+
+    fn send(&mut self) -> Events {
+        if self.lines.len() == self.quoted {
+            self.error = Some(String::from("Write something before sending."));
+            return Events::Unknown;
+        }
+        let (Some(to), Some(subject)) = (&self.to, &self.subject) else {
+            return Events::Unknown;
+        };
+        match Manager::send_mail(self.user_id, to, subject, &self.lines.join("\n")) {
+            Ok(_) => {
+                self.navigate_to = NavigateTo::MailboxView { sent: true };
+                Events::NavigateView
+            }
+            Err(e) => {
+                self.error = Some(e.report("sending mail"));
+                Events::Unknown
+            }
+        }
+    }
+}
+
+
+impl View for MailComposeView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.title));
+        output.push_str(&render_error(&self.error));
+
+        let Some(to) = &self.to else {
+            output.push_str(&format!("\x1b[1;32m> To (username, CNTRL+Q to cancel):\x1b[0m {}", self.input));
+            return output;
+        };
+        output.push_str(&format!("\x1b[1;33mTo:\x1b[0m {}\r\n", to));
+        let Some(subject) = &self.subject else {
+            output.push_str(&format!("\x1b[1;32m> Subject (CNTRL+Q to cancel):\x1b[0m {}", self.input));
+            return output;
+        };
+        output.push_str(&format!("\x1b[1;33mSubject:\x1b[0m {}\r\n\r\n", subject));
+
+        // only the last lines are shown once the mail is longer than the screen
+        output.push_str(&render_draft(&self.lines, &self.input, window.rows_after(RESERVED_ROWS)));
+        output
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if event == Events::CntrlQ {
+            return self.cancel();
+        }
+        else if event == Events::Enter {
+            self.error = None;
+            if self.to.is_none() {
+                return self.submit_recipient();
+            }
+            else if self.subject.is_none() {
+                return self.submit_subject();
+            }
+            else if self.input.is_empty() {
+                return self.send();
+            }
+            return self.submit_line();
+        }
+        self.input = buffer_string;
+        Events::Unknown
+    }
+}
@@ -0,0 +1,171 @@
+use std::any::Any;
+use std::collections::HashMap;
+use crate::db::manage::Manager;
+use crate::input_interface::Events;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_body_line, render_error, wrap_text, NavigateTo, View, WindowSize};
+
+// rows used by the title, the mail header and the help text around the body
+const RESERVED_ROWS: usize = 12;
+
+
+/**
+* Shows one mail, opening it from the inbox marks it read
+*/
+pub struct MailReadView {
+    mail_id: i32,
+    // opened from the sent folder
+    sent: bool,
+    user_id: i32,
+    mail: HashMap<String, String>,
+    navigate_to: NavigateTo,
+    // the first body line shown when the mail is longer than the screen
+    scroll: usize,
+    window: WindowSize,
+    // [D] has to be pressed twice
+    confirm_delete: bool,
+    error: Option<String>,
+}
+
+
+impl MailReadView {
+    pub fn new(mail_id: i32, sent: bool, user_id: i32, window: &WindowSize) -> Self {
+        let mut view = Self {
+            mail_id,
+            sent,
+            user_id,
+            mail: HashMap::new(),
+            navigate_to: NavigateTo::NoneView,
+            scroll: 0,
+            window: *window,
+            confirm_delete: false,
+            error: None,
+        };
+        view.refresh_data();
+        if !sent && view.error.is_none() {
+            if let Err(e) = Manager::mark_mail_read(mail_id, user_id) {
+                view.error = Some(e.report("marking mail read"));
+            }
+        }
+        view
+    }
+
+    fn field(&self, key: &str) -> &str {
+        self.mail.get(key).map(String::as_str).unwrap_or("")
+    }
+
+    fn body_lines(&self) -> Vec<String> {
+        let width = self.window.width.max(1);
+        self.field("body").split('\n').flat_map(|line| wrap_text(line, width, width)).collect()
+    }
+
+    fn back_to_mailbox(&mut self) -> Events {
+        self.navigate_to = NavigateTo::MailboxView { sent: self.sent };
+        Events::NavigateView
+    }
+}
+
+
+impl View for MailReadView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.field("subject")));
+        output.push_str(&render_error(&self.error));
+        if self.mail.is_empty() {
+            output.push_str("[CNTRL+Q] Back\r\n");
+            return output;
+        }
+
+        output.push_str(&format!("\x1b[1;33mFrom:\x1b[0m {}\r\n", self.field("sender")));
+        output.push_str(&format!("\x1b[1;33mTo:\x1b[0m {}\r\n", self.field("recipient")));
+        output.push_str(&format!("\x1b[1;33mDate:\x1b[0m {}\r\n", self.field("created_date")));
+        if self.sent {
+            let read_date = self.field("read_date");
+            let read = if read_date.is_empty() { "not yet" } else { read_date };
+            output.push_str(&format!("\x1b[1;33mRead:\x1b[0m {}\r\n", read));
+        }
+        output.push_str("\r\n");
+
+        let rows = window.rows_after(RESERVED_ROWS);
+        let lines = self.body_lines();
+        for line in lines.iter().skip(self.scroll).take(rows) {
+            output.push_str(&render_body_line(line));
+        }
+        if self.scroll + rows < lines.len() {
+            output.push_str("\x1b[2m... ↓ (Arrow Down) for more\x1b[0m\r\n");
+        }
+
+        if self.confirm_delete {
+            output.push_str("\n\x1b[1;31mPress [D] again to delete this mail.\x1b[0m\r\n");
+        } else {
+            output.push_str("\n↑ / ↓ scroll\r\n");
+        }
+        if !self.sent {
+            output.push_str("[R] Reply ");
+        }
+        output.push_str("[F] Forward [D] Delete\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_mail(self.mail_id, self.user_id) {
+            Ok(mail) => self.mail = mail,
+            Err(e) => self.error = Some(e.report("loading mail")),
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.window = *window;
+        self.scroll = 0;
+    }
+
+    fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
+        let confirming_delete = std::mem::take(&mut self.confirm_delete);
+        self.error = None;
+        if event == Events::CntrlQ {
+            return self.back_to_mailbox();
+        }
+        else if self.mail.is_empty() {
+            // the mail could not be loaded, there is nothing to act on
+        }
+        else if event == Events::UpArrow && self.scroll > 0 {
+            self.scroll -= 1;
+        }
+        else if event == Events::DownArrow && self.scroll + self.window.rows_after(RESERVED_ROWS) < self.body_lines().len() {
+            self.scroll += 1;
+        }
+        else if event == Events::KeyD && !confirming_delete {
+            self.confirm_delete = true;
+        }
+        else if event == Events::KeyD {
+            match Manager::delete_mail(self.mail_id, self.user_id) {
+                Ok(()) => return self.back_to_mailbox(),
+                Err(e) => self.error = Some(e.report("deleting mail")),
+            }
+        }
+        else if (event == Events::KeyR || event == Events::KeyF) && !permissions::can(self.user_id, Action::SendMail) {
+            self.error = Some(Action::SendMail.denied_message().to_string());
+        }
+        else if event == Events::KeyR && !self.sent && self.field("sender_username").is_empty() {
+            self.error = Some(String::from("The sender's account was deleted, there is no one to reply to."));
+        }
+        else if event == Events::KeyR && !self.sent {
+            self.navigate_to = NavigateTo::MailComposeView { mail_id: Some(self.mail_id), forward: false };
+            return Events::NavigateView;
+        }
+        else if event == Events::KeyF {
+            self.navigate_to = NavigateTo::MailComposeView { mail_id: Some(self.mail_id), forward: true };
+            return Events::NavigateView;
+        }
+        Events::Unknown
+    }
+}
@@ -0,0 +1,156 @@
+use std::any::Any;
+use crate::db::manage::{MailRow, Manager};
+use crate::input_interface::Events;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title and the help text below the mail list
+const RESERVED_ROWS: usize = 9;
+
+
+/**
+* The inbox, or the sent folder showing whether each mail was read
+*/
+pub struct MailboxView {
+    user_id: i32,
+    sent: bool,
+    navigate_to: NavigateTo,
+    mail: Vec<MailRow>,
+    selected_index: usize,
+    offset: i32,
+    page_size: i32,
+    error: Option<String>,
+}
+
+
+impl MailboxView {
+    pub fn new(user_id: i32, sent: bool, window: &WindowSize) -> Self {
+        let mut view = Self {
+            user_id,
+            sent,
+            navigate_to: NavigateTo::NoneView,
+            mail: Vec::new(),
+            selected_index: 0,
+            offset: 0,
+            page_size: window.page_size(RESERVED_ROWS),
+            error: None,
+        };
+        view.refresh_data();
+        view
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.mail.len() {
+            self.selected_index += 1;
+        }
+    }
+}
+
+
+impl View for MailboxView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, _window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        let folder = if self.sent { "Sent" } else { "Inbox" };
+        output.push_str(&format!("\x1b[1;32mMail - {}\x1b[0m\r\n\r\n", folder));
+        output.push_str(&render_error(&self.error));
+
+        if self.mail.is_empty() {
+            output.push_str(&format!("Nothing in {} yet.\r\n", folder.to_lowercase()));
+        }
+        for (idx, (_, other_user, subject, created_date, read_date)) in self.mail.iter().enumerate() {
+            let line = if self.sent {
+                let read = match read_date {
+                    Some(read_date) => format!("read {}", read_date),
+                    None => String::from("not read yet"),
+                };
+                format!("to {}  {}  [{}] {}", other_user, subject, created_date, read)
+            } else {
+                format!("{}  {}  [{}]", other_user, subject, created_date)
+            };
+            // unread mail in the inbox is marked
+            let marker = if !self.sent && read_date.is_none() { "\x1b[1;36m*\x1b[0m" } else { " " };
+            if idx == self.selected_index {
+                output.push_str(&format!("{} \x1b[1;33m> {}\x1b[0m\r\n", marker, line));
+            } else {
+                output.push_str(&format!("{}   {}\r\n", marker, line));
+            }
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to read, ← / → to change page\r\n");
+        let other_folder = if self.sent { "inbox" } else { "sent mail" };
+        output.push_str(&format!("[N] New mail\r\n[TAB] Show {}\r\n[CNTRL+Q] Back\r\n", other_folder));
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_mailbox(self.user_id, self.sent, self.offset, self.page_size) {
+            Ok(mail) => self.mail = mail,
+            Err(e) => self.error = Some(e.report("loading mail")),
+        }
+        if self.selected_index >= self.mail.len() {
+            self.selected_index = self.mail.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
+        self.error = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::MenuView;
+            return Events::NavigateView;
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow && self.mail.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Tab {
+            self.sent = !self.sent;
+            self.offset = 0;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Enter && !self.mail.is_empty() {
+            self.navigate_to = NavigateTo::MailReadView { mail_id: self.mail[self.selected_index].0, sent: self.sent };
+            return Events::NavigateView;
+        }
+        else if event == Events::KeyN && !permissions::can(self.user_id, Action::SendMail) {
+            self.error = Some(Action::SendMail.denied_message().to_string());
+        }
+        else if event == Events::KeyN {
+            self.navigate_to = NavigateTo::MailComposeView { mail_id: None, forward: false };
+            return Events::NavigateView;
+        }
+        Events::Unknown
+    }
+}
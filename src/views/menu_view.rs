@@ -1,23 +1,63 @@
 use std::any::Any;
-use crate::views::base_view::{NavigateTo, View};
+use crate::config;
+use crate::db::error::DbResult;
+use crate::db::manage::Manager;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
 use crate::input_interface::Events;
 
 
 pub struct BBSMenu {
+    user_id: i32,
     options: Vec<&'static str>,
     selected_index: usize,
-    navigate_to: NavigateTo
+    navigate_to: NavigateTo,
+    // mail in the inbox not opened yet
+    unread_mail: i64,
+    // unread messages in the rooms the user has been in, and direct messages sent to them
+    unread_rooms: i64,
+    unread_messages: i64,
+    // why the session was sent back to the menu
+    error: Option<String>,
 }
 
 impl BBSMenu {
-    pub fn new() -> Self {
-        Self {
-            options: vec!["🚪 Rooms", "👥 People", "👨‍💻 Me", "❌ Quit"],
+    pub fn new(user_id: i32) -> Self {
+        let mut options = vec!["🚪 Rooms", "📋 Boards", "💬 Messages", "✉ Mail", "👥 People", "🔍 Search", "👨‍💻 Me", "❌ Quit"];
+        // only sysops see the admin screen
+        if permissions::can(user_id, Action::Administer) {
+            options.insert(7, "🛠 Admin");
+        }
+        let mut menu = Self {
+            user_id,
+            options,
             selected_index: 0,
-            navigate_to: NavigateTo::NoneView
+            navigate_to: NavigateTo::NoneView,
+            unread_mail: 0,
+            unread_rooms: 0,
+            unread_messages: 0,
+            error: None,
+        };
+        menu.refresh_data();
+        menu
+    }
+
+    /**
+    * the number of unread items shown next to a menu option, 0 for none
+    */
+    fn badge(&self, option: &str) -> i64 {
+        match option {
+            "🚪 Rooms" => self.unread_rooms,
+            "✉ Mail" => self.unread_mail,
+            "💬 Messages" => self.unread_messages,
+            _ => 0,
         }
     }
 
+    pub fn show_error(&mut self, message: &str) {
+        self.error = Some(message.to_string());
+    }
+
     fn move_up(&mut self) {
         if self.selected_index > 0 {
             self.selected_index -= 1;
@@ -38,7 +78,7 @@ impl BBSMenu {
 
 
 impl View for BBSMenu {
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
@@ -46,15 +86,31 @@ impl View for BBSMenu {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, _window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H"); // Clear screen + move cursor to top
-        output.push_str("\x1b[1;32mWelcome to Friendly Automations Rust BBS!\x1b[0m\r\n\r\n");
+        let banner = &config::get().banner;
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", banner.title));
+        if !banner.motd.is_empty() {
+            for line in banner.motd.lines() {
+                output.push_str(&format!("{}\r\n", line));
+            }
+            output.push_str("\r\n");
+        }
+        output.push_str(&render_error(&self.error));
+        if self.unread_mail > 0 {
+            let plural = if self.unread_mail == 1 { "" } else { "s" };
+            output.push_str(&format!("\x1b[1;36mYou have {} new message{}.\x1b[0m\r\n\r\n", self.unread_mail, plural));
+        }
 
         for (idx, option) in self.options.iter().enumerate() {
+            let badge = match self.badge(option) {
+                0 => String::new(),
+                unread => format!(" ({} unread)", unread),
+            };
             if idx == self.selected_index {
-                output.push_str(&format!("\x1b[1;33m> {} \x1b[0m\r\n", option)); // Highlighted selection
+                output.push_str(&format!("\x1b[1;33m> {}{} \x1b[0m\r\n", option, badge)); // Highlighted selection
             } else {
-                output.push_str(&format!("  {}\r\n", option));
+                output.push_str(&format!("  {}{}\r\n", option, badge));
             }
         }
 
@@ -62,10 +118,20 @@ impl View for BBSMenu {
         output
     }
 
-    fn refresh_data(&mut self) {}
+    fn refresh_data(&mut self) {
+        // a count that can't be loaded is left out rather than blocking the menu
+        let count = |result: DbResult<i64>| result.unwrap_or_else(|e| {
+            e.report("counting unread messages");
+            0
+        });
+        self.unread_mail = count(Manager::count_unread_mail(self.user_id));
+        self.unread_rooms = count(Manager::get_room_unread_counts(self.user_id).map(|counts| counts.values().sum()));
+        self.unread_messages = count(Manager::get_dm_unread_counts(self.user_id).map(|counts| counts.values().sum()));
+    }
 
     fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
         let result_event: Events;
+        self.error = None;
 
         if event == Events::UpArrow {
             self.move_up();
@@ -82,12 +148,27 @@ impl View for BBSMenu {
         } else if  selection == "🚪 Rooms" {
             self.navigate_to = NavigateTo::RoomsView;
             result_event = Events::NavigateView;
+        } else if  selection == "📋 Boards" {
+            self.navigate_to = NavigateTo::BoardsView;
+            result_event = Events::NavigateView;
+        } else if  selection == "💬 Messages" {
+            self.navigate_to = NavigateTo::ConversationsView;
+            result_event = Events::NavigateView;
+        } else if  selection == "✉ Mail" {
+            self.navigate_to = NavigateTo::MailboxView { sent: false };
+            result_event = Events::NavigateView;
         } else if  selection == "👥 People" {
             self.navigate_to = NavigateTo::PeopleView;
             result_event = Events::NavigateView;
+        } else if  selection == "🔍 Search" {
+            self.navigate_to = NavigateTo::SearchView;
+            result_event = Events::NavigateView;
         } else if  selection == "👨‍💻 Me" {
             self.navigate_to = NavigateTo::MeView;
             result_event = Events::NavigateView;
+        } else if  selection == "🛠 Admin" {
+            self.navigate_to = NavigateTo::AdminView;
+            result_event = Events::NavigateView;
         } else {
             result_event = Events::Unknown;
         }
@@ -5,4 +5,15 @@ pub mod login_register_view;
 pub mod users_view;
 pub mod user_view;
 pub mod room_view;
-pub mod direct_message_view;
\ No newline at end of file
+pub mod direct_message_view;
+pub mod account_view;
+pub mod admin_view;
+pub mod boards_view;
+pub mod threads_view;
+pub mod thread_view;
+pub mod compose_view;
+pub mod mailbox_view;
+pub mod mail_read_view;
+pub mod mail_compose_view;
+pub mod conversations_view;
+pub mod search_view;
@@ -1,82 +1,417 @@
 use std::any::Any;
+use crate::db::error::DbError;
 use crate::db::manage::Manager;
+use crate::presence;
+use crate::permissions::{self, Action};
 use crate::input_interface::Events;
-use crate::views::base_view::{NavigateTo, View};
+use crate::views::base_view::{render_error, render_messages, screen_rows, wrap_text, NavigateTo, View, WindowSize};
+
+// rows used by the title, the who's here line and the message prompt when nothing else is shown,
+// render fits the messages to what it actually draws around them
+const RESERVED_ROWS: usize = 5;
+
+// longest mute a moderator can give, one week
+const MAX_MUTE_MINUTES: u32 = 7 * 24 * 60;
 
 
 pub struct RoomView{
     query_offset: i32,
+    page_size: i32,
     user_id: i32,
     room_id: i32,
     room_name: String,
     navigate_to: NavigateTo,
     message: String,
     messages: Vec<(i32, String, String, String)>,
-    sending_message: bool
+    // ids of the messages, in the same order
+    message_ids: Vec<i32>,
+    // names of the users in the room right now
+    here: Vec<String>,
+    // the user can delete messages and kick users here
+    can_moderate: bool,
+    // [TAB] switches from typing to picking a message to moderate
+    moderating: bool,
+    selected_message: usize,
+    // the author being muted while the moderator types the minutes
+    muting: Option<(i32, String)>,
+    // the user to send back to the room list and the message they see
+    user_to_kick: Option<(i32, String)>,
+    error: Option<String>,
+    // confirms the last moderation action
+    notice: Option<String>,
+    // the message a search result opened the room at, highlighted while it is on screen
+    found_message: Option<i32>,
 }
 
 impl RoomView {
-    pub fn new(room_id: i32, room_name: String, user_id: i32) -> Self {
-        let mut messages = Manager::get_message_from_room(room_id, 0);
-        messages.reverse();
-        Self {
+    pub fn new(room_id: i32, user_id: i32, window: &WindowSize) -> Self {
+        let page_size = window.page_size(RESERVED_ROWS);
+        let (room_name, error) = match Manager::get_room_name_by_id(room_id) {
+            Ok(room_name) => (room_name, None),
+            Err(e) => ("Room".to_string(), Some(e.report("loading a room"))),
+        };
+        let mut view = Self {
             user_id,
             room_id,
             navigate_to: NavigateTo::NoneView,
-            messages,
+            messages: Vec::new(),
+            message_ids: Vec::new(),
+            here: presence::users_in_room(room_id),
+            can_moderate: permissions::can(user_id, Action::ModerateRoom { room_id }),
+            moderating: false,
+            selected_message: 0,
+            muting: None,
+            user_to_kick: None,
             room_name,
-            sending_message: false,
             message: String::new(),
-            query_offset: 0
+            query_offset: 0,
+            page_size,
+            error,
+            notice: None,
+            found_message: None,
+        };
+        if view.error.is_none() {
+            view.refresh_data();
         }
+        view
     }
 
     fn get_user_id(&self) -> i32 {
         self.user_id
     }
 
+    /**
+    * checks the moderator rights again, a sysop or the room owner may have changed them while
+    * the user is in the room
+    */
+    fn check_can_moderate(&mut self) -> bool {
+        self.can_moderate = permissions::can(self.user_id, Action::ModerateRoom { room_id: self.room_id });
+        self.can_moderate
+    }
+
+    /**
+    * the who's here line under the room title, cut to one line of the terminal
+    */
+    fn render_here(&self, window: &WindowSize) -> String {
+        let label = format!("Here ({}): ", self.here.len());
+        // leave room for the ellipsis when the names don't fit
+        let width = window.width.saturating_sub(label.len() + 2);
+        let lines = wrap_text(&self.here.join(", "), width, width);
+        let mut names = lines.first().cloned().unwrap_or_default();
+        if lines.len() > 1 {
+            names.push_str(" …");
+        }
+        format!("\x1b[1;36m{}\x1b[0m{}\r\n", label, names)
+    }
+
+    /**
+    * pages back so `message_id` is the last message on screen, ↓ (Arrow Down) shows what came after it
+    */
+    pub fn show_message(&mut self, message_id: i32) {
+        match Manager::count_newer_room_messages(self.room_id, message_id) {
+            Ok(newer) => {
+                self.query_offset = newer;
+                self.found_message = Some(message_id);
+                self.refresh_data();
+                if self.query_offset > 0 {
+                    self.notice = Some("↓ (Arrow Down) for newer messages.".to_string());
+                }
+            }
+            Err(e) => self.error = Some(e.report("finding a message")),
+        }
+    }
+
+    /**
+    * the user a kick or ban asked to remove from the room, with the message they are shown
+    */
+    pub fn get_user_to_kick(&self) -> Option<(i32, String)> {
+        self.user_to_kick.clone()
+    }
+
+    /**
+    * the author of the selected message if the moderator may act on them. Room moderators,
+    * the owner and sysops can't be kicked, banned or muted
+    */
+    fn selected_author(&mut self) -> Option<(i32, String)> {
+        let (author_id, _, username, _) = self.messages.get(self.selected_message)?.clone();
+        if author_id == self.user_id {
+            self.error = Some("You can't moderate yourself.".to_string());
+            return None;
+        }
+        if matches!(Manager::get_user_role(author_id), Err(DbError::NotFound(_))) {
+            self.error = Some("That user no longer exists.".to_string());
+            return None;
+        }
+        if permissions::can(author_id, Action::ModerateRoom { room_id: self.room_id }) {
+            self.error = Some(format!("{} moderates this room.", username));
+            return None;
+        }
+        Some((author_id, username))
+    }
+
+    fn delete_selected_message(&mut self) -> Events {
+        let Some(message_id) = self.message_ids.get(self.selected_message).copied() else {
+            return Events::Unknown;
+        };
+        match Manager::delete_room_message(self.room_id, message_id) {
+            Ok(()) => {
+                println!("User {} deleted message {} in room {}", self.user_id, message_id, self.room_id);
+                self.notice = Some("Message deleted.".to_string());
+                self.refresh_data();
+                // the other sessions in the room reload their messages
+                Events::RoomMessageSent
+            }
+            Err(e) => {
+                self.error = Some(e.report("deleting a message"));
+                Events::Unknown
+            }
+        }
+    }
+
+    fn kick_selected_author(&mut self) -> Events {
+        let Some((author_id, username)) = self.selected_author() else {
+            return Events::Unknown;
+        };
+        let in_room = presence::sessions_for_user(author_id).iter().any(|session| session.room_id == Some(self.room_id));
+        if !in_room {
+            self.notice = Some(format!("{} is not in the room.", username));
+            return Events::Unknown;
+        }
+        println!("User {} kicked user {} from room {}", self.user_id, author_id, self.room_id);
+        self.notice = Some(format!("Kicked {}.", username));
+        self.user_to_kick = Some((author_id, format!("You were kicked from {}.", self.room_name)));
+        Events::KickUser
+    }
+
+    /**
+    * bans the author of the selected message and kicks them, or lifts their ban
+    */
+    fn toggle_ban_selected_author(&mut self) -> Events {
+        let Some((author_id, username)) = self.selected_author() else {
+            return Events::Unknown;
+        };
+        let result = Manager::is_banned_from_room(self.room_id, author_id).and_then(|banned| {
+            Manager::set_room_ban(self.room_id, author_id, self.user_id, !banned)?;
+            Ok(!banned)
+        });
+        match result {
+            Ok(true) => {
+                println!("User {} banned user {} from room {}", self.user_id, author_id, self.room_id);
+                self.notice = Some(format!("{} is banned from {}.", username, self.room_name));
+                self.user_to_kick = Some((author_id, format!("You were banned from {}.", self.room_name)));
+                Events::KickUser
+            }
+            Ok(false) => {
+                self.notice = Some(format!("{} is no longer banned from {}.", username, self.room_name));
+                Events::Unknown
+            }
+            Err(e) => {
+                self.error = Some(e.report("banning a user from a room"));
+                Events::Unknown
+            }
+        }
+    }
 
+    /**
+    * lifts the mute of the selected author, or asks for how long to mute them
+    */
+    fn toggle_mute_selected_author(&mut self) -> Events {
+        let Some((author_id, username)) = self.selected_author() else {
+            return Events::Unknown;
+        };
+        match Manager::get_mute_seconds_left(self.room_id, author_id) {
+            Ok(Some(_)) => {
+                match Manager::set_room_mute(self.room_id, author_id, self.user_id, None) {
+                    Ok(()) => self.notice = Some(format!("{} is no longer muted.", username)),
+                    Err(e) => self.error = Some(e.report("unmuting a user")),
+                }
+                Events::Unknown
+            }
+            Ok(None) => {
+                self.muting = Some((author_id, username));
+                Events::InputModeEnable
+            }
+            Err(e) => {
+                self.error = Some(e.report("muting a user"));
+                Events::Unknown
+            }
+        }
+    }
 
+    fn submit_mute(&mut self, input: &str) -> Events {
+        self.message.clear();
+        let Some((author_id, username)) = self.muting.take() else {
+            return Events::InputModeDisable;
+        };
+        let minutes = match input.trim().parse::<u32>() {
+            Ok(minutes) if (1..=MAX_MUTE_MINUTES).contains(&minutes) => minutes,
+            _ => {
+                self.error = Some(format!("Enter a number of minutes from 1 to {}.", MAX_MUTE_MINUTES));
+                return Events::InputModeDisable;
+            }
+        };
+        match Manager::set_room_mute(self.room_id, author_id, self.user_id, Some(minutes)) {
+            Ok(()) => {
+                println!("User {} muted user {} in room {} for {} minutes", self.user_id, author_id, self.room_id, minutes);
+                self.notice = Some(format!("{} is muted for {} minutes.", username, minutes));
+            }
+            Err(e) => self.error = Some(e.report("muting a user")),
+        }
+        Events::InputModeDisable
+    }
+
+    /**
+    * the keys of moderation mode, the client is out of input mode except while typing mute minutes
+    */
+    fn handle_moderation_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if self.muting.is_some() {
+            if event == Events::CntrlQ {
+                self.muting = None;
+                self.message.clear();
+                return Events::InputModeDisable;
+            }
+            else if event == Events::Enter {
+                return self.submit_mute(&buffer_string);
+            }
+            self.message = buffer_string;
+            return Events::Unknown;
+        }
+
+        self.error = None;
+        self.notice = None;
+        self.user_to_kick = None;
+        if event == Events::Tab || event == Events::CntrlQ {
+            self.moderating = false;
+            self.message.clear();
+            return Events::InputModeEnable;
+        }
+        else if [Events::KeyD, Events::KeyK, Events::KeyB, Events::KeyM].contains(&event) && !self.check_can_moderate() {
+            self.moderating = false;
+            self.error = Some(Action::ModerateRoom { room_id: self.room_id }.denied_message().to_string());
+            return Events::InputModeEnable;
+        }
+        else if event == Events::UpArrow && self.selected_message > 0 {
+            self.selected_message -= 1;
+        }
+        else if event == Events::DownArrow && self.selected_message + 1 < self.messages.len() {
+            self.selected_message += 1;
+        }
+        else if event == Events::KeyD {
+            return self.delete_selected_message();
+        }
+        else if event == Events::KeyK {
+            return self.kick_selected_author();
+        }
+        else if event == Events::KeyB {
+            return self.toggle_ban_selected_author();
+        }
+        else if event == Events::KeyM {
+            return self.toggle_mute_selected_author();
+        }
+        Events::Unknown
+    }
+
+    fn render_prompt(&self) -> String {
+        if let Some((_, username)) = &self.muting {
+            return format!("\n\x1b[1;33mMute {} for how many minutes (CNTRL+Q to cancel):\x1b[0m {}", username, self.message);
+        }
+        if self.moderating {
+            return "\n\x1b[1;33m[D] Delete message  [K] Kick  [B] Ban / unban  [M] Mute / unmute  [TAB] Back to chat\x1b[0m".to_string();
+        }
+        format!("\n\x1b[1;35m>>>\x1b[0m {}", self.message)
+    }
 }
 
 impl View for RoomView {
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
     fn get_navigate_to(&self) -> &NavigateTo {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H");
-        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.room_name));
+        let moderator = if self.can_moderate { " \x1b[1;33m(moderator, [TAB] moderate)\x1b[0m" } else { "" };
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m{}\r\n", self.room_name, moderator));
+        output.push_str(&self.render_here(window));
+        output.push_str("\r\n");
+        output.push_str(&render_error(&self.error));
+        if let Some(notice) = &self.notice {
+            output.push_str(&format!("\x1b[1;33m{}\x1b[0m\r\n\r\n", notice));
+        }
 
+        let selected = if self.moderating {
+            Some(self.selected_message)
+        } else {
+            self.found_message.and_then(|found| self.message_ids.iter().position(|message_id| *message_id == found))
+        };
+        // errors, notices and a prompt that wraps leave fewer rows for the messages
+        let prompt = self.render_prompt();
+        let reserved = screen_rows(&output, window.width) + screen_rows(&prompt, window.width);
+        output.push_str(&render_messages(&self.messages, self.user_id, window, window.rows_after(reserved), selected));
+        output.push_str(&prompt);
+        output
+    }
 
-        // Append sorted rooms to output
-        for (user_id, created_date, user, message) in self.messages.iter() {
-            if *user_id == self.user_id {
-                output.push_str(&format!("\x1b[1;32m[{}]\x1b[0m \x1b[1;35m{}\x1b[0m  {}\r\n", created_date, user, message));
+    fn refresh_data(&mut self) {
+        self.here = presence::users_in_room(self.room_id);
+        self.check_can_moderate();
+        // a sysop may have renamed the room
+        if let Ok(room_name) = Manager::get_room_name_by_id(self.room_id) {
+            self.room_name = room_name;
+        }
+        match Manager::get_message_from_room(self.room_id, self.query_offset, self.page_size) {
+            Ok(mut messages) => {
+                messages.reverse();
+                self.message_ids = messages.iter().map(|(message_id, ..)| *message_id).collect();
+                self.messages = messages
+                    .into_iter()
+                    .map(|(_, author_id, created_date, username, message)| (author_id, created_date, username, message))
+                    .collect();
+                self.error = None;
+                if self.selected_message >= self.messages.len() {
+                    self.selected_message = self.messages.len().saturating_sub(1);
+                }
             }
-            else {
-                output.push_str(&format!("\x1b[1;32m[{}]\x1b[0m \x1b[38;5;214m{}\x1b[0m  {}\r\n", created_date, user, message));
+            Err(e) => self.error = Some(e.report("loading room messages")),
+        }
+        // everything posted so far has been on screen, unless the user paged back through history
+        if self.query_offset == 0 {
+            if let Err(e) = Manager::mark_room_read(self.user_id, self.room_id) {
+                self.error = Some(e.report("marking the room read"));
             }
         }
-        output.push_str("\n\x1b[1;35m>>>\x1b[0m ");
-        output.push_str(self.message.as_str());
-        output
     }
 
-    fn refresh_data(&mut self) {
-        self.messages = Manager::get_message_from_room(self.room_id, self.query_offset);
-        self.messages.reverse();
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn scrolls_with_arrows(&self) -> bool {
+        true
     }
 
 
     fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
         let mut result_event: Events = Events::Unknown;
 
-        if event == Events::UpArrow  && self.messages.len() == 20 {
+        if self.moderating {
+            return self.handle_moderation_event(event, buffer_string);
+        }
+
+        else if event == Events::Tab && self.check_can_moderate() {
+            self.moderating = true;
+            self.selected_message = self.messages.len().saturating_sub(1);
+            self.message.clear();
+            self.error = None;
+            self.notice = None;
+            result_event = Events::InputModeDisable;
+        }
+
+        else if event == Events::UpArrow  && self.messages.len() == self.page_size as usize {
             self.query_offset += 1;
             self.refresh_data();
         }
@@ -84,13 +419,26 @@ impl View for RoomView {
         else if event == Events::DownArrow && self.query_offset != 0 {
             self.query_offset -= 1;
             self.refresh_data();
+            if self.query_offset == 0 {
+                self.notice = None;
+            }
         }
 
         else if event == Events::Enter {
-            if self.message != "" {
-                Manager::post_message(self.room_id, self.message.clone(), self.get_user_id());
-                result_event = Events::RoomMessageSent;
-                self.message.clear();
+            let post = Action::PostMessage { room_id: self.room_id };
+            if !self.message.is_empty() && !permissions::can(self.user_id, post) {
+                self.error = Some(post.denied_message().to_string());
+            }
+            else if !self.message.is_empty() {
+                // the message is kept in the prompt if it could not be saved
+                match Manager::post_message(self.room_id, self.message.clone(), self.get_user_id()) {
+                    Ok(()) => {
+                        result_event = Events::RoomMessageSent;
+                        self.message.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e.report("posting a room message")),
+                }
             }
             else {
                 result_event = Events::Unknown;
@@ -1,35 +1,77 @@
 use std::any::Any;
-use crate::views::base_view::{NavigateTo, View};
+use std::collections::HashMap;
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
 use crate::input_interface::Events;
 use crate::db::manage::Manager;
+use crate::presence;
+use crate::permissions::{self, Action, RoomRole};
 use std::str;
 use crate::input_interface::Events::Unknown;
 
+// rows used by the title and the help text below the room list
+const RESERVED_ROWS: usize = 9;
+
 pub struct RoomsView {
     input_mode: bool,
     input: String,
-    rooms: Vec<(String, u32)>,
+    // the rooms are filtered by this name while it is not empty
+    search: String,
+    rooms: Vec<(i32, String)>,
+    // users in each room, from the presence registry
+    online: HashMap<i32, usize>,
+    // unread messages in the rooms the user has been in
+    unread: HashMap<i32, i64>,
+    offset: i32,
+    page_size: i32,
     user_id: i32,
+    selected_room_id: i32,
+    error: Option<String>,
+    // confirms a moderator was added or removed, or says why the user was sent back here
+    notice: Option<String>,
     selected_index: usize,
     navigate_to: NavigateTo,
     selecting_room: bool,
     searching_room: bool,
-    creating_room: bool
+    creating_room: bool,
+    // the room whose moderators are being changed
+    managing_room: Option<(i32, String)>,
 }
 
 impl RoomsView {
-    pub fn new(user_id: i32) -> Self {
-        let rooms = Manager::get_rooms(0);
-        Self {
+    pub fn new(user_id: i32, window: &WindowSize) -> Self {
+        let page_size = window.page_size(RESERVED_ROWS);
+        let (rooms, error) = match Manager::get_rooms(0, page_size) {
+            Ok(rooms) => (rooms, None),
+            Err(e) => (Vec::new(), Some(e.report("loading rooms"))),
+        };
+        let mut view = Self {
             input_mode: false,
             input: String::new(),
+            search: String::new(),
             rooms,
+            online: presence::room_counts(),
+            unread: HashMap::new(),
+            offset: 0,
+            page_size,
             selected_index: 0,
             user_id,
+            selected_room_id: -1,
+            error,
+            notice: None,
             navigate_to: NavigateTo::NoneView,
             selecting_room: true,
             searching_room: false,
-            creating_room: false
+            creating_room: false,
+            managing_room: None,
+        };
+        view.load_unread();
+        view
+    }
+
+    fn load_unread(&mut self) {
+        match Manager::get_room_unread_counts(self.user_id) {
+            Ok(unread) => self.unread = unread,
+            Err(e) => self.error = Some(e.report("counting unread messages")),
         }
     }
 
@@ -38,6 +80,8 @@ impl RoomsView {
             self.selecting_room = true;
             self.searching_room = false;
             self.creating_room = false;
+            self.creating_room = false;
+            self.managing_room = None;
             self.input_mode = false;
         }
         else if state == "searching_room" {
@@ -54,6 +98,14 @@ impl RoomsView {
             self.input_mode = false;
             self.input = String::new();
         }
+        else if state == "managing_moderators" {
+            self.managing_room = self.rooms.get(self.selected_index).cloned();
+            self.creating_room = false;
+            self.searching_room = false;
+            self.selecting_room = false;
+            self.input_mode = false;
+            self.input = String::new();
+        }
 
     }
 
@@ -72,8 +124,38 @@ impl RoomsView {
         }
     }
 
-    pub fn get_selection(&self) -> &str {
-        &self.rooms[self.selected_index].0
+    /**
+    * makes the named user a moderator of the room being managed, or removes them if they already are one
+    */
+    fn toggle_moderator(&mut self, room_id: i32, room_name: &str) {
+        let username = self.input.trim().to_string();
+        let result = Manager::get_user_id_by_name(&username).and_then(|user_id| {
+            let role = Manager::get_room_role(room_id, user_id)?;
+            let new_role = match role {
+                Some(RoomRole::Owner) => return Ok(None),
+                Some(RoomRole::Moderator) => None,
+                None => Some(RoomRole::Moderator),
+            };
+            Manager::set_room_role(room_id, user_id, new_role)?;
+            Ok(Some(new_role.is_some()))
+        });
+        match result {
+            Ok(Some(true)) => self.notice = Some(format!("{} is now a moderator of {}.", username, room_name)),
+            Ok(Some(false)) => self.notice = Some(format!("{} is no longer a moderator of {}.", username, room_name)),
+            Ok(None) => self.error = Some(format!("{} owns {}.", username, room_name)),
+            Err(e) => self.error = Some(e.report("changing room moderators")),
+        }
+    }
+
+    pub fn show_notice(&mut self, message: &str) {
+        self.notice = Some(message.to_string());
+    }
+
+    /**
+    * the id of the room picked with Enter
+    */
+    pub fn get_selected_room_id(&self) -> i32 {
+        self.selected_room_id
     }
 
 
@@ -82,7 +164,7 @@ impl RoomsView {
 impl View for RoomsView {
 
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
@@ -90,9 +172,17 @@ impl View for RoomsView {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, _window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H");
-        output.push_str("\x1b[1;32mRooms\x1b[0m\r\n\r\n");
+        if self.search.is_empty() {
+            output.push_str("\x1b[1;32mRooms\x1b[0m\r\n\r\n");
+        } else {
+            output.push_str(&format!("\x1b[1;32mRooms matching {}\x1b[0m\r\n\r\n", self.search));
+        }
+        output.push_str(&render_error(&self.error));
+        if let Some(notice) = &self.notice {
+            output.push_str(&format!("\x1b[1;33m{}\x1b[0m\r\n\r\n", notice));
+        }
 
 
         if self.searching_room {
@@ -104,31 +194,61 @@ impl View for RoomsView {
             output.push_str("\x1b[1;32m> Create Room (CNTRL+Q to exit): ");
             output.push_str(self.input.as_str());
         }
+        else if let Some((_, room_name)) = &self.managing_room {
+            output.push_str(&format!("\x1b[1;32m> Add or remove a moderator of {} (CNTRL+Q to exit): ", room_name));
+            output.push_str(self.input.as_str());
+        }
         else if self.selecting_room {
             // Append sorted rooms to output
-            for (index, (room, count)) in self.rooms.iter().enumerate() {
+            for (index, (room_id, room)) in self.rooms.iter().enumerate() {
+                let count = self.online.get(room_id).copied().unwrap_or(0);
+                let unread = match self.unread.get(room_id) {
+                    Some(unread) => format!(", {} unread", unread),
+                    None => String::new(),
+                };
                 if index == self.selected_index && self.selecting_room {
-                    output.push_str(&format!("\x1b[1;33m> {}: {} online\x1b[0m\r\n", room, count));
+                    output.push_str(&format!("\x1b[1;33m> {}: {} online{}\x1b[0m\r\n", room, count, unread));
                 }
                 else {
-                    output.push_str(&format!("  {}: {} online\r\n", room, count));
+                    output.push_str(&format!("  {}: {} online{}\r\n", room, count, unread));
                 }
             }
-            output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to select a room\r\n[S] Search for a room.\r\n[C] Create Room.\r\n[N] Next Page\r\n[H / CNTRL+Q] Home\r\n");
+            output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to select a room, ← / → to change page\r\n[S] Search for a room.\r\n[C] Create Room.\r\n[M] Room moderators.\r\n[H / CNTRL+Q] Home\r\n");
         }
         output
     }
 
     fn refresh_data(&mut self) {
-        if self.searching_room {
-            self.rooms = Manager::search_rooms(self.input.to_string(), 0);
+        let rooms = if !self.search.is_empty() {
+            Manager::search_rooms(self.search.clone(), self.offset, self.page_size)
         }
 
         else {
-            self.rooms = Manager::get_rooms(0);
+            Manager::get_rooms(self.offset, self.page_size)
+        };
+        self.online = presence::room_counts();
+        match rooms {
+            Ok(rooms) => {
+                self.rooms = rooms;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.report("loading rooms")),
+        }
+        self.load_unread();
+        if self.selected_index >= self.rooms.len() {
+            self.selected_index = self.rooms.len().saturating_sub(1);
         }
     }
 
+    fn follows_presence(&self) -> bool {
+        true
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
     fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
         let mut result_event: Events = Unknown;
 
@@ -138,6 +258,12 @@ impl View for RoomsView {
         }
 
         else if event == Events::CntrlQ && self.input_mode {
+            // leaving the search prompt shows all rooms again
+            if self.searching_room && !self.search.is_empty() {
+                self.search.clear();
+                self.offset = 0;
+                self.refresh_data();
+            }
             self.set_context_state("selecting_room");
             result_event = Events::InputModeDisable
         }
@@ -151,22 +277,53 @@ impl View for RoomsView {
             self.move_down();
         }
 
-        else if event == Events::Enter && !self.input_mode && self.rooms.len() > 0 {
+        else if event == Events::RightArrow && !self.input_mode && self.rooms.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+
+        else if event == Events::LeftArrow && !self.input_mode && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+
+        else if event == Events::Enter && !self.input_mode && !self.rooms.is_empty() {
+            self.selected_room_id = self.rooms[self.selected_index].0;
             self.navigate_to = NavigateTo::RoomView;
             result_event = Events::RoomJoin;
         }
 
-        else if event == Events::KeyH && !(self.creating_room  || self.searching_room) {
+        else if event == Events::KeyH && !self.input_mode {
             self.navigate_to = NavigateTo::MenuView;
             result_event = Events::NavigateView;
         }
 
+        else if event == Events::KeyC && !self.creating_room && !self.input_mode && !permissions::can(self.user_id, Action::CreateRoom) {
+            self.error = Some(Action::CreateRoom.denied_message().to_string());
+        }
+
         else if event == Events::KeyC && !self.creating_room && !self.input_mode{
+            self.notice = None;
             self.set_context_state("creating_room");
             self.input_mode = true;
             result_event = Events::InputModeEnable;
         }
 
+        else if event == Events::KeyM && !self.input_mode && !self.rooms.is_empty() {
+            let action = Action::ManageModerators { room_id: self.rooms[self.selected_index].0 };
+            self.notice = None;
+            if permissions::can(self.user_id, action) {
+                self.set_context_state("managing_moderators");
+                self.input_mode = true;
+                result_event = Events::InputModeEnable;
+            }
+            else {
+                self.error = Some(action.denied_message().to_string());
+            }
+        }
+
         else if event == Events::KeyS && !self.searching_room && !self.input_mode {
             self.set_context_state("searching_room");
             self.input_mode = true;
@@ -179,13 +336,28 @@ impl View for RoomsView {
         }
 
         else if self.input_mode && event == Events::Enter && self.creating_room && self.input.trim() != ""{
-            Manager::create_room(self.input.to_string(), self.user_id.to_string());
+            let created = Manager::create_room(self.input.to_string(), self.user_id);
             self.refresh_data();
+            if let Err(e) = created {
+                self.error = Some(e.report("creating a room"));
+            }
             self.set_context_state("selecting_room");
             result_event =  Events::InputModeDisable;
         }
 
+        else if self.input_mode && event == Events::Enter && self.input.trim() != "" && self.managing_room.is_some() {
+            if let Some((room_id, room_name)) = self.managing_room.clone() {
+                self.error = None;
+                self.toggle_moderator(room_id, &room_name);
+            }
+            self.set_context_state("selecting_room");
+            result_event = Events::InputModeDisable;
+        }
+
         else if self.input_mode && event == Events::Enter && self.searching_room && self.input.trim() != "" {
+            self.search = self.input.trim().to_string();
+            self.offset = 0;
+            self.selected_index = 0;
             self.refresh_data();
             self.set_context_state("selecting_room");
             result_event =  Events::InputModeDisable;
@@ -0,0 +1,235 @@
+use std::any::Any;
+use crate::db::manage::{Manager, SearchRow, MATCH_END, MATCH_START};
+use crate::input_interface::Events;
+use crate::views::base_view::{render_error, wrap_text, NavigateTo, View, WindowSize};
+
+// rows used by the title and the help text below the results
+const RESERVED_ROWS: usize = 9;
+
+
+/**
+* Full-text search over room history and the user's direct messages. Opening a room result
+* enters the room at that message, a direct message opens the conversation
+*/
+pub struct SearchView {
+    user_id: i32,
+    navigate_to: NavigateTo,
+    // the prompt is shown instead of the results while a search is typed
+    typing: bool,
+    input: String,
+    // the words the results were found for
+    query: String,
+    results: Vec<SearchRow>,
+    selected_index: usize,
+    // the room or user and the message picked with Enter
+    selected_result: (i32, i32),
+    offset: i32,
+    page_size: i32,
+    error: Option<String>,
+}
+
+
+impl SearchView {
+    pub fn new(user_id: i32, window: &WindowSize) -> Self {
+        Self {
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            typing: true,
+            input: String::new(),
+            query: String::new(),
+            results: Vec::new(),
+            selected_index: 0,
+            selected_result: (-1, -1),
+            offset: 0,
+            page_size: Self::results_per_page(window),
+            error: None,
+        }
+    }
+
+    // every result takes two rows
+    fn results_per_page(window: &WindowSize) -> i32 {
+        (window.page_size(RESERVED_ROWS) / 2).max(1)
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.results.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    /**
+    * the room whose result was picked with Enter
+    */
+    pub fn get_selected_room_id(&self) -> i32 {
+        self.selected_result.0
+    }
+
+    /**
+    * the user whose direct message was picked with Enter
+    */
+    pub fn get_selected_user_id(&self) -> i32 {
+        self.selected_result.0
+    }
+
+    /**
+    * the message picked with Enter
+    */
+    pub fn get_selected_message_id(&self) -> i32 {
+        self.selected_result.1
+    }
+
+    fn submit_query(&mut self) -> Events {
+        let query = self.input.trim().to_string();
+        if query.is_empty() {
+            return Events::Unknown;
+        }
+        self.query = query;
+        self.input.clear();
+        self.typing = false;
+        self.offset = 0;
+        self.selected_index = 0;
+        self.refresh_data();
+        Events::InputModeDisable
+    }
+
+    fn open_selected(&mut self) -> Events {
+        let (message_id, direct, target_id, ..) = self.results[self.selected_index];
+        self.selected_result = (target_id, message_id);
+        if direct {
+            self.navigate_to = NavigateTo::DirectMessageView;
+            return Events::NavigateView;
+        }
+        self.navigate_to = NavigateTo::RoomView;
+        Events::RoomJoin
+    }
+}
+
+
+/**
+* the first line of a snippet with the matched words highlighted
+*/
+fn render_snippet(snippet: &str, width: usize) -> String {
+    let lines = wrap_text(&snippet.replace('\n', " "), width, width);
+    let mut line = lines.first().cloned().unwrap_or_default();
+    if lines.len() > 1 {
+        line.push('…');
+    }
+    // a highlight cut off at the end of the line is closed by the reset
+    format!("{}\x1b[0m", line.replace(MATCH_START, "\x1b[1;33m").replace(MATCH_END, "\x1b[0m"))
+}
+
+
+impl View for SearchView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str("\x1b[1;32mSearch\x1b[0m\r\n\r\n");
+        output.push_str(&render_error(&self.error));
+
+        if self.typing {
+            output.push_str(&format!("\x1b[1;33m> Search messages (CNTRL+Q to cancel):\x1b[0m {}", self.input));
+            return output;
+        }
+
+        output.push_str(&format!("Results for \x1b[1m{}\x1b[0m\r\n\r\n", self.query));
+        if self.results.is_empty() {
+            output.push_str("Nothing found.\r\n");
+        }
+        for (idx, (_, direct, _, target_name, author, created_date, snippet)) in self.results.iter().enumerate() {
+            let place = if *direct { format!("@{}", target_name) } else { format!("#{}", target_name) };
+            let header = format!("{}  {}  [{}]", place, author, created_date);
+            if idx == self.selected_index {
+                output.push_str(&format!("\x1b[1;33m> {}\x1b[0m\r\n", header));
+            } else {
+                output.push_str(&format!("  {}\r\n", header));
+            }
+            output.push_str(&format!("    {}\r\n", render_snippet(snippet, window.width.saturating_sub(5).max(1))));
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to open a result, ← / → to change page\r\n[S] New search\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        if self.query.is_empty() {
+            return;
+        }
+        match Manager::search_messages(self.user_id, &self.query, self.offset, self.page_size) {
+            Ok(results) => self.results = results,
+            Err(e) => self.error = Some(e.report("searching messages")),
+        }
+        if self.selected_index >= self.results.len() {
+            self.selected_index = self.results.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = Self::results_per_page(window);
+        self.refresh_data();
+    }
+
+    fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
+        if self.typing {
+            if event == Events::CntrlQ && self.query.is_empty() {
+                self.navigate_to = NavigateTo::MenuView;
+                return Events::NavigateView;
+            }
+            else if event == Events::CntrlQ {
+                // back to the results of the last search
+                self.typing = false;
+                self.input.clear();
+                return Events::InputModeDisable;
+            }
+            else if event == Events::Enter {
+                self.error = None;
+                return self.submit_query();
+            }
+            self.input = buffer_string;
+            return Events::Unknown;
+        }
+
+        self.error = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::MenuView;
+            return Events::NavigateView;
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow && self.results.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::KeyS {
+            self.typing = true;
+            return Events::InputModeEnable;
+        }
+        else if event == Events::Enter && !self.results.is_empty() {
+            return self.open_selected();
+        }
+        Events::Unknown
+    }
+}
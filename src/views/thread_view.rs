@@ -0,0 +1,190 @@
+use std::any::Any;
+use crate::db::manage::{Manager, PostRow};
+use crate::input_interface::Events;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_body_line, render_error, wrap_text, NavigateTo, View, WindowSize};
+
+// rows used by the title, the post header and the help text around the post body
+const RESERVED_ROWS: usize = 10;
+
+
+/**
+* Reads a thread one post at a time, starting at the first post the user has not read
+*/
+pub struct ThreadView {
+    thread_id: i32,
+    board_id: i32,
+    subject: String,
+    user_id: i32,
+    navigate_to: NavigateTo,
+    posts: Vec<PostRow>,
+    current: usize,
+    // the newest post the read marker is at
+    last_read: i32,
+    // the first body line shown when the post is longer than the screen
+    scroll: usize,
+    window: WindowSize,
+    error: Option<String>,
+}
+
+
+impl ThreadView {
+    pub fn new(thread_id: i32, post_id: Option<i32>, user_id: i32, window: &WindowSize) -> Self {
+        let mut view = Self {
+            thread_id,
+            board_id: -1,
+            subject: String::new(),
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            posts: Vec::new(),
+            current: 0,
+            last_read: 0,
+            scroll: 0,
+            window: *window,
+            error: None,
+        };
+        match Manager::get_thread(thread_id) {
+            Ok((board_id, subject)) => {
+                view.board_id = board_id;
+                view.subject = subject;
+            }
+            Err(e) => view.error = Some(e.report("loading a thread")),
+        }
+        view.refresh_data();
+
+        match Manager::get_last_read_post(user_id, thread_id) {
+            Ok(last_read) => {
+                view.last_read = last_read;
+                let start = match post_id {
+                    Some(post_id) => view.posts.iter().position(|post| post.0 == post_id),
+                    None => view.posts.iter().position(|post| post.0 > last_read),
+                };
+                view.current = start.unwrap_or(0);
+            }
+            Err(e) => view.error = Some(e.report("loading a thread")),
+        }
+        view.mark_read();
+        view
+    }
+
+    /**
+    * remembers the current post as read when every post before it was read, a thread opened
+    * further on leaves the posts it skipped new
+    */
+    fn mark_read(&mut self) {
+        let Some(post_id) = self.posts.get(self.current).map(|post| post.0) else {
+            return;
+        };
+        let read_before = self.current == 0 || self.posts[self.current - 1].0 <= self.last_read;
+        if !read_before || post_id <= self.last_read {
+            return;
+        }
+        match Manager::mark_thread_read(self.user_id, self.thread_id, post_id) {
+            Ok(()) => self.last_read = post_id,
+            Err(e) => self.error = Some(e.report("marking a thread read")),
+        }
+    }
+
+    fn show_post(&mut self, index: usize) {
+        self.current = index;
+        self.scroll = 0;
+        self.mark_read();
+    }
+
+    /**
+    * the body of the current post wrapped to the window width
+    */
+    fn body_lines(&self) -> Vec<String> {
+        let width = self.window.width.max(1);
+        match self.posts.get(self.current) {
+            Some(post) => post.4.split('\n').flat_map(|line| wrap_text(line, width, width)).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+
+impl View for ThreadView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.subject));
+        output.push_str(&render_error(&self.error));
+
+        if let Some((_, parent_id, author, created_date, _)) = self.posts.get(self.current) {
+            output.push_str(&format!("\x1b[1;33m#{}/{} {}\x1b[0m [{}]", self.current + 1, self.posts.len(), author, created_date));
+            let parent = parent_id.and_then(|parent_id| self.posts.iter().position(|post| post.0 == parent_id));
+            if let Some(parent) = parent {
+                output.push_str(&format!(" in reply to #{} by {}", parent + 1, self.posts[parent].2));
+            }
+            output.push_str("\r\n\r\n");
+
+            let rows = window.rows_after(RESERVED_ROWS);
+            let lines = self.body_lines();
+            for line in lines.iter().skip(self.scroll).take(rows) {
+                output.push_str(&render_body_line(line));
+            }
+            if self.scroll + rows < lines.len() {
+                output.push_str("\x1b[2m... ↓ (Arrow Down) for more\x1b[0m\r\n");
+            }
+        }
+
+        output.push_str("\n← / → previous / next post, ↑ / ↓ scroll\r\n[R] Reply to this post\r\n[CNTRL+Q] Back to threads\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_posts(self.thread_id) {
+            Ok(posts) => self.posts = posts,
+            Err(e) => self.error = Some(e.report("loading posts")),
+        }
+        if self.current >= self.posts.len() {
+            self.current = self.posts.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.window = *window;
+        self.scroll = 0;
+    }
+
+    fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
+        self.error = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::ThreadsView { board_id: self.board_id };
+            return Events::NavigateView;
+        }
+        else if event == Events::RightArrow && self.current + 1 < self.posts.len() {
+            self.show_post(self.current + 1);
+        }
+        else if event == Events::LeftArrow && self.current > 0 {
+            self.show_post(self.current - 1);
+        }
+        else if event == Events::UpArrow && self.scroll > 0 {
+            self.scroll -= 1;
+        }
+        else if event == Events::DownArrow && self.scroll + self.window.rows_after(RESERVED_ROWS) < self.body_lines().len() {
+            self.scroll += 1;
+        }
+        else if event == Events::KeyR && !permissions::can(self.user_id, Action::PostToBoard) {
+            self.error = Some(Action::PostToBoard.denied_message().to_string());
+        }
+        else if event == Events::KeyR && !self.posts.is_empty() {
+            self.navigate_to = NavigateTo::ComposeView {
+                board_id: self.board_id,
+                thread_id: Some(self.thread_id),
+                reply_to: Some(self.posts[self.current].0),
+            };
+            return Events::NavigateView;
+        }
+        Events::Unknown
+    }
+}
@@ -0,0 +1,145 @@
+use std::any::Any;
+use crate::db::manage::{Manager, ThreadRow};
+use crate::input_interface::Events;
+use crate::permissions::{self, Action};
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
+
+// rows used by the title and the help text below the thread list
+const RESERVED_ROWS: usize = 9;
+
+
+/**
+* Lists the threads of a board, the most recently active first, marking those with unread posts
+*/
+pub struct ThreadsView {
+    board_id: i32,
+    board_name: String,
+    user_id: i32,
+    navigate_to: NavigateTo,
+    threads: Vec<ThreadRow>,
+    selected_index: usize,
+    offset: i32,
+    page_size: i32,
+    error: Option<String>,
+}
+
+
+impl ThreadsView {
+    pub fn new(board_id: i32, user_id: i32, window: &WindowSize) -> Self {
+        let mut view = Self {
+            board_id,
+            board_name: String::new(),
+            user_id,
+            navigate_to: NavigateTo::NoneView,
+            threads: Vec::new(),
+            selected_index: 0,
+            offset: 0,
+            page_size: window.page_size(RESERVED_ROWS),
+            error: None,
+        };
+        match Manager::get_board_name(board_id) {
+            Ok(name) => view.board_name = name,
+            Err(e) => view.error = Some(e.report("loading a board")),
+        }
+        view.refresh_data();
+        view
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.threads.len() {
+            self.selected_index += 1;
+        }
+    }
+}
+
+
+impl View for ThreadsView {
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_navigate_to(&self) -> &NavigateTo {
+        &self.navigate_to
+    }
+
+    fn render(&self, _window: &WindowSize) -> String {
+        let mut output = String::from("\x1b[2J\x1b[H");
+        output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.board_name));
+        output.push_str(&render_error(&self.error));
+
+        if self.threads.is_empty() {
+            output.push_str("No threads yet, start one with [N].\r\n");
+        }
+        for (idx, (_, subject, author, replies, last_post_date, is_new)) in self.threads.iter().enumerate() {
+            let marker = if *is_new { "\x1b[1;36m*\x1b[0m" } else { " " };
+            let line = format!("{}  by {}, {} replies, last post {}", subject, author, replies, last_post_date);
+            if idx == self.selected_index {
+                output.push_str(&format!("{} \x1b[1;33m> {}\x1b[0m\r\n", marker, line));
+            } else {
+                output.push_str(&format!("{}   {}\r\n", marker, line));
+            }
+        }
+
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to read a thread, ← / → to change page, * marks unread posts\r\n");
+        output.push_str("[N] New thread\r\n[CNTRL+Q] Back to boards\r\n");
+        output
+    }
+
+    fn refresh_data(&mut self) {
+        match Manager::get_threads(self.board_id, self.user_id, self.offset, self.page_size) {
+            Ok(threads) => self.threads = threads,
+            Err(e) => self.error = Some(e.report("loading threads")),
+        }
+        if self.selected_index >= self.threads.len() {
+            self.selected_index = self.threads.len().saturating_sub(1);
+        }
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
+    fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
+        self.error = None;
+        if event == Events::CntrlQ {
+            self.navigate_to = NavigateTo::BoardsView;
+            return Events::NavigateView;
+        }
+        else if event == Events::UpArrow {
+            self.move_up();
+        }
+        else if event == Events::DownArrow {
+            self.move_down();
+        }
+        else if event == Events::RightArrow && self.threads.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Enter && !self.threads.is_empty() {
+            self.navigate_to = NavigateTo::ThreadView { thread_id: self.threads[self.selected_index].0, post_id: None };
+            return Events::NavigateView;
+        }
+        else if event == Events::KeyN && !permissions::can(self.user_id, Action::PostToBoard) {
+            self.error = Some(Action::PostToBoard.denied_message().to_string());
+        }
+        else if event == Events::KeyN {
+            self.navigate_to = NavigateTo::ComposeView { board_id: self.board_id, thread_id: None, reply_to: None };
+            return Events::NavigateView;
+        }
+        Events::Unknown
+    }
+}
@@ -2,35 +2,93 @@ use std::any::Any;
 use std::collections::HashMap;
 use crate::db::manage::Manager;
 use crate::input_interface::Events;
-use crate::views::base_view::{NavigateTo, View};
+use crate::presence::{self, SessionId, SessionInfo};
+use crate::views::base_view::{format_duration, render_error, NavigateTo, View, WindowSize};
 
 pub struct UserView{
     user_id: i32,
     navigate_to: NavigateTo,
     is_current_user: bool,
-    user_data: HashMap<String, String>
+    user_data: HashMap<String, String>,
+    error: Option<String>,
+    // the session looking at this view
+    session_id: SessionId,
+    viewing_sessions: bool,
+    sessions: Vec<SessionInfo>,
+    selected_index: usize,
+    session_to_end: Option<SessionId>,
 }
 
+
 impl UserView {
-    pub fn new(user_id: i32, is_current_user: bool) -> Self {
-        let user_data = Manager::get_user(user_id);
-        Self {
+    pub fn new(user_id: i32, is_current_user: bool, session_id: SessionId) -> Self {
+        let mut view = Self {
             user_id,
             navigate_to: NavigateTo::NoneView,
             is_current_user,
-            user_data
-        }
+            user_data: HashMap::new(),
+            error: None,
+            session_id,
+            viewing_sessions: false,
+            sessions: Vec::new(),
+            selected_index: 0,
+            session_to_end: None,
+        };
+        view.refresh_data();
+        view
     }
 
     pub fn get_user_id(&self) -> i32{
         self.user_id
     }
+
+    /**
+    * the session picked with [E] on the sessions screen
+    */
+    pub fn get_session_to_end(&self) -> Option<SessionId> {
+        self.session_to_end
+    }
+
+    fn move_up(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+        }
+    }
+
+    fn move_down(&mut self) {
+        if self.selected_index + 1 < self.sessions.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    fn render_sessions(&self) -> String {
+        let mut output = String::from("\x1b[1;32mMy sessions\x1b[0m\r\n\r\n");
+        for (idx, session) in self.sessions.iter().enumerate() {
+            let this_session = if session.session_id == self.session_id { " (this session)" } else { "" };
+            let line = format!(
+                "#{} from {}, connected {}, idle {}{}",
+                session.session_id,
+                session.remote_addr,
+                format_duration(session.connected_for),
+                format_duration(session.idle_for),
+                this_session
+            );
+            if idx == self.selected_index {
+                output.push_str(&format!("\x1b[1;33m> {}\x1b[0m\r\n", line));
+            }
+            else {
+                output.push_str(&format!("  {}\r\n", line));
+            }
+        }
+        output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) to select a session\r\n[E] End session\r\n[CNTRL+Q] Back\r\n");
+        output
+    }
 }
 
 impl View for UserView {
 
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
@@ -38,14 +96,21 @@ impl View for UserView {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, _window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H");
+        if self.viewing_sessions {
+            output.push_str(&self.render_sessions());
+            return output;
+        }
+
         if self.is_current_user {
             output.push_str("\x1b[1;32mMe\x1b[0m\r\n\r\n");
         }
         else {
-            output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", self.user_data["username"]));
+            let username = self.user_data.get("username").map(|name| name.as_str()).unwrap_or("User");
+            output.push_str(&format!("\x1b[1;32m{}\x1b[0m\r\n\r\n", username));
         }
+        output.push_str(&render_error(&self.error));
 
 
             // Append sorted rooms to output
@@ -59,11 +124,14 @@ impl View for UserView {
                     output.push_str("offline: ⚪️\r\n");
                 }
             }
-            else {
-                output.push_str(&format!("{}: {}\r\n", key, value));
+            // profile fields nobody filled in are left out
+            else if !value.is_empty() {
+                output.push_str(&format!("{}: {}\r\n", key.replace('_', " "), value));
             }
         }
         if self.is_current_user {
+            output.push_str("\n[M] My sessions\r\n[A] Account settings\r\n[H / CNTRL+Q] Home\r\n");
+        } else if self.error.is_some() {
             output.push_str("\n[H / CNTRL+Q] Home\r\n");
         } else {
             output.push_str("\n[S] Send Message\r\n[H / CNTRL+Q] Home\r\n");
@@ -71,13 +139,65 @@ impl View for UserView {
         output
     }
 
+    fn refresh_data(&mut self) {
+        match Manager::get_user(self.user_id) {
+            Ok(mut user_data) => {
+                // online while the user has any session open
+                let online = if presence::is_online(self.user_id) { "1" } else { "0" };
+                user_data.insert("online".to_string(), online.to_string());
+                self.user_data = user_data;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.report("loading a user")),
+        }
+        if self.is_current_user {
+            self.sessions = presence::sessions_for_user(self.user_id);
+            if self.selected_index >= self.sessions.len() {
+                self.selected_index = self.sessions.len().saturating_sub(1);
+            }
+        }
+    }
+
+    fn follows_presence(&self) -> bool {
+        true
+    }
+
 
     fn handle_event(&mut self, event: Events, _buffer_string: String) -> Events {
         let result_event: Events;
 
-        if event == Events::KeyS {
+        if self.viewing_sessions {
+            if event == Events::UpArrow {
+                self.move_up();
+            }
+            else if event == Events::DownArrow {
+                self.move_down();
+            }
+            else if event == Events::CntrlQ {
+                self.viewing_sessions = false;
+            }
+            // this session is ended with Quit from the menu
+            else if event == Events::KeyE {
+                if let Some(session) = self.sessions.get(self.selected_index) {
+                    if session.session_id != self.session_id {
+                        self.session_to_end = Some(session.session_id);
+                        return Events::EndSession;
+                    }
+                }
+            }
+            return Events::Unknown;
+        }
+
+        if event == Events::KeyS && self.error.is_none() && !self.is_current_user {
             self.navigate_to = NavigateTo::DirectMessageView;
             result_event = Events::NavigateView
+        } else if event == Events::KeyM && self.is_current_user {
+            self.viewing_sessions = true;
+            self.refresh_data();
+            result_event = Events::Unknown;
+        } else if event == Events::KeyA && self.is_current_user {
+            self.navigate_to = NavigateTo::AccountView;
+            result_event = Events::NavigateView;
         } else if event == Events::KeyH || event == Events::CntrlQ {
             self.navigate_to = NavigateTo::MenuView;
             result_event = Events::NavigateView;
@@ -88,4 +208,4 @@ impl View for UserView {
         result_event
     }
 
-}
\ No newline at end of file
+}
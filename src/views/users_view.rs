@@ -1,36 +1,84 @@
 use std::any::Any;
-use crate::views::base_view::{NavigateTo, View};
+use std::collections::HashMap;
+use crate::views::base_view::{render_error, NavigateTo, View, WindowSize};
 use crate::input_interface::Events;
 use crate::db::manage::Manager;
+use crate::db::error::DbResult;
+use crate::presence;
+
+// rows used by the title and the help text below the user list
+const RESERVED_ROWS: usize = 7;
 
 
 pub struct UsersView {
-    users: Vec<(String, bool)>,
+    user_id: i32,
+    // (id, username, online)
+    users: Vec<(i32, String, bool)>,
+    // unread direct messages by sender
+    unread: HashMap<i32, i64>,
+    offset: i32,
+    page_size: i32,
     navigate_to: NavigateTo,
     selecting_user: bool,
     searching_user: bool,
     selected_index: usize,
+    selected_user_id: i32,
     query: String,
+    // the users are filtered by this name while it is not empty
+    search: String,
+    error: Option<String>,
 }
 
 impl UsersView {
 
-    pub fn new() -> Self {
-        let users = Manager::get_online_users(0);
+    pub fn new(user_id: i32, window: &WindowSize) -> Self {
+        let page_size = window.page_size(RESERVED_ROWS);
+        let (users, error) = match Self::load_users(None, 0, page_size) {
+            Ok(users) => (users, None),
+            Err(e) => (Vec::new(), Some(e.report("loading users"))),
+        };
         let navigate_to: NavigateTo = NavigateTo::NoneView;
         let selecting_user = true;
         let searching_user = false;
-        Self {
+        let mut view = Self {
+            user_id,
             users,
+            unread: HashMap::new(),
+            offset: 0,
+            page_size,
             navigate_to,
             selecting_user,
             searching_user,
             selected_index: 0,
+            selected_user_id: -1,
             query: String::new(),
+            search: String::new(),
+            error,
+        };
+        view.load_unread();
+        view
+    }
+
+    fn load_unread(&mut self) {
+        match Manager::get_dm_unread_counts(self.user_id) {
+            Ok(unread) => self.unread = unread,
+            Err(e) => self.error = Some(e.report("counting unread messages")),
         }
     }
 
 
+    /**
+    * loads a page of users, or the users matching `query`, marking the ones with an open session
+    */
+    fn load_users(query: Option<&str>, offset: i32, page_size: i32) -> DbResult<Vec<(i32, String, bool)>> {
+        let online = presence::online_user_ids();
+        let users = match query {
+            Some(query) => Manager::search_users(query.to_string(), offset, page_size)?,
+            None => Manager::get_users(&online.iter().copied().collect::<Vec<i32>>(), offset, page_size)?,
+        };
+        Ok(users.into_iter().map(|(user_id, name)| (user_id, name, online.contains(&user_id))).collect())
+    }
+
     fn move_up(&mut self) {
         if self.selected_index > 0 {
             self.selected_index -= 1;
@@ -45,8 +93,11 @@ impl UsersView {
         }
     }
 
-    pub fn get_selection(&self) -> &str {
-        &self.users[self.selected_index].0
+    /**
+    * the id of the user picked with Enter
+    */
+    pub fn get_selected_user_id(&self) -> i32 {
+        self.selected_user_id
     }
 
 
@@ -56,7 +107,7 @@ impl UsersView {
 impl View for UsersView {
 
 
-    fn as_any(&self) -> &(dyn Any) {
+    fn as_any(&self) -> &dyn Any {
         self
     }
 
@@ -65,9 +116,14 @@ impl View for UsersView {
         &self.navigate_to
     }
 
-    fn render(&self) -> String {
+    fn render(&self, _window: &WindowSize) -> String {
         let mut output = String::from("\x1b[2J\x1b[H");
-        output.push_str("\x1b[1;32mUsers\x1b[0m\r\n\r\n");
+        if self.search.is_empty() {
+            output.push_str("\x1b[1;32mUsers\x1b[0m\r\n\r\n");
+        } else {
+            output.push_str(&format!("\x1b[1;32mUsers matching {}\x1b[0m\r\n\r\n", self.search));
+        }
+        output.push_str(&render_error(&self.error));
 
 
         if self.searching_user {
@@ -75,36 +131,53 @@ impl View for UsersView {
             output.push_str(self.query.as_str());
         } else if self.selecting_user {
             // Append sorted rooms to output
-            for (index, (user, online)) in self.users.iter().enumerate() {
-                let online_emoji;
-                if *online {
-                    online_emoji = "🟢 online"
+            for (index, (user_id, user, online)) in self.users.iter().enumerate() {
+                let online_emoji = if *online {
+                    "🟢 online"
                 }
                 else {
-                    online_emoji = "⚪️ offline"
-                }
+                    "⚪️ offline"
+                };
+                let unread = match self.unread.get(user_id) {
+                    Some(unread) => format!(", {} unread", unread),
+                    None => String::new(),
+                };
                 if index == self.selected_index {
-                    output.push_str(&format!("\x1b[1;33m> {}: {}\x1b[0m\r\n", user, online_emoji));
+                    output.push_str(&format!("\x1b[1;33m> {}: {}{}\x1b[0m\r\n", user, online_emoji, unread));
                 }
                 else {
-                    output.push_str(&format!("  {}: {}\r\n", user, online_emoji));
+                    output.push_str(&format!("  {}: {}{}\r\n", user, online_emoji, unread));
                 }
             }
-            output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to select a user\r\n[S] Search for a user\r\n[N] Next Page\r\n[H / CNTRL+Q] Home\r\n");
+            output.push_str("\nUse ↑ (Arrow Up) / ↓ (Arrow Down) and Enter to select a user, ← / → to change page\r\n[S] Search for a user\r\n[H / CNTRL+Q] Home\r\n");
         }
         output
     }
 
     fn refresh_data(&mut self) {
-        if !self.query.is_empty() {
-            self.users = Manager::search_users(self.query.clone());
+        let search = if self.search.is_empty() { None } else { Some(self.search.as_str()) };
+        match Self::load_users(search, self.offset, self.page_size) {
+            Ok(users) => {
+                self.users = users;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.report("loading users")),
         }
-
-        else {
-            self.users = Manager::get_online_users(0);
+        self.load_unread();
+        if self.selected_index >= self.users.len() {
+            self.selected_index = self.users.len().saturating_sub(1);
         }
     }
 
+    fn follows_presence(&self) -> bool {
+        true
+    }
+
+    fn resize(&mut self, window: &WindowSize) {
+        self.page_size = window.page_size(RESERVED_ROWS);
+        self.refresh_data();
+    }
+
 
 
     fn handle_event(&mut self, event: Events, buffer_string: String) -> Events {
@@ -116,7 +189,18 @@ impl View for UsersView {
         else if event == Events::DownArrow && !self.searching_user {
             self.move_down();
         }
-        else if event == Events::Enter && !self.searching_user {
+        else if event == Events::RightArrow && !self.searching_user && self.users.len() == self.page_size as usize {
+            self.offset += self.page_size;
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::LeftArrow && !self.searching_user && self.offset > 0 {
+            self.offset = (self.offset - self.page_size).max(0);
+            self.selected_index = 0;
+            self.refresh_data();
+        }
+        else if event == Events::Enter && !self.searching_user && !self.users.is_empty() {
+            self.selected_user_id = self.users[self.selected_index].0;
             self.navigate_to = NavigateTo::UserView;
             result_event = Events::NavigateView;
         }
@@ -142,15 +226,24 @@ impl View for UsersView {
             self.selecting_user = true;
             self.searching_user = false;
             self.query = String::new();
+            // leaving the search prompt shows all users again
+            if !self.search.is_empty() {
+                self.search.clear();
+                self.offset = 0;
+                self.refresh_data();
+            }
             result_event = Events::InputModeDisable
         }
 
 
-        else if self.searching_user && buffer_string != ""  && event != Events::Enter {
+        else if self.searching_user && !buffer_string.is_empty()  && event != Events::Enter {
              self.query = buffer_string;
         }
 
-        else if self.searching_user && self.query != ""  && event == Events::Enter {
+        else if self.searching_user && !self.query.is_empty()  && event == Events::Enter {
+            self.search = self.query.trim().to_string();
+            self.offset = 0;
+            self.selected_index = 0;
             self.refresh_data();
             self.query = String::new();
             self.searching_user = false;